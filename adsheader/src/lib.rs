@@ -0,0 +1,107 @@
+use std::{num::NonZeroU32, path::PathBuf};
+
+use core::{
+    ads::{self, AdsHeader},
+    batch::JobArgs,
+    clap::{self, Parser},
+    input::InputArgs,
+    log::{debug, error, info},
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    input: PathBuf,
+    channels: NonZeroU32,
+    sample_rate: NonZeroU32,
+    interleave: u32,
+    format: u32,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+
+    if args.channels.get() == 1 && args.interleave != 0 {
+        error!("If there is only 1 channel, then interleave cannot be 0");
+        return;
+    }
+    if args.format != ads::CODEC_PCM16 && args.format != ads::CODEC_VAG {
+        error!("Only two formats are supported: 1 (PCM_LE16) and 16 (SONY_4BIT_ADPCM)");
+        return;
+    }
+
+    let output = Output::new(&args.output, &args.input);
+
+    let files = core::get_files(&args.input, &args.files, &[]);
+    core::batch::run(files, args.batch.jobs, |file_path| {
+        info!("Doing {file_path:?}");
+
+        let mut file = match std::fs::read(file_path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Unable to load file `{file_path:?}`, skipping: {e}");
+                return;
+            }
+        };
+        debug!("File length: {}", file.len());
+        add_header(&args, &mut file);
+
+        let Some(output_path) = output.file(&file_path.with_extension("ads")) else {
+            return;
+        };
+        debug!("Writing to {output_path:?}");
+        std::fs::write(&output_path, &file).unwrap_or_else(|e| {
+            error!("Unable to write to file `{output_path:?}`: {e}");
+        });
+    });
+}
+
+fn add_header(args: &Args, file: &mut Vec<u8>) {
+    let header = AdsHeader {
+        codec: args.format,
+        sample_rate: args.sample_rate.get(),
+        channels: args.channels.get(),
+        interleave: args.interleave,
+        loop_start: None,
+        loop_end: None,
+        body_size: file.len() as u32,
+    };
+    report::set("header", &header);
+    let mut bytes = vec![];
+    header.write(&mut bytes);
+    file.splice(0..0, bytes);
+}
+
+#[test]
+fn adsheader_test() {
+    let args = Args {
+        input: PathBuf::default(),
+        channels: NonZeroU32::new(1).unwrap(),
+        sample_rate: NonZeroU32::new(8000).unwrap(),
+        interleave: 0,
+        format: 16,
+        output: OutputArgs::default(),
+        files: InputArgs::default(),
+        batch: JobArgs::default(),
+        report: ReportArgs::default(),
+    };
+    let mut file = include_bytes!("../tests/vag_adpcm.bin").to_vec();
+    add_header(&args, &mut file);
+
+    assert_eq!(file[0], 0x53);
+    assert_eq!(file[1], 0x53);
+    assert_eq!(file[2], 0x68);
+    assert_eq!(file[3], 0x64);
+    assert_eq!(file.len(), 0x410 + 40);
+}
@@ -0,0 +1,221 @@
+use std::{fs::File, io::Write, num::NonZeroUsize, path::PathBuf, process::exit, sync::Mutex};
+
+use core::{
+    adpcm,
+    ads::{self, AdsHeader},
+    clap::{self, CommandFactory, FromArgMatches, Parser},
+    input::InputArgs,
+    log::{error, info, warn},
+    report::{self, ReportArgs},
+    vag, Reader,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// The `ads` file to find loops in
+    ads_input: PathBuf,
+    /// The file to write the loop locations to, writes to STDOUT otherwise.
+    #[clap(long)]
+    list_file: Option<PathBuf>,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Parses the arguments of the standalone binary, which also accepts the old
+/// `-o`/`--output` spelling of `--list-file`.
+#[must_use]
+pub fn parse_legacy() -> Args {
+    Args::from_arg_matches(&legacy_command().get_matches()).unwrap_or_else(|e| e.exit())
+}
+
+fn legacy_command() -> clap::Command {
+    Args::command().mut_arg("list_file", |arg| arg.short_alias('o').alias("output"))
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+
+    let files = core::get_files(&args.ads_input, &args.files, &["ads"]);
+
+    let file = Mutex::new(args.list_file.map(|path| {
+        File::create(&path).unwrap_or_else(|e| {
+            error!("Unable to create file {path:?}: {e}");
+            exit(1)
+        })
+    }));
+
+    // One at a time, to keep the loops in order
+    core::batch::run(files, NonZeroUsize::MIN, |path| {
+        info!("Finding in {path:?}");
+
+        let contents = match std::fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                error!("Unable to read file {path:?}, skipping: {e}");
+                return;
+            }
+        };
+        let loops = match find_loops(&contents) {
+            Ok(loops) => loops,
+            Err(e) => {
+                error!("Unable to parse {path:?}, skipping: {e}");
+                return;
+            }
+        };
+        if let Some((loop_begin, loop_end)) = loops {
+            report::set("loop_start", loop_begin);
+            report::set("loop_end", loop_end);
+            let text = format!(
+                "{loop_begin} {loop_end} {}",
+                path.with_extension("wav")
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+            );
+            if let Some(file) = file.lock().unwrap().as_mut() {
+                write!(file, "{text}\r\n").unwrap();
+            } else {
+                info!("{text}");
+            }
+        }
+    });
+}
+
+/// Finds the first and last sample of the loop of each channel.
+///
+/// ADPCM streams loop from the block flagged `6` to the next block with the
+/// end and repeat bits set, or to the end of the channel when there is none.
+/// When there are no flags, and for PCM16 streams, the loop points of the
+/// header are used instead.
+pub fn find_loops(ads_file: &[u8]) -> core::Result<Option<(u32, u32)>> {
+    let mut reader = Reader::new(ads_file);
+    let header = AdsHeader::parse(&mut reader)?;
+    report::set("header", &header);
+    let header_loop = header.loop_start.zip(header.loop_end);
+
+    match header.codec {
+        ads::CODEC_VAG => {}
+        ads::CODEC_PCM16 => return Ok(header_loop),
+        _ => return Ok(None),
+    }
+
+    let body = reader.remaining();
+    let body = &body[..body.len().min(header.body_size as usize)];
+    let loops = adpcm::deinterleave(body, header.channels as usize, header.interleave as usize)
+        .iter()
+        .map(|blocks| channel_loop(blocks))
+        .collect::<Vec<_>>();
+    if loops.iter().any(|channel| *channel != loops[0]) {
+        warn!("The channels loop differently, only the first one's loop is used");
+    }
+
+    let samples = vag::SAMPLES_PER_BLOCK as u32;
+    Ok(loops[0].or_else(|| {
+        header_loop.map(|(start, end)| {
+            (
+                start.saturating_mul(samples),
+                end.saturating_add(1).saturating_mul(samples) - 1,
+            )
+        })
+    }))
+}
+
+/// The first and last sample of the loop in the blocks of a channel.
+fn channel_loop(blocks: &[u8]) -> Option<(u32, u32)> {
+    let flags = blocks
+        .chunks_exact(vag::BLOCK_SIZE)
+        .map(|block| block[1])
+        .collect::<Vec<_>>();
+    let start = flags
+        .iter()
+        .position(|flag| *flag == vag::FLAG_LOOP_START)?;
+    let loop_end_bits = adpcm::FLAG_BIT_END | adpcm::FLAG_BIT_REPEAT;
+    let end = match flags[start..]
+        .iter()
+        .position(|flag| flag & loop_end_bits == loop_end_bits)
+    {
+        // A block flagged `7` isn't played, so the loop ends before it.
+        Some(offset) if flags[start + offset] == vag::FLAG_END => start + offset,
+        Some(offset) => start + offset + 1,
+        None => flags.len(),
+    };
+
+    let samples = vag::SAMPLES_PER_BLOCK;
+    Some(((start * samples) as u32, (end * samples) as u32 - 1))
+}
+
+#[test]
+fn without_loop() {
+    let loops = find_loops(include_bytes!("../tests/withoutloop.ads"));
+    assert_eq!(loops, Ok(None));
+}
+
+#[test]
+fn with_loop() {
+    let loops = find_loops(include_bytes!("../tests/withloop.ads"));
+    assert_eq!(loops, Ok(Some((896, 1791))));
+}
+
+#[test]
+fn with_loop_multichannel() {
+    let loops = find_loops(include_bytes!("../tests/multichannel.ads"));
+    assert_eq!(loops, Ok(Some((448, 895))));
+}
+
+#[test]
+fn truncated() {
+    let file = include_bytes!("../tests/withloop.ads");
+    assert!(matches!(
+        find_loops(&file[0..0x20]),
+        Err(core::Error::Truncated { offset: 0x20, .. })
+    ));
+}
+
+#[test]
+fn loop_end_flag() {
+    let mut file = include_bytes!("../tests/withloop.ads").to_vec();
+    let flag = |block: usize| ads::HEADER_SIZE + block * vag::BLOCK_SIZE + 1;
+    file[flag(63)] = 2;
+    file[flag(40)] = vag::FLAG_LOOP_END;
+    assert_eq!(find_loops(&file), Ok(Some((896, 41 * 28 - 1))));
+
+    // Stops before an end block, and at the end of the channel without one.
+    file[flag(40)] = vag::FLAG_END;
+    assert_eq!(find_loops(&file), Ok(Some((896, 40 * 28 - 1))));
+    file[flag(40)] = 2;
+    assert_eq!(find_loops(&file), Ok(Some((896, 64 * 28 - 1))));
+}
+
+#[test]
+fn header_loops() {
+    let header = AdsHeader {
+        codec: ads::CODEC_PCM16,
+        sample_rate: 48000,
+        channels: 2,
+        interleave: 0x200,
+        loop_start: Some(100),
+        loop_end: Some(199),
+        body_size: 0x400,
+    };
+    let mut file = vec![];
+    header.write(&mut file);
+    file.resize(file.len() + 0x400, 0);
+    assert_eq!(find_loops(&file), Ok(Some((100, 199))));
+
+    file[8] = ads::CODEC_VAG as u8;
+    assert_eq!(find_loops(&file), Ok(Some((2800, 5599))));
+}
+
+#[test]
+fn legacy_spelling() {
+    let matches = legacy_command()
+        .try_get_matches_from(["adsloopfind", "a.ads", "-o", "loops.txt"])
+        .unwrap();
+    let args = Args::from_arg_matches(&matches).unwrap();
+    assert!(args.list_file.is_some());
+    assert!(Args::try_parse_from(["adsloopfind", "a.ads", "-o", "loops.txt"]).is_err());
+}
@@ -0,0 +1,212 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use core::{
+    adpcm,
+    ads::{self, AdsHeader},
+    batch::JobArgs,
+    clap::{self, Parser},
+    input::InputArgs,
+    log::{error, info},
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    vag, Reader,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// The `ads` file to read from.
+    input: PathBuf,
+    /// Sets a loop starting at this sample instead of removing the loops,
+    /// rounded down to a whole block.
+    #[clap(long, requires = "loop_end")]
+    loop_start: Option<usize>,
+    /// The sample just after the end of the loop to set, rounded up to a whole block.
+    #[clap(long, requires = "loop_start")]
+    loop_end: Option<usize>,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+    let output = Output::new(&args.output, &args.input);
+
+    let files = core::get_files(&args.input, &args.files, &["ads"]);
+    core::batch::run(files, args.batch.jobs, |file_path| {
+        info!("Unlooping {file_path:?}");
+
+        let mut ads_bytes = match std::fs::read(file_path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                error!("Unable to read file {file_path:?}, skipping: {e}");
+                return;
+            }
+        };
+
+        if let (Some(start), Some(end)) = (args.loop_start, args.loop_end) {
+            let Some(looped) = set_loop(&mut ads_bytes, start..end) else {
+                return;
+            };
+            if write(&output, file_path, "looped", &ads_bytes) {
+                info!("Looped samples {} to {}", looped.start, looped.end);
+            }
+            return;
+        }
+
+        let Some(changed_chunks) = unloop(&mut ads_bytes) else {
+            return;
+        };
+
+        if changed_chunks == 0 {
+            info!("No markers found");
+        } else if write(&output, file_path, "unlooped", &ads_bytes) {
+            info!("{changed_chunks} markers removed");
+        }
+    });
+}
+
+/// Writes the edited file next to the input with a suffix, returning whether it was written.
+fn write(output: &Output, file_path: &Path, suffix: &str, ads_bytes: &[u8]) -> bool {
+    let out_path = format!(
+        "{}_{suffix}.ads",
+        file_path.with_extension("").to_string_lossy()
+    );
+    let Some(out_path) = output.file(Path::new(&out_path)) else {
+        return false;
+    };
+    if let Err(e) = std::fs::write(&out_path, ads_bytes) {
+        error!("Unable to write {out_path:?}: {e}");
+        return false;
+    }
+    true
+}
+
+/// Parses the header and returns it with the offset of the body, if the
+/// body is Sony 4-bit ADPCM.
+fn parse(ads_bytes: &[u8]) -> Option<(AdsHeader, usize)> {
+    let mut reader = Reader::new(ads_bytes);
+    let header = match AdsHeader::parse(&mut reader) {
+        Ok(header) => header,
+        Err(e) => {
+            error!("Invalid header, skipping: {e}");
+            return None;
+        }
+    };
+    report::set("header", &header);
+
+    if header.codec != ads::CODEC_VAG {
+        error!("Invalid codec, only support Sony 4-bit ADPCM, skipping");
+        return None;
+    }
+    Some((header, reader.position()))
+}
+
+/// Returns the number of changed chunks
+fn unloop(ads_bytes: &mut [u8]) -> Option<usize> {
+    let (header, body_start) = parse(ads_bytes)?;
+    let mut changed_chunks = 0;
+    for chunk in ads_bytes[body_start..].chunks_mut(vag::BLOCK_SIZE) {
+        if vag::remove_loop(chunk) {
+            changed_chunks += 1;
+        }
+    }
+    // The loop points of the header are a marker too.
+    if header.loop_start.is_some() || header.loop_end.is_some() {
+        ads::write_loop(ads_bytes, None);
+        changed_chunks += 1;
+    }
+
+    report::set("markers_removed", changed_chunks);
+    Some(changed_chunks)
+}
+
+/// Sets the loop of every channel, returning the samples actually looped.
+fn set_loop(ads_bytes: &mut [u8], range: Range<usize>) -> Option<Range<usize>> {
+    let (header, body_start) = parse(ads_bytes)?;
+    let looped = match adpcm::set_loop(
+        &mut ads_bytes[body_start..],
+        header.channels as usize,
+        header.interleave as usize,
+        range,
+    ) {
+        Ok(looped) => looped,
+        Err(e) => {
+            error!("Unable to set the loop, skipping: {e}");
+            return None;
+        }
+    };
+
+    // The header counts the blocks of each channel, with the end inclusive.
+    let samples = vag::SAMPLES_PER_BLOCK;
+    ads::write_loop(
+        ads_bytes,
+        Some((
+            (looped.start / samples) as u32,
+            (looped.end / samples - 1) as u32,
+        )),
+    );
+
+    report::set("loop_start", looped.start);
+    report::set("loop_end", looped.end);
+    Some(looped)
+}
+
+#[test]
+fn with_loop() {
+    let mut file = include_bytes!("../tests/withloop.ads").to_vec();
+    assert_eq!(unloop(&mut file), Some(64));
+}
+
+#[test]
+fn without_loop() {
+    let mut file = include_bytes!("../tests/withoutloop.ads").to_vec();
+    assert_eq!(unloop(&mut file), Some(2));
+}
+
+#[test]
+fn invalid() {
+    let mut file = include_bytes!("../tests/pcm.ads").to_vec();
+    assert_eq!(unloop(&mut file), None);
+}
+
+#[test]
+fn loop_editing() {
+    let mut file = include_bytes!("../tests/withoutloop.ads").to_vec();
+    assert_eq!(set_loop(&mut file, 100..1000), Some(84..1008));
+    let header = AdsHeader::parse(&mut Reader::new(&file)).unwrap();
+    let decoded = adpcm::decode_interleaved(
+        &file[ads::HEADER_SIZE..],
+        header.channels as usize,
+        header.interleave as usize,
+    );
+    for channel in decoded {
+        assert_eq!(channel.loop_start, Some(84));
+        assert_eq!(channel.loop_end, Some(1008));
+    }
+    assert_eq!((header.loop_start, header.loop_end), (Some(3), Some(35)));
+
+    let mut file = include_bytes!("../tests/pcm.ads").to_vec();
+    assert_eq!(set_loop(&mut file, 100..1000), None);
+}
+
+#[test]
+fn header_loop() {
+    // adsloopfind reads the header when there are no flags, so unlooping
+    // clears its loop too.
+    let mut file = include_bytes!("../tests/withloop.ads").to_vec();
+    ads::write_loop(&mut file, Some((32, 63)));
+    assert_eq!(adsloopfind::find_loops(&file), Ok(Some((896, 1791))));
+    assert_eq!(unloop(&mut file), Some(65));
+    assert_eq!(adsloopfind::find_loops(&file), Ok(None));
+}
@@ -0,0 +1,150 @@
+use core::cds::{self, Token};
+use core::clap::{self, Parser};
+use core::log::{debug, error, info};
+use core::{
+    batch::JobArgs,
+    input::InputArgs,
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    seq::{self, SeqFile},
+    Reader,
+};
+use std::path::{Path, PathBuf};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// `cds` file to read
+    input: PathBuf,
+    /// Also convert the sequence to a MIDI file.
+    #[clap(long)]
+    midi: bool,
+    /// What to convert the CDS meta commands into.
+    #[clap(long, value_enum, default_value_t)]
+    commands: cds::Commands,
+    /// How to write the loops of the sequence.
+    #[clap(long, value_enum, default_value_t)]
+    loops: cds::Loops,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+
+    let output = Output::new(&args.output, &args.input);
+
+    let files = core::get_files(&args.input, &args.files, &["cds"]);
+    core::batch::run(files, args.batch.jobs, |file_path| {
+        convert_file(file_path, &output, args.commands, args.loops, args.midi);
+    });
+}
+
+/// Converts one CDS file to SEQ, writing loops and meta commands as
+/// `loops` and `commands` say, and also to MIDI if `midi` is set.
+pub fn convert_file(
+    file_path: &Path,
+    output: &Output,
+    commands: cds::Commands,
+    loops: cds::Loops,
+    midi: bool,
+) {
+    let contents = match std::fs::read(file_path) {
+        Ok(file) => file,
+        Err(e) => {
+            error!("Unable to load {file_path:?}, skipping: {e}");
+            return;
+        }
+    };
+    let mut reader = Reader::new(&contents);
+
+    let header = match cds::Header::parse(&mut reader) {
+        Ok(header) => header,
+        Err(e) => {
+            error!("Failed to parse header {file_path:?}, skipping: {e}");
+            return;
+        }
+    };
+
+    debug!("{header:?}");
+    report::set("header", &header);
+
+    let mut tokens = cds::parse_file(reader.remaining());
+    cds::balance_tokens(&mut tokens);
+
+    let track = match cds::convert(
+        reader.remaining(),
+        header.quarter_note_time,
+        commands,
+        loops,
+    ) {
+        Ok(track) => track,
+        Err(e) => {
+            error!("Unable to convert {file_path:?}, skipping: {e}");
+            return;
+        }
+    };
+    let Some(seq_path) = output.file(&file_path.with_extension("seq")) else {
+        return;
+    };
+    let mut seq_file = vec![];
+    seq::Header::new(header.ppqn, header.quarter_note_time).write(&mut seq_file);
+    seq_file.extend_from_slice(&track);
+    if let Err(e) = std::fs::write(&seq_path, &seq_file) {
+        error!("Unable to write {seq_path:?}, skipping: {e}");
+        return;
+    }
+
+    if midi {
+        match SeqFile::parse(&seq_file) {
+            Ok(seq) => {
+                let mut midi = vec![];
+                seq.to_smf().write(&mut midi);
+                if let Some(midi_path) = output.extra_file(&seq_path, "mid") {
+                    if let Err(e) = std::fs::write(&midi_path, midi) {
+                        error!("Unable to write MIDI file {midi_path:?}: {e}");
+                    }
+                }
+            }
+            Err(e) => error!("Unable to convert {file_path:?} to MIDI: {e}"),
+        }
+    }
+
+    info!("Quarter note time: {}", header.quarter_note_time);
+    info!("PPQN: {}", header.ppqn);
+    info!(
+        "BPM: {}",
+        60_000_000u32
+            .checked_div(header.quarter_note_time)
+            .unwrap_or_default()
+    );
+    info!(
+        "Version: {}.{}",
+        header.version.to_be_bytes()[0],
+        header.version.to_be_bytes()[1],
+    );
+    let local_loops = tokens
+        .iter()
+        .filter(|token| matches!(token, Token::LoopStart(_)))
+        .count();
+    info!("Local loops: {local_loops}");
+    report::set("local_loops", local_loops);
+    match cds::parse_events(reader.remaining()) {
+        Ok(events) => {
+            let unknown_commands = cds::unknown_commands(&events);
+            for (kind, count) in &unknown_commands {
+                info!("Unknown meta command FF {kind:02X}: {count} times");
+            }
+            report::set("events", events.len());
+            report::set("unknown_commands", unknown_commands);
+        }
+        Err(e) => error!("Unable to list the meta commands of {file_path:?}: {e}"),
+    }
+}
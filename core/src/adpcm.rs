@@ -0,0 +1,472 @@
+//! Sony 4-bit ADPCM, the codec used by VAG, VAB bodies and console ADS streams.
+//!
+//! Every 16 byte block starts with a shift/filter byte and a flag byte,
+//! followed by 28 4-bit samples, low nibble first.
+
+use std::ops::Range;
+
+use crate::{
+    vag::{self, BLOCK_SIZE, FLAG_END, FLAG_LOOP_END, FLAG_LOOP_START, SAMPLES_PER_BLOCK},
+    Error, Result,
+};
+
+/// Prediction coefficients in 1/64ths, indexed by the filter nibble.
+pub const COEFFICIENTS: [(i32, i32); 5] = [(0, 0), (60, 0), (115, -52), (98, -55), (122, -60)];
+
+/// Flag bit marking the last block to be played before stopping or looping.
+pub const FLAG_BIT_END: u8 = 1;
+/// Flag bit marking that the end of the sample jumps back to the loop start.
+pub const FLAG_BIT_REPEAT: u8 = 2;
+/// Flag bit marking the start of a loop.
+pub const FLAG_BIT_LOOP_START: u8 = 4;
+
+/// A decoded mono stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Decoded {
+    pub samples: Vec<i16>,
+    /// Sample the loop starts at.
+    pub loop_start: Option<usize>,
+    /// Sample just after the end of the loop.
+    pub loop_end: Option<usize>,
+}
+
+/// Decoder state carried between blocks.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Decoder {
+    history: (i32, i32),
+}
+
+impl Decoder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes the 28 samples of a block onto the end of `out`.
+    pub fn decode_block(&mut self, block: &[u8; BLOCK_SIZE], out: &mut Vec<i16>) {
+        // Shifts above 12 and unknown filters behave like these on hardware.
+        let shift = match block[0] & 0xf {
+            shift @ 0..=12 => shift,
+            _ => 9,
+        };
+        let (f0, f1) = COEFFICIENTS
+            .get((block[0] >> 4) as usize)
+            .copied()
+            .unwrap_or_default();
+
+        for byte in &block[2..] {
+            for nibble in [byte & 0xf, byte >> 4] {
+                let delta = (((nibble as i16) << 12) >> shift) as i32;
+                let prediction = (self.history.0 * f0 + self.history.1 * f1 + 32) >> 6;
+                let sample = (delta + prediction).clamp(i16::MIN as i32, i16::MAX as i32);
+                self.history = (sample, self.history.0);
+                out.push(sample as i16);
+            }
+        }
+    }
+}
+
+/// Decodes a headerless ADPCM body, stopping at the first end flag.
+///
+/// A block flagged `7` marks the end and isn't played, while blocks with
+/// the end bit set otherwise are played before stopping.
+#[must_use]
+pub fn decode(body: &[u8]) -> Decoded {
+    let mut decoder = Decoder::new();
+    let mut decoded = Decoded {
+        samples: Vec::with_capacity(body.len() / BLOCK_SIZE * SAMPLES_PER_BLOCK),
+        ..Decoded::default()
+    };
+
+    for block in body.chunks_exact(BLOCK_SIZE) {
+        let flag = block[1];
+        if flag == FLAG_END {
+            break;
+        }
+        if flag & FLAG_BIT_LOOP_START != 0 && decoded.loop_start.is_none() {
+            decoded.loop_start = Some(decoded.samples.len());
+        }
+        decoder.decode_block(block.try_into().unwrap(), &mut decoded.samples);
+        if flag & FLAG_BIT_END != 0 {
+            if flag & FLAG_BIT_REPEAT != 0 {
+                decoded.loop_end = Some(decoded.samples.len());
+            }
+            break;
+        }
+    }
+
+    // A loop start without an end loops to the end of the sample.
+    if decoded.loop_start.is_some() && decoded.loop_end.is_none() {
+        decoded.loop_end = Some(decoded.samples.len());
+    }
+
+    decoded
+}
+
+/// Splits a body whose channels take turns every `interleave` bytes into the
+/// blocks of each channel.
+#[must_use]
+pub fn deinterleave(body: &[u8], channels: usize, interleave: usize) -> Vec<Vec<u8>> {
+    let channels = channels.max(1);
+    let mut split = vec![Vec::with_capacity(body.len() / channels); channels];
+    for (index, chunk) in body.chunks(interleave.max(BLOCK_SIZE)).enumerate() {
+        split[index % channels].extend(chunk);
+    }
+    split
+}
+
+/// Joins the bodies of several channels, taking turns every `interleave`
+/// bytes. Every channel is padded with silent blocks to the same number of
+/// turns.
+#[must_use]
+pub fn interleave(channels: &[Vec<u8>], interleave: usize) -> Vec<u8> {
+    let interleave = interleave.max(BLOCK_SIZE);
+    let turns = channels
+        .iter()
+        .map(|body| body.len().div_ceil(interleave))
+        .max()
+        .unwrap_or_default();
+    let mut body = Vec::with_capacity(turns * interleave * channels.len());
+    for turn in 0..turns {
+        for channel in channels {
+            let chunk = channel
+                .get(turn * interleave..)
+                .unwrap_or_default()
+                .iter()
+                .take(interleave);
+            let len = chunk.len();
+            body.extend(chunk);
+            body.extend(std::iter::repeat_n(0, interleave - len));
+        }
+    }
+    body
+}
+
+/// Decodes every channel of an interleaved body separately.
+#[must_use]
+pub fn decode_interleaved(body: &[u8], channels: usize, interleave: usize) -> Vec<Decoded> {
+    deinterleave(body, channels, interleave)
+        .iter()
+        .map(|body| decode(body))
+        .collect()
+}
+
+/// Rewrites the flags of every channel of an interleaved body so that it
+/// loops over `range`, in samples like [`Decoded`].
+///
+/// The flags can only mark whole blocks, so the start is rounded down and the
+/// end up, and a loop always spans at least two blocks. The range actually
+/// looped is returned. Flags before the loop are cleared, and those after it
+/// lose their loop bits, as they are never played.
+pub fn set_loop(
+    body: &mut [u8],
+    channels: usize,
+    interleave: usize,
+    range: Range<usize>,
+) -> Result<Range<usize>> {
+    if range.start >= range.end {
+        return Err(Error::OffsetOutOfRange {
+            field: "loop_start",
+            offset: range.start,
+            limit: range.end,
+        });
+    }
+    let num_blocks = deinterleave(body, channels, interleave)
+        .iter()
+        .map(|channel| channel.len() / BLOCK_SIZE)
+        .min()
+        .unwrap_or_default();
+    let start = range.start / SAMPLES_PER_BLOCK;
+    let end = range.end.div_ceil(SAMPLES_PER_BLOCK).max(start + 2);
+    if end > num_blocks {
+        return Err(Error::OffsetOutOfRange {
+            field: "loop_end",
+            offset: range.end,
+            limit: num_blocks * SAMPLES_PER_BLOCK,
+        });
+    }
+
+    let channels = channels.max(1);
+    let interleave = interleave.max(BLOCK_SIZE);
+    let blocks_per_turn = interleave.div_ceil(BLOCK_SIZE);
+    for (index, chunk) in body.chunks_mut(interleave).enumerate() {
+        let first_block = index / channels * blocks_per_turn;
+        for (block, data) in (first_block..).zip(chunk.chunks_mut(BLOCK_SIZE)) {
+            if data.len() < 2 {
+                continue;
+            }
+            match block {
+                _ if block < start => data[1] = 0,
+                _ if block == start => data[1] = FLAG_LOOP_START,
+                _ if block < end - 1 => data[1] = FLAG_BIT_REPEAT,
+                _ if block == end - 1 => data[1] = FLAG_LOOP_END,
+                _ => {
+                    vag::remove_loop(data);
+                }
+            }
+        }
+    }
+
+    Ok(start * SAMPLES_PER_BLOCK..end * SAMPLES_PER_BLOCK)
+}
+
+/// Index of the block playback stops at, the first with the end bit set.
+#[must_use]
+pub fn end_block(body: &[u8]) -> Option<usize> {
+    body.chunks_exact(BLOCK_SIZE)
+        .position(|block| block[1] & FLAG_BIT_END != 0)
+}
+
+/// Encoder state carried between blocks, mirroring what the decoder will see.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Encoder {
+    history: (i32, i32),
+}
+
+impl Encoder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encodes 28 samples into a block with the given flag byte, using
+    /// whichever filter and shift decode with the least error.
+    pub fn encode_block(
+        &mut self,
+        samples: &[i16; SAMPLES_PER_BLOCK],
+        flag: u8,
+    ) -> [u8; BLOCK_SIZE] {
+        let (_, mut block, history) = (0..COEFFICIENTS.len() as u8)
+            .flat_map(|filter| (0..=12).map(move |shift| (filter, shift)))
+            .map(|(filter, shift)| self.try_block(samples, filter, shift))
+            .min_by_key(|(error, ..)| *error)
+            .unwrap();
+        block[1] = flag;
+        self.history = history;
+        block
+    }
+
+    /// Encodes a block with a fixed filter and shift, returning the squared
+    /// error, the block and the history after decoding it.
+    fn try_block(
+        &self,
+        samples: &[i16; SAMPLES_PER_BLOCK],
+        filter: u8,
+        shift: u8,
+    ) -> (u64, [u8; BLOCK_SIZE], (i32, i32)) {
+        let (f0, f1) = COEFFICIENTS[filter as usize];
+        let step = 1 << (12 - shift);
+        let mut history = self.history;
+        let mut block = [0; BLOCK_SIZE];
+        block[0] = filter << 4 | shift;
+        let mut error = 0;
+
+        for (i, &sample) in samples.iter().enumerate() {
+            let prediction = (history.0 * f0 + history.1 * f1 + 32) >> 6;
+            let nibble = (sample as i32 - prediction + step / 2)
+                .div_euclid(step)
+                .clamp(-8, 7);
+            let decoded = (nibble * step + prediction).clamp(i16::MIN as i32, i16::MAX as i32);
+            error += (sample as i32 - decoded).pow(2) as u64;
+            history = (decoded, history.0);
+            block[2 + i / 2] |= ((nibble & 0xf) as u8) << (4 * (i % 2));
+        }
+
+        (error, block, history)
+    }
+}
+
+/// Encodes mono samples into a headerless ADPCM body, padding the last block with silence.
+///
+/// `loop_range` is in samples, like [`Decoded`], but the flags can only mark
+/// whole blocks, so its start is rounded down and its end up. A loop always
+/// spans at least two blocks, and anything after it is dropped since it
+/// would never be played.
+#[must_use]
+pub fn encode(samples: &[i16], loop_range: Option<Range<usize>>) -> Vec<u8> {
+    let loop_blocks = loop_range.map(|range| {
+        let start = range.start / SAMPLES_PER_BLOCK;
+        start..range.end.div_ceil(SAMPLES_PER_BLOCK).max(start + 2)
+    });
+    let num_blocks = match &loop_blocks {
+        Some(blocks) => blocks.end,
+        None => samples.len().div_ceil(SAMPLES_PER_BLOCK).max(1),
+    };
+
+    let mut encoder = Encoder::new();
+    let mut body = Vec::with_capacity(num_blocks * BLOCK_SIZE);
+    for index in 0..num_blocks {
+        let mut block = [0; SAMPLES_PER_BLOCK];
+        let chunk = samples
+            .get(index * SAMPLES_PER_BLOCK..)
+            .unwrap_or_default()
+            .iter()
+            .take(SAMPLES_PER_BLOCK);
+        for (sample, &value) in block.iter_mut().zip(chunk) {
+            *sample = value;
+        }
+
+        let flag = match &loop_blocks {
+            Some(blocks) if index == blocks.start => FLAG_LOOP_START,
+            Some(blocks) if index == blocks.end - 1 => FLAG_LOOP_END,
+            Some(blocks) if blocks.contains(&index) => FLAG_BIT_REPEAT,
+            None if index == num_blocks - 1 => FLAG_BIT_END,
+            _ => 0,
+        };
+        body.extend(encoder.encode_block(&block, flag));
+    }
+
+    body
+}
+
+#[test]
+fn silence() {
+    let decoded = decode(&[0; 3 * BLOCK_SIZE]);
+    assert_eq!(decoded.samples, [0; 3 * SAMPLES_PER_BLOCK]);
+    assert_eq!(decoded.loop_start, None);
+}
+
+#[test]
+fn filters() {
+    let mut block = [0; BLOCK_SIZE];
+    block[0] = 0x0c;
+    block[2] = 0xf1;
+    let mut out = vec![];
+    Decoder::new().decode_block(&block, &mut out);
+    assert_eq!(out[0..3], [1, -1, 0]);
+
+    block[0] = 0x18;
+    block[2] = 0x04;
+    out.clear();
+    Decoder::new().decode_block(&block, &mut out);
+    // 64, then decaying by 60 / 64 each sample
+    assert_eq!(out[0..3], [64, 60, 56]);
+}
+
+#[test]
+fn loops() {
+    let mut body = [0; 5 * BLOCK_SIZE];
+    body[BLOCK_SIZE + 1] = crate::vag::FLAG_LOOP_START;
+    body[2 * BLOCK_SIZE + 1] = 2;
+    body[3 * BLOCK_SIZE + 1] = crate::vag::FLAG_LOOP_END;
+    let decoded = decode(&body);
+    assert_eq!(decoded.samples.len(), 4 * SAMPLES_PER_BLOCK);
+    assert_eq!(decoded.loop_start, Some(SAMPLES_PER_BLOCK));
+    assert_eq!(decoded.loop_end, Some(4 * SAMPLES_PER_BLOCK));
+    assert_eq!(end_block(&body), Some(3));
+
+    body[3 * BLOCK_SIZE + 1] = FLAG_END;
+    let decoded = decode(&body);
+    assert_eq!(decoded.samples.len(), 3 * SAMPLES_PER_BLOCK);
+    assert_eq!(decoded.loop_end, Some(3 * SAMPLES_PER_BLOCK));
+    assert_eq!(end_block(&body[..3 * BLOCK_SIZE]), None);
+}
+
+#[test]
+fn interleaving() {
+    let blocks = |shift, count| {
+        let mut block = [0; BLOCK_SIZE];
+        block[0] = shift;
+        block.repeat(count)
+    };
+    let left = blocks(1, 3);
+    let right = blocks(2, 2);
+    let body = interleave(&[left.clone(), right.clone()], 2 * BLOCK_SIZE);
+    assert_eq!(body.len(), 8 * BLOCK_SIZE);
+    let shifts = body
+        .chunks(BLOCK_SIZE)
+        .map(|block| block[0])
+        .collect::<Vec<_>>();
+    assert_eq!(shifts, [1, 1, 2, 2, 1, 0, 0, 0]);
+
+    let split = deinterleave(&body, 2, 2 * BLOCK_SIZE);
+    assert_eq!(split[0][..left.len()], left);
+    assert_eq!(split[1][..right.len()], right);
+    assert_eq!(deinterleave(&body, 1, 0)[0], body);
+
+    let decoded = decode_interleaved(&body, 2, 2 * BLOCK_SIZE);
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(decoded[0].samples.len(), 4 * SAMPLES_PER_BLOCK);
+}
+
+#[test]
+fn loop_editing() {
+    let mut body = [0; 6 * BLOCK_SIZE];
+    body[1] = FLAG_LOOP_START;
+    body[BLOCK_SIZE + 1] = FLAG_END;
+    body[5 * BLOCK_SIZE + 1] = FLAG_LOOP_END;
+    assert_eq!(set_loop(&mut body, 1, 0, 30..90), Ok(28..112));
+    let flags = body
+        .chunks(BLOCK_SIZE)
+        .map(|block| block[1])
+        .collect::<Vec<_>>();
+    assert_eq!(
+        flags,
+        [0, FLAG_LOOP_START, FLAG_BIT_REPEAT, FLAG_LOOP_END, 0, 0]
+    );
+    let decoded = decode(&body);
+    assert_eq!(decoded.loop_start, Some(28));
+    assert_eq!(decoded.loop_end, Some(112));
+
+    assert_eq!(set_loop(&mut body, 1, 0, 120..141), Ok(112..168));
+    assert!(set_loop(&mut body, 1, 0, 150..160).is_err());
+    assert!(set_loop(&mut body, 1, 0, 50..50).is_err());
+
+    let mut stereo = interleave(&[body.to_vec(), body.to_vec()], 2 * BLOCK_SIZE);
+    assert_eq!(set_loop(&mut stereo, 2, 2 * BLOCK_SIZE, 0..56), Ok(0..56));
+    for channel in decode_interleaved(&stereo, 2, 2 * BLOCK_SIZE) {
+        assert_eq!((channel.loop_start, channel.loop_end), (Some(0), Some(56)));
+    }
+}
+
+#[test]
+fn vab_samples() {
+    let vab = include_bytes!("../tests/test.vab");
+    let vab_file = crate::vab::VabFile::parse(vab).unwrap();
+    for range in vab_file.vag_ranges {
+        let decoded = decode(&vab[range.clone()]);
+        assert!(decoded.samples.len() <= range.len() / BLOCK_SIZE * SAMPLES_PER_BLOCK);
+        assert!(decoded.samples.iter().any(|sample| *sample != 0));
+    }
+}
+
+#[test]
+fn encode_round_trip() {
+    let samples = (0..1000)
+        .map(|i| (f32::sin(i as f32 / 10.0) * 12000.0) as i16)
+        .collect::<Vec<_>>();
+    let body = encode(&samples, None);
+    assert_eq!(
+        body.len(),
+        1000usize.div_ceil(SAMPLES_PER_BLOCK) * BLOCK_SIZE
+    );
+
+    let decoded = decode(&body);
+    assert_eq!(decoded.loop_start, None);
+    assert_eq!(
+        decoded.samples.len(),
+        body.len() / BLOCK_SIZE * SAMPLES_PER_BLOCK
+    );
+    let error = samples
+        .iter()
+        .zip(&decoded.samples)
+        .map(|(a, b)| (*a as f64 - *b as f64).powi(2))
+        .sum::<f64>();
+    let rms = (error / samples.len() as f64).sqrt();
+    assert!(rms < 100.0, "{rms}");
+}
+
+#[test]
+fn encode_loop() {
+    let samples = vec![1000; 10 * SAMPLES_PER_BLOCK];
+    let body = encode(&samples, Some(30..100));
+    let flags = body
+        .chunks(BLOCK_SIZE)
+        .map(|block| block[1])
+        .collect::<Vec<_>>();
+    assert_eq!(flags, [0, FLAG_LOOP_START, FLAG_BIT_REPEAT, FLAG_LOOP_END]);
+
+    let decoded = decode(&body);
+    assert_eq!(decoded.loop_start, Some(SAMPLES_PER_BLOCK));
+    assert_eq!(decoded.loop_end, Some(4 * SAMPLES_PER_BLOCK));
+}
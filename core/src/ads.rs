@@ -0,0 +1,162 @@
+//! Sony ADS streams, using the simplified `SShd`/`SSbd` header.
+
+use crate::{Reader, Result};
+
+pub const MAGIC: [u8; 4] = *b"SShd";
+pub const BODY_MAGIC: [u8; 4] = *b"SSbd";
+/// Length of the simplified header written by the unLoKable tools.
+pub const HEADER_SIZE: usize = 0x28;
+/// Size of the header fields up to the end of the loop points, shorter
+/// variants have no loop points and longer ones extra fields after them.
+const LOOP_FIELDS_SIZE: usize = 0x18;
+
+pub const CODEC_PCM16: u32 = 0x01;
+pub const CODEC_VAG: u32 = 0x10;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct AdsHeader {
+    pub codec: u32,
+    pub sample_rate: u32,
+    pub channels: u32,
+    pub interleave: u32,
+    /// First sample of the loop in PCM16 streams, or first block of each
+    /// channel in ADPCM ones.
+    pub loop_start: Option<u32>,
+    /// Last sample or block of the loop, like `loop_start`.
+    pub loop_end: Option<u32>,
+    /// Size of the stream following the header in bytes.
+    pub body_size: u32,
+}
+
+impl AdsHeader {
+    /// Parses the header, leaving `bytes` at the start of the body.
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        bytes.magic("ADS", MAGIC)?;
+        let header_size = bytes.u32_le("header_size")? as usize;
+        let header_start = bytes.position();
+        let codec = bytes.u32_le("codec")?;
+        let sample_rate = bytes.u32_le("sample_rate")?;
+        let channels = bytes.u32_le("channels")?;
+        let interleave = bytes.u32_le("interleave")?;
+        let (loop_start, loop_end) = if header_size >= LOOP_FIELDS_SIZE {
+            (bytes.u32_le("loop_start")?, bytes.u32_le("loop_end")?)
+        } else {
+            (u32::MAX, u32::MAX)
+        };
+        bytes.seek("header_size", header_start + header_size)?;
+
+        bytes.magic("ADS body", BODY_MAGIC)?;
+        let body_size = bytes.u32_le("body_size")?;
+
+        Ok(Self {
+            codec,
+            sample_rate,
+            channels,
+            interleave,
+            loop_start: (loop_start != u32::MAX).then_some(loop_start),
+            loop_end: (loop_end != u32::MAX).then_some(loop_end),
+            body_size,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(
+            [
+                MAGIC,
+                (LOOP_FIELDS_SIZE as u32).to_le_bytes(),
+                self.codec.to_le_bytes(),
+                self.sample_rate.to_le_bytes(),
+                self.channels.to_le_bytes(),
+                self.interleave.to_le_bytes(),
+                self.loop_start.unwrap_or(u32::MAX).to_le_bytes(),
+                self.loop_end.unwrap_or(u32::MAX).to_le_bytes(),
+                BODY_MAGIC,
+                self.body_size.to_le_bytes(),
+            ]
+            .into_iter()
+            .flatten(),
+        );
+    }
+}
+
+/// Overwrites the loop points in the header of a file, returning false when
+/// the header is too short to have them.
+pub fn write_loop(file: &mut [u8], loop_points: Option<(u32, u32)>) -> bool {
+    let header_size = file.get(4..8).map_or(0, |size| {
+        u32::from_le_bytes(size.try_into().unwrap()) as usize
+    });
+    let Some(fields) = file.get_mut(0x18..0x20) else {
+        return false;
+    };
+    if header_size < LOOP_FIELDS_SIZE {
+        return false;
+    }
+    let (start, end) = loop_points.unwrap_or((u32::MAX, u32::MAX));
+    fields[0..4].copy_from_slice(&start.to_le_bytes());
+    fields[4..8].copy_from_slice(&end.to_le_bytes());
+    true
+}
+
+#[test]
+fn header_round_trip() {
+    let header = AdsHeader {
+        codec: CODEC_VAG,
+        sample_rate: 44100,
+        channels: 2,
+        interleave: 0x100,
+        loop_start: None,
+        loop_end: None,
+        body_size: 0x400,
+    };
+    let mut bytes = vec![];
+    header.write(&mut bytes);
+    assert_eq!(bytes.len(), HEADER_SIZE);
+    let mut reader = Reader::new(&bytes);
+    assert_eq!(AdsHeader::parse(&mut reader), Ok(header));
+    assert_eq!(reader.position(), HEADER_SIZE);
+}
+
+#[test]
+fn header_variants() {
+    let header = AdsHeader {
+        codec: CODEC_PCM16,
+        sample_rate: 48000,
+        channels: 1,
+        interleave: 0,
+        loop_start: Some(100),
+        loop_end: Some(200),
+        body_size: 0x400,
+    };
+    let mut bytes = vec![];
+    header.write(&mut bytes);
+
+    // An extended header with another field after the loop points.
+    let mut extended = bytes.clone();
+    extended[4] = 0x1c;
+    extended.splice(0x20..0x20, [0xaa; 4]);
+    assert_eq!(
+        AdsHeader::parse(&mut Reader::new(&extended)),
+        Ok(header.clone())
+    );
+
+    let mut looped = bytes.clone();
+    assert!(write_loop(&mut looped, None));
+    let unlooped = AdsHeader::parse(&mut Reader::new(&looped)).unwrap();
+    assert_eq!((unlooped.loop_start, unlooped.loop_end), (None, None));
+
+    // A short header without them.
+    let mut short = bytes;
+    short[4] = 0x10;
+    short.drain(0x18..0x20);
+    assert_eq!(
+        AdsHeader::parse(&mut Reader::new(&short)),
+        Ok(AdsHeader {
+            loop_start: None,
+            loop_end: None,
+            ..header
+        })
+    );
+    let unchanged = short.clone();
+    assert!(!write_loop(&mut short, Some((1, 2))));
+    assert_eq!(short, unchanged);
+}
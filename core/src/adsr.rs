@@ -0,0 +1,116 @@
+//! The PlayStation SPU volume envelope, as set by the `adsr1`/`adsr2`
+//! registers stored in VAB tones and SND zones.
+//!
+//! The envelope level runs from 0 to `0x7fff` and is stepped at the SPU rate
+//! of 44100 Hz. Each phase is either linear or exponential, with a rate made
+//! of a shift and a step.
+
+/// Rate the envelope is stepped at.
+pub const SPU_RATE: u32 = 44100;
+/// Level of the envelope at full volume.
+pub const MAX_LEVEL: i32 = 0x7fff;
+
+/// The envelope converted to durations, for formats with a simpler envelope.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Envelope {
+    /// Seconds to rise from silence to full volume.
+    pub attack: f32,
+    /// Seconds to fall from full volume to silence, at the decay rate.
+    pub decay: f32,
+    /// Level held after the decay, from 0 to 1.
+    pub sustain_level: f32,
+    /// Seconds to fall from full volume to silence once the note is released.
+    pub release: f32,
+}
+
+impl Envelope {
+    /// Converts the registers, ignoring the sustain rate as most envelopes
+    /// hold the sustain level.
+    #[must_use]
+    pub fn from_registers(adsr1: u16, adsr2: u16) -> Self {
+        let attack_exponential = adsr1 & 0x8000 != 0;
+        let attack_shift = ((adsr1 >> 10) & 0x1f) as u8;
+        let attack_step = ((adsr1 >> 8) & 0x3) as u8;
+        let decay_shift = ((adsr1 >> 4) & 0xf) as u8;
+        let sustain_level = ((adsr1 & 0xf) as i32 + 1) * 0x800;
+        let release_exponential = adsr2 & 0x20 != 0;
+        let release_shift = (adsr2 & 0x1f) as u8;
+
+        Self {
+            attack: seconds(Phase {
+                exponential: attack_exponential,
+                decrease: false,
+                shift: attack_shift,
+                step: attack_step,
+            }),
+            decay: seconds(Phase {
+                exponential: true,
+                decrease: true,
+                shift: decay_shift,
+                step: 0,
+            }),
+            sustain_level: sustain_level.min(MAX_LEVEL) as f32 / MAX_LEVEL as f32,
+            release: seconds(Phase {
+                exponential: release_exponential,
+                decrease: true,
+                shift: release_shift,
+                step: 0,
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Phase {
+    exponential: bool,
+    decrease: bool,
+    shift: u8,
+    step: u8,
+}
+
+/// Steps the envelope across its whole range, returning the time taken.
+fn seconds(phase: Phase) -> f32 {
+    let base_cycles = 1u64 << phase.shift.saturating_sub(11);
+    let base_step = if phase.decrease {
+        -8 + phase.step as i32
+    } else {
+        7 - phase.step as i32
+    } << 11u8.saturating_sub(phase.shift);
+
+    let mut level = if phase.decrease { MAX_LEVEL } else { 0 };
+    let mut ticks = 0;
+    while (phase.decrease && level > 0) || (!phase.decrease && level < MAX_LEVEL) {
+        let mut cycles = base_cycles;
+        let mut step = base_step;
+        if phase.exponential {
+            if phase.decrease {
+                step = (step * level) >> 15;
+            } else if level > 0x6000 {
+                cycles *= 4;
+            }
+        }
+        level += step;
+        ticks += cycles;
+    }
+
+    ticks as f32 / SPU_RATE as f32
+}
+
+#[test]
+fn envelope() {
+    // The default envelope of the Sony tools
+    let envelope = Envelope::from_registers(0x80ff, 0x5fc0);
+    assert!(envelope.attack < 0.01, "{envelope:?}");
+    assert_eq!(envelope.sustain_level, 1.0);
+    assert!(envelope.release < 0.01, "{envelope:?}");
+
+    // Linear attack with the slowest step at shift 11, one level per 4 steps
+    let envelope = Envelope::from_registers(11 << 10 | 3 << 8, 0x1f);
+    assert_eq!(
+        envelope.attack,
+        (MAX_LEVEL as f32 / 4.0).ceil() / SPU_RATE as f32
+    );
+    assert_eq!(envelope.sustain_level, 0x800 as f32 / MAX_LEVEL as f32);
+    // Exponential releases take far longer than linear ones
+    assert!(Envelope::from_registers(0x4, 0x3f).release > envelope.release);
+}
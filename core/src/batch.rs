@@ -0,0 +1,207 @@
+//! Working on several input files at once, keeping the log lines of each
+//! file together.
+
+use std::{
+    cell::RefCell,
+    num::NonZeroUsize,
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use log::{info, warn, Level, Log, Metadata, Record};
+
+use crate::report;
+
+// Batch options, flattened into the arguments of every tool that reads folders.
+// Not a doc comment, as clap would use it for the description of the tool.
+#[derive(clap::Args, Clone, Debug)]
+pub struct JobArgs {
+    /// How many files to work on at once.
+    #[clap(short, long, default_value_t = NonZeroUsize::MIN)]
+    pub jobs: NonZeroUsize,
+}
+
+impl Default for JobArgs {
+    fn default() -> Self {
+        Self {
+            jobs: NonZeroUsize::MIN,
+        }
+    }
+}
+
+/// What happened to the files of a batch.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub succeeded: usize,
+    /// The files that logged an error or panicked, in the order given.
+    pub failed: Vec<PathBuf>,
+}
+
+/// The log lines of the file a thread is working on.
+struct Capture {
+    /// Whether to hold the lines until the file is done, rather than
+    /// writing them straight away.
+    buffered: bool,
+    lines: Vec<(Level, String, String)>,
+    errors: usize,
+}
+
+thread_local! {
+    static CAPTURE: RefCell<Option<Capture>> = const { RefCell::new(None) };
+}
+
+/// Held while writing the lines of a file, so they aren't mixed with others.
+static FLUSH: Mutex<()> = Mutex::new(());
+
+/// Wraps the logger of the tools to count the errors of each file, and to
+/// buffer their lines when working on several at once.
+pub(crate) struct Logger<L>(pub L);
+
+impl<L: Log> Log for Logger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let buffered = CAPTURE.with_borrow_mut(|capture| {
+            let Some(capture) = capture else {
+                return false;
+            };
+            if record.level() == Level::Error {
+                capture.errors += 1;
+            }
+            if capture.buffered {
+                capture.lines.push((
+                    record.level(),
+                    record.target().to_owned(),
+                    record.args().to_string(),
+                ));
+            }
+            capture.buffered
+        });
+        if !buffered {
+            self.0.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+/// Calls `handle` on every file, using up to `jobs` threads, then logs a
+/// summary if there was more than one file, and prints the report if one is
+/// being made.
+pub fn run<F>(files: Vec<PathBuf>, jobs: NonZeroUsize, handle: F) -> Summary
+where
+    F: Fn(&Path) + Sync,
+{
+    let buffered = jobs.get() > 1;
+    let reporting = report::enabled();
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![true; files.len()]);
+    let reports = Mutex::new(vec![serde_json::Value::Null; files.len()]);
+    let work = || loop {
+        let index = next.fetch_add(1, Ordering::Relaxed);
+        let Some(file) = files.get(index) else {
+            break;
+        };
+        CAPTURE.set(Some(Capture {
+            buffered,
+            lines: vec![],
+            errors: 0,
+        }));
+        if reporting {
+            report::begin(file);
+        }
+        let panicked = std::panic::catch_unwind(AssertUnwindSafe(|| handle(file))).is_err();
+        let capture = CAPTURE.take().unwrap();
+        if !capture.lines.is_empty() {
+            let _lock = FLUSH
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            for (level, target, message) in capture.lines {
+                log::logger().log(
+                    &Record::builder()
+                        .level(level)
+                        .target(&target)
+                        .args(format_args!("{message}"))
+                        .build(),
+                );
+            }
+        }
+        let ok = !panicked && capture.errors == 0;
+        results.lock().unwrap()[index] = ok;
+        if reporting {
+            reports.lock().unwrap()[index] = report::finish(ok);
+        }
+    };
+    std::thread::scope(|scope| {
+        for _ in 1..jobs.get().min(files.len()) {
+            scope.spawn(work);
+        }
+        work();
+    });
+
+    let results = results.into_inner().unwrap();
+    let summary = Summary {
+        succeeded: results.iter().filter(|ok| **ok).count(),
+        failed: files
+            .into_iter()
+            .zip(results)
+            .filter(|(_, ok)| !ok)
+            .map(|(file, _)| file)
+            .collect(),
+    };
+    if summary.succeeded + summary.failed.len() > 1 {
+        info!(
+            "{} files done, {} failed",
+            summary.succeeded,
+            summary.failed.len()
+        );
+        for file in &summary.failed {
+            warn!("Failed: {file:?}");
+        }
+    }
+    if reporting {
+        report::print(reports.into_inner().unwrap(), &summary.failed);
+    }
+    summary
+}
+
+#[test]
+fn summary() {
+    struct Discard;
+    impl Log for Discard {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+        fn log(&self, _: &Record) {}
+        fn flush(&self) {}
+    }
+    log::set_boxed_logger(Box::new(Logger(Discard))).unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+
+    let files = (0..20)
+        .map(|i| PathBuf::from(format!("{i}")))
+        .collect::<Vec<_>>();
+    let done = AtomicUsize::new(0);
+    let summary = run(files, NonZeroUsize::new(4).unwrap(), |file| {
+        done.fetch_add(1, Ordering::Relaxed);
+        match file.to_str().unwrap() {
+            "3" => log::error!("Unable to handle {file:?}"),
+            "7" => panic!("panicked on {file:?}"),
+            _ => info!("Handled {file:?}"),
+        }
+    });
+    assert_eq!(done.into_inner(), 20);
+    assert_eq!(summary.succeeded, 18);
+    assert_eq!(summary.failed, [Path::new("3"), Path::new("7")]);
+}
@@ -0,0 +1,1069 @@
+//! Crystal Dynamics CDS sequences, a MIDI-like event stream with nested loops.
+
+use std::collections::BTreeMap;
+
+use either::Either;
+use log::trace;
+
+use crate::{midi, seq, Error, Reader, Result};
+
+pub const MAGIC: [u8; 4] = *b"QESa";
+pub const HEADER_SIZE: usize = 12;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Header {
+    pub quarter_note_time: u32,
+    pub ppqn: u16,
+    pub version: u16,
+}
+
+impl Header {
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        bytes.magic("CDS", MAGIC)?;
+        Ok(Header {
+            quarter_note_time: bytes.u32_le("quarter_note_time")?,
+            ppqn: bytes.u16_le("ppqn")?,
+            version: bytes.u16_le("version")?,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(MAGIC);
+        out.extend(self.quarter_note_time.to_le_bytes());
+        out.extend(self.ppqn.to_le_bytes());
+        out.extend(self.version.to_le_bytes());
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// `FF2E01XX` with `XX` being loop count.
+    LoopStart(u8),
+    /// Data without any sentinel values.
+    Data(&'a [u8]),
+    /// `FF2F00`.
+    LoopFinish,
+    /// `FF4400`.
+    GlobalEnding,
+}
+
+#[must_use]
+pub fn parse_file(bytes: &[u8]) -> Vec<Token<'_>> {
+    let mut i = 0;
+    let mut tokens = vec![];
+    while i + 2 < bytes.len() {
+        match &bytes[i..i + 3] {
+            [0xff, 0x2e, 0x01] if i + 3 < bytes.len() => {
+                tokens.push(Token::LoopStart(bytes[i + 3]));
+                i += 4;
+            }
+            [0xff, 0x2f, 0x00] => {
+                tokens.push(Token::LoopFinish);
+                i += 3;
+            }
+            [0xff, 0x44, 0x00] => {
+                tokens.push(Token::GlobalEnding);
+                i += 3;
+            }
+            _ => {
+                if let Some(Token::Data(data)) = tokens.last_mut() {
+                    *data = &bytes[i - data.len()..=i];
+                } else {
+                    tokens.push(Token::Data(&bytes[i..=i]));
+                }
+                i += 1;
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Inserts loop markers so that every loop start has a matching finish.
+pub fn balance_tokens(tokens: &mut Vec<Token>) {
+    let mut loop_starter_count = tokens
+        .iter()
+        .filter(|x| matches!(x, Token::LoopStart(_)))
+        .count();
+    let mut loop_terminator_count = tokens
+        .iter()
+        .filter(|x| matches!(x, Token::LoopFinish))
+        .count();
+    while loop_starter_count < loop_terminator_count {
+        tokens.insert(0, Token::LoopStart(0));
+        tokens.insert(0, Token::Data(&[0]));
+        loop_starter_count += 1;
+    }
+    while loop_starter_count > loop_terminator_count {
+        tokens.insert(tokens.len() - 1, Token::LoopFinish);
+        tokens.insert(tokens.len() - 1, Token::Data(&[0]));
+        loop_terminator_count += 1;
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Lexeme {
+    Loop(u8, Vec<Lexeme>),
+    Data(Vec<u8>),
+}
+
+impl Lexeme {
+    pub fn visualise(&self, depth: usize) {
+        match self {
+            Self::Loop(count, children) => {
+                trace!("{}Loop: {}x", "\t".repeat(depth), count);
+                for child in children {
+                    child.visualise(depth + 1);
+                }
+            }
+            Self::Data(data) => trace!(
+                "{}Data: {:#04x} .. {:#04x}",
+                "\t".repeat(depth),
+                data[0],
+                data.last().unwrap(),
+            ),
+        }
+    }
+
+    /// Writes the lexeme back as CDS data, keeping the loops as they are.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Data(data) => out.extend_from_slice(data),
+            Self::Loop(count, lexemes) => {
+                out.extend([0xff, 0x2e, 0x01, *count]);
+                for lexeme in lexemes {
+                    lexeme.write(out);
+                }
+                out.extend([0xff, 0x2f, 0x00]);
+            }
+        }
+    }
+
+    /// Writes the lexeme with its loops repeated, each pass ending with a
+    /// loop finish and infinite loops played once.
+    pub fn write_unrolled(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Data(data) => out.extend_from_slice(data),
+            Self::Loop(count, lexemes) => {
+                out.extend([0xff, 0x2e, 0x01, 0x00]);
+                for _ in 0..(*count).max(1) {
+                    for lexeme in lexemes {
+                        lexeme.write_unrolled(out);
+                    }
+                    out.extend([0xff, 0x2f, 0x00]);
+                }
+            }
+        }
+    }
+}
+
+/// Groups balanced tokens into their loops.
+pub fn lex_file<'a>(tokens: &'a [Token<'a>]) -> Result<Vec<Lexeme>> {
+    let mut lexemes: Vec<Either<Token, Lexeme>> =
+        tokens.iter().copied().map(Either::Left).collect::<Vec<_>>();
+
+    let mut i = 0;
+    while i < lexemes.len() {
+        match lexemes[i] {
+            Either::Left(Token::LoopFinish) => {
+                lexemes.remove(i);
+                let mut loop_body = vec![];
+                let mut j = i;
+                loop {
+                    let previous = j.checked_sub(1).ok_or(NO_LOOP_START)?;
+                    j = previous;
+                    match lexemes[j] {
+                        Either::Left(Token::LoopStart(count)) => {
+                            loop_body.reverse();
+                            lexemes[j] =
+                                Either::Right(Lexeme::Loop(count, std::mem::take(&mut loop_body)));
+                            i = j;
+                            break;
+                        }
+                        Either::Left(_) => return Err(UNFINISHED_LOOP),
+                        Either::Right(_) => loop_body.push(lexemes.remove(j).unwrap_right()),
+                    }
+                }
+            }
+            Either::Left(Token::Data(data)) => {
+                lexemes[i] = Either::Right(Lexeme::Data(data.to_vec()));
+            }
+            Either::Left(Token::GlobalEnding) => {
+                lexemes[i] = Either::Right(Lexeme::Data(vec![0xff, 0x44, 0x00]));
+            }
+            Either::Left(Token::LoopStart(_)) | Either::Right(_) => {}
+        }
+
+        i += 1;
+    }
+
+    lexemes
+        .into_iter()
+        .map(|lexeme| lexeme.either(|_| Err(UNFINISHED_LOOP), Ok))
+        .collect()
+}
+
+const NO_LOOP_START: Error = Error::BadTrack {
+    format: "CDS",
+    reason: "loop finish without a start",
+};
+const UNFINISHED_LOOP: Error = Error::BadTrack {
+    format: "CDS",
+    reason: "loop start without a finish",
+};
+const NO_END: Error = Error::BadTrack {
+    format: "CDS",
+    reason: "no end of track found",
+};
+
+/// A Crystal Dynamics meta command, `FF`, its kind and a length byte
+/// followed by that many bytes of payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// `FF 05 03`, microseconds per quarter note.
+    Tempo(u32),
+    /// `FF 2E 01`, with the loop count, 0 looping forever.
+    LoopStart(u8),
+    /// `FF 2F 00`.
+    LoopFinish,
+    /// `FF 32 01`, finishes a loop that plays forever.
+    InfiniteLoopFinish(u8),
+    /// `FF 44 00`, the end of the sequence.
+    GlobalEnding,
+    /// `FF F0`, the only command of variable length, holding a system
+    /// exclusive message without the leading `F0`.
+    SysEx(Vec<u8>),
+    /// One of the other commands found in the games, with the length
+    /// [`command_length`] gives. What they do isn't documented, so they are
+    /// kept as they are and only told apart by their kind.
+    Undocumented { kind: u8, data: Vec<u8> },
+    /// A command that isn't found in the games, or not with that length.
+    Other { kind: u8, data: Vec<u8> },
+}
+
+/// Payload length of the meta commands found in the games, `FF F0` being
+/// the only one that varies.
+#[must_use]
+pub fn command_length(kind: u8) -> Option<u8> {
+    match kind {
+        0x45 => Some(6),
+        0xf1 => Some(4),
+        0x05 | 0x39..=0x3f => Some(3),
+        0x14 | 0x15 | 0x18 | 0x29 | 0x33..=0x36 | 0x38 | 0x4b..=0x4d | 0x50 => Some(2),
+        0x00..=0x02
+        | 0x06
+        | 0x07
+        | 0x0e
+        | 0x10
+        | 0x1a
+        | 0x1c
+        | 0x24
+        | 0x25
+        | 0x2e
+        | 0x31
+        | 0x32
+        | 0x48 => Some(1),
+        0x03 | 0x04 | 0x08..=0x0b | 0x12 | 0x13 | 0x2f | 0x41..=0x44 | 0x49 => Some(0),
+        _ => None,
+    }
+}
+
+impl Command {
+    fn parse(kind: u8, data: &[u8]) -> Self {
+        match (kind, data) {
+            (0x05, &[a, b, c]) => Self::Tempo(u32::from_be_bytes([0, a, b, c])),
+            (0x2e, &[count]) => Self::LoopStart(count),
+            (0x2f, []) => Self::LoopFinish,
+            (0x32, &[value]) => Self::InfiniteLoopFinish(value),
+            (0x44, []) => Self::GlobalEnding,
+            (0xf0, data) => Self::SysEx(data.to_vec()),
+            _ if command_length(kind) == Some(data.len() as u8) => Self::Undocumented {
+                kind,
+                data: data.to_vec(),
+            },
+            _ => Self::Other {
+                kind,
+                data: data.to_vec(),
+            },
+        }
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        let data = self.data();
+        out.extend([0xff, self.kind(), data.len() as u8]);
+        out.extend(data);
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> u8 {
+        match self {
+            Self::Tempo(_) => 0x05,
+            Self::LoopStart(_) => 0x2e,
+            Self::LoopFinish => 0x2f,
+            Self::InfiniteLoopFinish(_) => 0x32,
+            Self::GlobalEnding => 0x44,
+            Self::SysEx(_) => 0xf0,
+            Self::Undocumented { kind, .. } | Self::Other { kind, .. } => *kind,
+        }
+    }
+
+    #[must_use]
+    pub fn data(&self) -> Vec<u8> {
+        match self {
+            Self::Tempo(tempo) => tempo.to_be_bytes()[1..].to_vec(),
+            Self::LoopStart(value) | Self::InfiniteLoopFinish(value) => vec![*value],
+            Self::LoopFinish | Self::GlobalEnding => vec![],
+            Self::SysEx(data) | Self::Undocumented { data, .. } | Self::Other { data, .. } => {
+                data.clone()
+            }
+        }
+    }
+
+    /// Whether the command is one found in the games, with the usual length.
+    #[must_use]
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Self::Other { .. })
+    }
+
+    #[must_use]
+    pub fn name(&self) -> String {
+        match self {
+            Self::Tempo(_) => "tempo".to_owned(),
+            Self::LoopStart(_) => "loop start".to_owned(),
+            Self::LoopFinish => "loop finish".to_owned(),
+            Self::InfiniteLoopFinish(_) => "infinite loop finish".to_owned(),
+            Self::GlobalEnding => "global ending".to_owned(),
+            Self::SysEx(_) => "system exclusive".to_owned(),
+            Self::Undocumented { kind, .. } => format!("undocumented command {kind:02X}"),
+            Self::Other { kind, .. } => format!("unknown command {kind:02X}"),
+        }
+    }
+
+    /// The standard MIDI message doing the same thing, if there is one.
+    #[must_use]
+    pub fn to_midi(&self) -> Option<midi::Message> {
+        match self {
+            Self::Tempo(tempo) => Some(midi::Message::Tempo(*tempo)),
+            Self::SysEx(data) => Some(midi::Message::SysEx(data.clone())),
+            _ => None,
+        }
+    }
+
+    /// A marker naming the command, followed by its payload in hex.
+    #[must_use]
+    pub fn to_marker(&self) -> midi::Message {
+        let mut text = format!("CDS {}", self.name());
+        for byte in self.data() {
+            text += &format!(" {byte:02X}");
+        }
+        midi::Message::Meta {
+            kind: midi::META_MARKER,
+            data: text.into_bytes(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// A channel message, kept as it is.
+    Midi(midi::Message),
+    Command(Command),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// Ticks since the previous event.
+    pub delta: u32,
+    pub message: Message,
+}
+
+/// Reads the events of a CDS body up to the global ending, which is
+/// included, without handling the loops.
+pub fn parse_events(body: &[u8]) -> Result<Vec<Event>> {
+    let mut bytes = Reader::new(body);
+    let mut events = vec![];
+    let mut running_status = None;
+    while !bytes.is_empty() {
+        let event = read_event(&mut bytes, &mut running_status)?;
+        let end = event.message == Message::Command(Command::GlobalEnding);
+        events.push(event);
+        if end {
+            break;
+        }
+    }
+    Ok(events)
+}
+
+/// Reads a single event, keeping track of the running status.
+pub fn read_event(bytes: &mut Reader, running_status: &mut Option<u8>) -> Result<Event> {
+    let delta = bytes.var_len("delta")?;
+    let offset = bytes.position();
+    let message = match bytes.u8("status")? {
+        0xff => {
+            let kind = bytes.u8("command")?;
+            let len = bytes.u8("command_length")?;
+            Message::Command(Command::parse(
+                kind,
+                bytes.bytes("command_data", len as usize)?,
+            ))
+        }
+        status @ 0xf0..=0xfe => {
+            return Err(Error::BadEvent {
+                format: "CDS",
+                offset,
+                status,
+            })
+        }
+        first => Message::Midi(midi::read_channel(bytes, "CDS", first, running_status)?),
+    };
+    Ok(Event { delta, message })
+}
+
+/// Writes events in the form [`parse_events`] reads, leaving out channel
+/// statuses repeated since the last command.
+pub fn write_events(events: &[Event], out: &mut Vec<u8>) {
+    let mut running_status = None;
+    for event in events {
+        midi::write_var_len(event.delta, out);
+        match &event.message {
+            Message::Midi(midi::Message::Channel { status, data }) => {
+                if running_status != Some(*status) {
+                    out.push(*status);
+                    running_status = Some(*status);
+                }
+                out.extend(&data[..midi::data_len(*status)]);
+            }
+            Message::Midi(message) => {
+                message.write(out);
+                running_status = None;
+            }
+            Message::Command(command) => {
+                command.write(out);
+                running_status = None;
+            }
+        }
+    }
+}
+
+/// Counts the commands in `events` that aren't known, by kind.
+#[must_use]
+pub fn unknown_commands(events: &[Event]) -> BTreeMap<u8, usize> {
+    let mut unknown = BTreeMap::new();
+    for event in events {
+        if let Message::Command(command) = &event.message {
+            if !command.is_known() {
+                *unknown.entry(command.kind()).or_default() += 1;
+            }
+        }
+    }
+    unknown
+}
+
+/// What to turn the meta commands into when converting to SEQ.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Commands {
+    /// Replace them all with tempo events at the header tempo.
+    #[default]
+    Tempo,
+    /// Use the standard MIDI equivalent, or a marker event naming it.
+    Marker,
+    /// Use the standard MIDI equivalent, or drop it.
+    Drop,
+}
+
+/// How loops are written when converting to SEQ.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Loops {
+    /// Repeat the body of every loop as many times as it plays.
+    #[default]
+    Unroll,
+    /// Keep the outermost loops as the NRPN loop markers of SEQ files,
+    /// unrolling the loops nested in them.
+    Nrpn,
+    /// Keep the outermost loops as `loopStart` and `loopEnd` marker events,
+    /// unrolling the loops nested in them.
+    Marker,
+}
+
+/// Converts the body of a CDS file into a SEQ track ending with `FF 2F 00`,
+/// writing loops as `loops` says and the meta commands SEQ lacks as
+/// `commands` says.
+///
+/// Fails if the events or loops can't be read or the track never ends.
+pub fn convert(
+    body: &[u8],
+    quarter_note_time: u32,
+    commands: Commands,
+    loops: Loops,
+) -> Result<Vec<u8>> {
+    if loops == Loops::Unroll {
+        return unroll(body, quarter_note_time, commands);
+    }
+
+    let events = parse_events(body)?;
+    let channel = events
+        .iter()
+        .find_map(|event| match event.message {
+            Message::Midi(midi::Message::Channel { status, .. }) => Some(status & 0xf),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let tree = loop_tree(events).ok_or(NO_END)?;
+
+    let mut writer = TrackWriter {
+        events: vec![],
+        delta: 0,
+        tempo: quarter_note_time,
+        commands,
+    };
+    for node in &tree {
+        match node {
+            Node::Loop {
+                count,
+                start_delta,
+                body,
+                finish_delta,
+            } => {
+                writer.delta += start_delta;
+                match loops {
+                    Loops::Nrpn => {
+                        writer.push(loop_controller(channel, midi::NRPN_LOOP_START));
+                        writer.push(midi::Message::Channel {
+                            status: 0xb0 | channel,
+                            data: [CONTROLLER_DATA_ENTRY, *count],
+                        });
+                    }
+                    _ => writer.push(loop_marker("loopStart")),
+                }
+                writer.unrolled(body);
+                writer.delta += finish_delta;
+                match loops {
+                    Loops::Nrpn => writer.push(loop_controller(channel, midi::NRPN_LOOP_END)),
+                    _ => writer.push(loop_marker("loopEnd")),
+                }
+                // Nothing after a loop that plays forever is ever reached
+                if *count == 0 {
+                    writer.push(midi::Message::EndOfTrack);
+                    break;
+                }
+            }
+            Node::Event(event) => writer.event(event),
+        }
+    }
+
+    let mut output = vec![];
+    seq::write_events(&writer.events, &mut output);
+    Ok(output)
+}
+
+/// Controller setting the data of the last parameter number.
+const CONTROLLER_DATA_ENTRY: u8 = 0x06;
+
+fn loop_controller(channel: u8, nrpn: u8) -> midi::Message {
+    midi::Message::Channel {
+        status: 0xb0 | channel,
+        data: [midi::CONTROLLER_NRPN_MSB, nrpn],
+    }
+}
+
+fn loop_marker(text: &str) -> midi::Message {
+    midi::Message::Meta {
+        kind: midi::META_MARKER,
+        data: text.as_bytes().to_vec(),
+    }
+}
+
+/// Events grouped into the loops they play in.
+#[derive(Debug)]
+enum Node {
+    Event(Event),
+    Loop {
+        count: u8,
+        start_delta: u32,
+        body: Vec<Node>,
+        finish_delta: u32,
+    },
+}
+
+/// Groups events into their loops, up to and including the global ending.
+/// Loops are balanced the same way as [`balance_tokens`] does, with extra
+/// loop finishes closing a loop that plays forever from the start, and an
+/// infinite loop finish ending the track.
+///
+/// Returns `None` if there's no global ending.
+fn loop_tree(events: Vec<Event>) -> Option<Vec<Node>> {
+    let mut root = vec![];
+    let mut open: Vec<(u8, u32, Vec<Node>)> = vec![];
+    let close = |root: &mut Vec<Node>, open: &mut Vec<(u8, u32, Vec<Node>)>, finish_delta| {
+        let node = match open.pop() {
+            Some((count, start_delta, body)) => Node::Loop {
+                count,
+                start_delta,
+                body,
+                finish_delta,
+            },
+            None => Node::Loop {
+                count: 0,
+                start_delta: 0,
+                body: std::mem::take(root),
+                finish_delta,
+            },
+        };
+        open.last_mut().map_or(root, |(_, _, body)| body).push(node);
+    };
+
+    for event in events {
+        match event.message {
+            Message::Command(Command::LoopStart(count)) => open.push((count, event.delta, vec![])),
+            Message::Command(Command::LoopFinish) => close(&mut root, &mut open, event.delta),
+            Message::Command(Command::InfiniteLoopFinish(_)) => {
+                close(&mut root, &mut open, event.delta);
+                while !open.is_empty() {
+                    close(&mut root, &mut open, 0);
+                }
+                root.push(Node::Event(Event {
+                    delta: 0,
+                    message: Message::Command(Command::GlobalEnding),
+                }));
+                return Some(root);
+            }
+            Message::Command(Command::GlobalEnding) => {
+                while !open.is_empty() {
+                    close(&mut root, &mut open, 0);
+                }
+                root.push(Node::Event(event));
+                return Some(root);
+            }
+            _ => open
+                .last_mut()
+                .map_or(&mut root, |(_, _, body)| body)
+                .push(Node::Event(event)),
+        }
+    }
+    None
+}
+
+/// Collects the events of a SEQ track, carrying the delta time of dropped
+/// events over to the next one.
+struct TrackWriter {
+    events: Vec<midi::Event>,
+    delta: u32,
+    tempo: u32,
+    commands: Commands,
+}
+
+impl TrackWriter {
+    fn push(&mut self, message: midi::Message) {
+        self.events.push(midi::Event {
+            delta: self.delta,
+            message,
+        });
+        self.delta = 0;
+    }
+
+    fn event(&mut self, event: &Event) {
+        self.delta += event.delta;
+        match &event.message {
+            Message::Midi(message) => self.push(message.clone()),
+            Message::Command(Command::GlobalEnding) => self.push(midi::Message::EndOfTrack),
+            Message::Command(command) => self.command(command),
+        }
+    }
+
+    fn command(&mut self, command: &Command) {
+        match (command.to_midi(), self.commands) {
+            (Some(message), _) => self.push(message),
+            (None, Commands::Tempo) => self.push(midi::Message::Tempo(self.tempo)),
+            (None, Commands::Marker) => self.push(command.to_marker()),
+            (None, Commands::Drop) => {}
+        }
+    }
+
+    /// Writes the nodes with their loops repeated, like
+    /// [`Lexeme::write_unrolled`].
+    fn unrolled(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Event(event) => self.event(event),
+                Node::Loop {
+                    count,
+                    start_delta,
+                    body,
+                    finish_delta,
+                } => {
+                    self.delta += start_delta;
+                    self.command(&Command::LoopStart(*count));
+                    for _ in 0..(*count).max(1) {
+                        self.unrolled(body);
+                        self.delta += finish_delta;
+                        self.command(&Command::LoopFinish);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Converts the body of a CDS file into a SEQ track ending with `FF 2F 00`,
+/// with every loop unrolled and the meta commands SEQ lacks converted as
+/// `commands` says.
+///
+/// Fails if the loops can't be lexed or the track never ends.
+pub fn unroll(body: &[u8], quarter_note_time: u32, commands: Commands) -> Result<Vec<u8>> {
+    let mut tokens = parse_file(body);
+    balance_tokens(&mut tokens);
+    trace!("{tokens:?}");
+
+    let lexemes = lex_file(&tokens)?;
+    trace!("{lexemes:?}");
+    for lexeme in &lexemes {
+        lexeme.visualise(0);
+    }
+
+    let mut output = vec![];
+    let mut has_infinite_loop = false;
+    for lexeme in &lexemes {
+        if matches!(lexeme, Lexeme::Loop(0, _)) {
+            has_infinite_loop = true;
+        }
+        lexeme.write_unrolled(&mut output);
+    }
+
+    let mut i = 0;
+    while i < output.len() {
+        let mut chunk = output.iter().skip(i).take(4);
+        if matches!(
+            [chunk.next(), chunk.next(), chunk.next()],
+            [Some(0xff), Some(0x32), Some(0x01)]
+        ) {
+            has_infinite_loop = true;
+            output.splice(i..i + 4, [0xff, 0x2f, 0x00]);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+
+    if commands != Commands::Tempo {
+        return convert_commands(&output, has_infinite_loop, commands)?.ok_or(NO_END);
+    }
+
+    dictionary(&mut output, quarter_note_time, has_infinite_loop);
+
+    let output_end = output
+        .windows(3)
+        .position(|c| c == [0xff, 0x2f, 0x00])
+        .ok_or(NO_END)?;
+    output.truncate(output_end + 3);
+    Ok(output)
+}
+
+/// Writes the unrolled events up to the loop finish or global ending that
+/// ends the track, the same one [`dictionary`] picks.
+fn convert_commands(
+    unrolled: &[u8],
+    has_infinite_loop: bool,
+    commands: Commands,
+) -> Result<Option<Vec<u8>>> {
+    let events = parse_events(unrolled)?;
+    let sentinel_count = events
+        .iter()
+        .filter(|event| {
+            matches!(
+                event.message,
+                Message::Command(Command::LoopFinish | Command::GlobalEnding)
+            )
+        })
+        .count();
+    let Some(last_sentinel) = sentinel_count.checked_sub(usize::from(has_infinite_loop)) else {
+        return Ok(None);
+    };
+
+    let mut converted = vec![];
+    let mut sentinel_index = 0;
+    let mut delta = 0;
+    for event in events {
+        delta += event.delta;
+        let message = match event.message {
+            Message::Midi(message) => message,
+            Message::Command(command) => {
+                if matches!(command, Command::LoopFinish | Command::GlobalEnding) {
+                    sentinel_index += 1;
+                    if sentinel_index == last_sentinel {
+                        converted.push(midi::Event {
+                            delta,
+                            message: midi::Message::EndOfTrack,
+                        });
+                        let mut output = vec![];
+                        seq::write_events(&converted, &mut output);
+                        return Ok(Some(output));
+                    }
+                }
+                match (command.to_midi(), commands) {
+                    (Some(message), _) => message,
+                    (None, Commands::Marker) => command.to_marker(),
+                    (None, _) => continue,
+                }
+            }
+        };
+        converted.push(midi::Event { delta, message });
+        delta = 0;
+    }
+    Ok(None)
+}
+
+fn dictionary(file: &mut Vec<u8>, quarter_note_time: u32, has_infinite_loop: bool) {
+    let sentinel_count = file
+        .windows(3)
+        .filter(|x| matches!(x, [0xff, 0x2f | 0x44, 0x00]))
+        .count();
+    let mut sentinel_index = 0;
+
+    let mut i = 0;
+    while i < file.len() {
+        let message = [file.get(i), file.get(i + 1), file.get(i + 2)]
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        if message.len() != 3 {
+            break;
+        }
+
+        let length = if message[0] == 0xff {
+            match [message[1], message[2]] {
+                [0x45, 0x06] => Some(9),
+                [0xf1, 0x04] => Some(7),
+                [0x39..=0x3f, 0x03] => Some(6),
+                [0x4b | 0x4c | 0x4d | 0x14 | 0x15 | 0x18 | 0x29 | 0x33..=0x36 | 0x38 | 0x50, 0x02] => {
+                    Some(5)
+                }
+                [0x00 | 0x0e | 0x01 | 0x1a | 0x1c | 0x02 | 0x2e | 0x06 | 0x07 | 0x10 | 0x24
+                | 0x31 | 0x25 | 0x48, 0x01] => Some(4),
+                [0x03 | 0x04 | 0x08 | 0x09 | 0x12 | 0x13 | 0x0a | 0x0b | 0x41..=0x43 | 0x49, 0x00] => {
+                    match file.get(i + 3) {
+                        Some(0xff) => None,
+                        _ => Some(3),
+                    }
+                }
+                [0x05, 0x03] => {
+                    file.splice(i..i + 3, [0xff, 0x51]);
+                    i += 3;
+                    None
+                }
+                [0x2f | 0x44, 0x00] => {
+                    sentinel_index += 1;
+                    if has_infinite_loop {
+                        if sentinel_index == sentinel_count - 1 {
+                            file[i + 1] = 0x2f;
+                            None
+                        } else {
+                            Some(3)
+                        }
+                    } else if sentinel_index == sentinel_count {
+                        file[i + 1] = 0x2f;
+                        None
+                    } else {
+                        Some(3)
+                    }
+                }
+                [0xf0, length] => Some(length as usize + 3),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        if let Some(length) = length {
+            file.splice(
+                i..i + length,
+                [0xff, 0x51]
+                    .into_iter()
+                    .chain(quarter_note_time.to_be_bytes().into_iter().skip(1)),
+            );
+            i += 5;
+        } else {
+            i += 1;
+        }
+    }
+}
+
+#[test]
+fn lexing() {
+    let body = [
+        0x00, 0x90, 0x3c, 0x64, 0xff, 0x2e, 0x01, 0x02, 0x10, 0x80, 0x3c, 0x00, 0xff, 0x2f, 0x00,
+        0x00, 0xff, 0x44, 0x00,
+    ];
+    let tokens = parse_file(&body);
+    assert_eq!(tokens.len(), 6);
+    let lexemes = lex_file(&tokens).unwrap();
+    assert_eq!(lexemes.len(), 4);
+    assert_eq!(
+        lexemes[1],
+        Lexeme::Loop(2, vec![Lexeme::Data(vec![0x10, 0x80, 0x3c, 0x00])])
+    );
+
+    let mut written = vec![];
+    for lexeme in &lexemes {
+        lexeme.write(&mut written);
+    }
+    assert_eq!(written, body);
+}
+
+#[test]
+fn balancing() {
+    let body = [
+        0x00, 0x90, 0x3c, 0x64, 0xff, 0x2f, 0x00, 0x00, 0xff, 0x44, 0x00,
+    ];
+    let mut tokens = parse_file(&body);
+    balance_tokens(&mut tokens);
+    assert_eq!(tokens[1], Token::LoopStart(0));
+    assert!(lex_file(&tokens).is_ok());
+}
+
+#[test]
+fn unrolling() {
+    let body = [
+        0x00, 0x90, 0x3c, 0x64, 0x00, 0xff, 0x2e, 0x01, 0x02, 0x10, 0x80, 0x3c, 0x00, 0x00, 0xff,
+        0x2f, 0x00, 0x00, 0xff, 0x44, 0x00,
+    ];
+    let track = unroll(&body, 500_000, Commands::Tempo).unwrap();
+    // Loop markers become tempo events, leaving only the last sentinel
+    assert_eq!(
+        track,
+        [
+            &[0x00, 0x90, 0x3c, 0x64, 0x00, 0xff, 0x51, 0x07, 0xa1, 0x20][..],
+            &[0x10, 0x80, 0x3c, 0x00, 0x00, 0xff, 0x51, 0x07, 0xa1, 0x20],
+            &[0x10, 0x80, 0x3c, 0x00, 0x00, 0xff, 0x51, 0x07, 0xa1, 0x20],
+            &[0x00, 0xff, 0x2f, 0x00],
+        ]
+        .concat()
+    );
+    let events = crate::seq::parse_events(&mut Reader::new(&track)).unwrap();
+    assert_eq!(events.len(), 7);
+    assert_eq!(unroll(&body[..4], 500_000, Commands::Tempo), Err(NO_END));
+    assert!(matches!(
+        convert(&body[..4], 500_000, Commands::Drop, Loops::Nrpn),
+        Err(Error::Truncated { field: "delta", .. })
+    ));
+}
+
+#[test]
+fn commands() {
+    let body = [
+        0x00, 0x90, 0x3c, 0x64, // Note on
+        0x00, 0xff, 0x05, 0x03, 0x06, 0x1a, 0x80, // Tempo
+        0x10, 0xff, 0x45, 0x06, 1, 2, 3, 4, 5, 6, // Undocumented
+        0x10, 0x3c, 0x00, // Running status note off
+        0x00, 0xff, 0x60, 0x01, 0x7f, // Unknown
+        0x00, 0xff, 0x44, 0x00, 0x00, 0x00, // Padding
+    ];
+    let events = parse_events(&body).unwrap();
+    assert_eq!(events.len(), 6);
+    // The status is written again after a command
+    let mut written = vec![];
+    write_events(&events, &mut written);
+    assert_eq!(written[..21], body[..21]);
+    assert_eq!(written[21..24], [0x10, 0x90, 0x3c]);
+    assert_eq!(written[24..], body[23..body.len() - 2]);
+    assert_eq!(events[1].message, Message::Command(Command::Tempo(400_000)));
+    assert_eq!(
+        unknown_commands(&events).into_iter().collect::<Vec<_>>(),
+        [(0x60, 1)]
+    );
+
+    let mut marker = vec![];
+    midi::write_var_len(0x10, &mut marker);
+    Command::parse(0x45, &[1, 2, 3, 4, 5, 6])
+        .to_marker()
+        .write(&mut marker);
+    assert!(marker.ends_with(b"CDS undocumented command 45 01 02 03 04 05 06"));
+    assert_eq!(Command::parse(0x45, &[1]).name(), "unknown command 45");
+    assert_eq!(Command::parse(0x60, &[1]).name(), "unknown command 60");
+    assert_eq!(
+        Command::parse(0xf1, &[1, 2, 3, 4]),
+        Command::Undocumented {
+            kind: 0xf1,
+            data: vec![1, 2, 3, 4]
+        }
+    );
+    assert_eq!(
+        Command::parse(0xf0, &[0x41, 0xf7]).to_midi(),
+        Some(midi::Message::SysEx(vec![0x41, 0xf7]))
+    );
+
+    let dropped = unroll(&body, 500_000, Commands::Drop).unwrap();
+    assert_eq!(
+        dropped,
+        [
+            0x00, 0x90, 0x3c, 0x64, // Note on
+            0x00, 0xff, 0x51, 0x06, 0x1a, 0x80, // Tempo
+            0x20, 0x90, 0x3c, 0x00, // Note off with the dropped delta
+            0x00, 0xff, 0x2f, 0x00,
+        ]
+    );
+    let marked = unroll(&body, 500_000, Commands::Marker).unwrap();
+    assert!(marked.windows(marker.len()).any(|window| window == marker));
+    assert!(crate::seq::parse_events(&mut Reader::new(&marked)).is_ok());
+}
+
+#[test]
+fn preserving_loops() {
+    let body = [
+        0x00, 0x91, 0x3c, 0x64, // Note on
+        0x00, 0xff, 0x2e, 0x01, 0x03, // Outer loop
+        0x10, 0xff, 0x2e, 0x01, 0x02, // Inner loop
+        0x10, 0x81, 0x3c, 0x00, // Note off
+        0x00, 0xff, 0x2f, 0x00, // Inner finish
+        0x08, 0xff, 0x2f, 0x00, // Outer finish
+        0x00, 0xff, 0x44, 0x00,
+    ];
+    let track = convert(&body, 500_000, Commands::Drop, Loops::Nrpn).unwrap();
+    assert_eq!(
+        track,
+        [
+            0x00, 0x91, 0x3c, 0x64, // Note on
+            0x00, 0xb1, 0x63, 0x14, 0x00, 0x06, 0x03, // Loop start with its count
+            0x20, 0x81, 0x3c, 0x00, // Both loop start deltas
+            0x10, 0x3c, 0x00, // Second pass of the inner loop
+            0x08, 0xb1, 0x63, 0x1e, // Loop end
+            0x00, 0xff, 0x2f, 0x00,
+        ]
+    );
+    let events = crate::seq::parse_events(&mut Reader::new(&track)).unwrap();
+    assert_eq!(
+        events[1].message.loop_marker(),
+        Some(midi::LoopMarker::Start)
+    );
+
+    let track = convert(&body, 500_000, Commands::Marker, Loops::Marker).unwrap();
+    let events = crate::seq::parse_events(&mut Reader::new(&track)).unwrap();
+    let markers = events
+        .iter()
+        .filter_map(|event| match &event.message {
+            midi::Message::Meta { data, .. } => Some(String::from_utf8_lossy(data)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        markers,
+        [
+            "loopStart",
+            "CDS loop start 02",
+            "CDS loop finish",
+            "CDS loop finish",
+            "loopEnd"
+        ]
+    );
+
+    // Extra finishes close loops from the start, and the track ends with them
+    let track = convert(&body[18..], 500_000, Commands::Drop, Loops::Nrpn).unwrap();
+    assert_eq!(
+        track,
+        [
+            0x00, 0xb0, 0x63, 0x14, 0x00, 0x06, 0x00, // Infinite loop start
+            0x08, 0x63, 0x1e, // Loop end, after the unrolled inner loop
+            0x00, 0xff, 0x2f, 0x00,
+        ]
+    );
+}
@@ -0,0 +1,445 @@
+//! DownLoadable Sounds banks, written from the instrument data of the other formats.
+//!
+//! Every region plays one mono 16-bit sample with its own volume envelope,
+//! which is all the converted banks need.
+
+use std::ops::Range;
+
+use log::warn;
+
+/// Which revision of the specification to target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Level {
+    /// Regions can't overlap and velocity ranges are ignored, so of the
+    /// regions sharing keys only the first is kept.
+    Level1,
+    #[default]
+    Level2,
+}
+
+const CONN_DST_PAN: u16 = 0x0004;
+const CONN_DST_EG1_ATTACKTIME: u16 = 0x0206;
+const CONN_DST_EG1_DECAYTIME: u16 = 0x0207;
+const CONN_DST_EG1_RELEASETIME: u16 = 0x0209;
+const CONN_DST_EG1_SUSTAINLEVEL: u16 = 0x020a;
+
+/// Bank flag of percussion instruments.
+const F_INSTRUMENT_DRUMS: u32 = 0x8000_0000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sample {
+    pub name: String,
+    pub samples: Vec<i16>,
+    pub sample_rate: u32,
+    /// Loop points relative to the start of the sample, with the end exclusive.
+    pub loop_range: Option<Range<u32>>,
+}
+
+/// The volume envelope and pan of a region.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Articulation {
+    /// Seconds to rise to full volume.
+    pub attack: f32,
+    /// Seconds to fall from full volume to silence at the decay rate.
+    pub decay: f32,
+    /// Amplitude held after the decay, from 0 to 1.
+    pub sustain_level: f32,
+    /// Seconds to fall from full volume to silence once released.
+    pub release: f32,
+    /// From -1 for left to 1 for right.
+    pub pan: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub key_range: (u8, u8),
+    pub velocity_range: (u8, u8),
+    /// Index of the sample played.
+    pub sample: u32,
+    pub unity_note: u8,
+    /// Tuning in cents, on top of the unity note.
+    pub fine_tune: i16,
+    /// Attenuation in centibels.
+    pub attenuation: i32,
+    pub looped: bool,
+    pub articulation: Articulation,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instrument {
+    pub name: String,
+    /// MIDI bank, with 128 being the percussion bank like in SoundFonts.
+    pub bank: u16,
+    pub program: u8,
+    pub regions: Vec<Region>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Dls {
+    pub name: String,
+    pub samples: Vec<Sample>,
+    pub instruments: Vec<Instrument>,
+}
+
+impl Dls {
+    pub fn write(&self, level: Level, out: &mut Vec<u8>) {
+        let mut body = b"DLS ".to_vec();
+        chunk(
+            &mut body,
+            b"colh",
+            &(self.instruments.len() as u32).to_le_bytes(),
+        );
+
+        let mut lins = b"lins".to_vec();
+        for instrument in &self.instruments {
+            let regions = match level {
+                Level::Level1 => level1_regions(instrument),
+                Level::Level2 => instrument.regions.iter().collect(),
+            };
+            let mut ins = b"ins ".to_vec();
+            let mut insh = (regions.len() as u32).to_le_bytes().to_vec();
+            insh.extend(locale_bank(instrument.bank).to_le_bytes());
+            insh.extend((instrument.program as u32).to_le_bytes());
+            chunk(&mut ins, b"insh", &insh);
+
+            let mut lrgn = b"lrgn".to_vec();
+            for region in regions {
+                let sample = &self.samples[region.sample as usize];
+                let mut rgn = match level {
+                    Level::Level1 => b"rgn ".to_vec(),
+                    Level::Level2 => b"rgn2".to_vec(),
+                };
+                let velocity_range = match level {
+                    Level::Level1 => (0, 127),
+                    Level::Level2 => region.velocity_range,
+                };
+                let mut rgnh = vec![];
+                for field in [
+                    region.key_range.0,
+                    region.key_range.1,
+                    velocity_range.0,
+                    velocity_range.1,
+                ] {
+                    rgnh.extend((field as u16).to_le_bytes());
+                }
+                // Options and key group
+                rgnh.extend([0; 4]);
+                chunk(&mut rgn, b"rgnh", &rgnh);
+                chunk(
+                    &mut rgn,
+                    b"wsmp",
+                    &wsmp(
+                        region.unity_note,
+                        region.fine_tune,
+                        region.attenuation,
+                        sample.loop_range.as_ref().filter(|_| region.looped),
+                    ),
+                );
+                let mut wlnk = vec![];
+                // Options and phase group, then the mono channel
+                wlnk.extend([0; 4]);
+                wlnk.extend(1u32.to_le_bytes());
+                wlnk.extend(region.sample.to_le_bytes());
+                chunk(&mut rgn, b"wlnk", &wlnk);
+
+                let (list, art) = match level {
+                    Level::Level1 => (b"lart", b"art1"),
+                    Level::Level2 => (b"lar2", b"art2"),
+                };
+                let mut lart = list.to_vec();
+                chunk(&mut lart, art, &articulation(&region.articulation));
+                chunk(&mut rgn, b"LIST", &lart);
+
+                chunk(&mut lrgn, b"LIST", &rgn);
+            }
+            chunk(&mut ins, b"LIST", &lrgn);
+            chunk(&mut ins, b"LIST", &info(&instrument.name));
+            chunk(&mut lins, b"LIST", &ins);
+        }
+        chunk(&mut body, b"LIST", &lins);
+
+        let mut wvpl = b"wvpl".to_vec();
+        let mut ptbl = 8u32.to_le_bytes().to_vec();
+        ptbl.extend((self.samples.len() as u32).to_le_bytes());
+        for sample in &self.samples {
+            // Offsets are from the start of the pool's contents
+            ptbl.extend(((wvpl.len() - 4) as u32).to_le_bytes());
+
+            let mut wave = b"wave".to_vec();
+            let mut fmt = vec![];
+            fmt.extend(1u16.to_le_bytes());
+            fmt.extend(1u16.to_le_bytes());
+            fmt.extend(sample.sample_rate.to_le_bytes());
+            fmt.extend((sample.sample_rate * 2).to_le_bytes());
+            fmt.extend(2u16.to_le_bytes());
+            fmt.extend(16u16.to_le_bytes());
+            chunk(&mut wave, b"fmt ", &fmt);
+            chunk(
+                &mut wave,
+                b"wsmp",
+                &wsmp(60, 0, 0, sample.loop_range.as_ref()),
+            );
+            let data = sample
+                .samples
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect::<Vec<_>>();
+            chunk(&mut wave, b"data", &data);
+            chunk(&mut wave, b"LIST", &info(&sample.name));
+            chunk(&mut wvpl, b"LIST", &wave);
+        }
+        chunk(&mut body, b"ptbl", &ptbl);
+        chunk(&mut body, b"LIST", &wvpl);
+        chunk(&mut body, b"LIST", &info(&self.name));
+
+        chunk(out, b"RIFF", &body);
+    }
+}
+
+/// The regions of an instrument that are kept at level 1, where velocity is
+/// ignored and regions can't overlap. When the key ranges of several regions
+/// overlap, such as velocity layers, only the first one is kept.
+fn level1_regions(instrument: &Instrument) -> Vec<&Region> {
+    let mut kept = Vec::<&Region>::with_capacity(instrument.regions.len());
+    for region in &instrument.regions {
+        let overlaps = kept.iter().any(|other| {
+            region.key_range.0 <= other.key_range.1 && other.key_range.0 <= region.key_range.1
+        });
+        if !overlaps {
+            kept.push(region);
+        }
+    }
+    let dropped = instrument.regions.len() - kept.len();
+    if dropped != 0 {
+        warn!(
+            "Dropped {dropped} regions of {:?} that overlap others once velocity is ignored",
+            instrument.name
+        );
+    }
+    kept
+}
+
+/// The bank field of an instrument locale, with the bank number as the bank
+/// select MSB.
+fn locale_bank(bank: u16) -> u32 {
+    if bank == 128 {
+        F_INSTRUMENT_DRUMS
+    } else {
+        ((bank as u32) & 0x7f) << 8
+    }
+}
+
+fn wsmp(
+    unity_note: u8,
+    fine_tune: i16,
+    attenuation: i32,
+    loop_range: Option<&Range<u32>>,
+) -> Vec<u8> {
+    let mut wsmp = 20u32.to_le_bytes().to_vec();
+    wsmp.extend((unity_note as u16).to_le_bytes());
+    wsmp.extend(fine_tune.to_le_bytes());
+    // Gain is in 1/65536 centibels
+    wsmp.extend((-attenuation.clamp(0, 0x7fff) << 16).to_le_bytes());
+    // Don't truncate or compress the sample
+    wsmp.extend(3u32.to_le_bytes());
+    wsmp.extend((loop_range.is_some() as u32).to_le_bytes());
+    if let Some(range) = loop_range {
+        wsmp.extend(16u32.to_le_bytes());
+        // Forward loop
+        wsmp.extend(0u32.to_le_bytes());
+        wsmp.extend(range.start.to_le_bytes());
+        wsmp.extend((range.end - range.start).to_le_bytes());
+    }
+    wsmp
+}
+
+fn articulation(articulation: &Articulation) -> Vec<u8> {
+    let connections = [
+        (CONN_DST_EG1_ATTACKTIME, time(articulation.attack)),
+        (CONN_DST_EG1_DECAYTIME, time(articulation.decay)),
+        (
+            CONN_DST_EG1_SUSTAINLEVEL,
+            sustain_level(articulation.sustain_level),
+        ),
+        (CONN_DST_EG1_RELEASETIME, time(articulation.release)),
+        (
+            CONN_DST_PAN,
+            ((articulation.pan.clamp(-1.0, 1.0) * 500.0) as i32) << 16,
+        ),
+    ];
+
+    let mut art = 8u32.to_le_bytes().to_vec();
+    art.extend((connections.len() as u32).to_le_bytes());
+    for (destination, scale) in connections {
+        // No source, control or transform
+        art.extend([0; 4]);
+        art.extend(destination.to_le_bytes());
+        art.extend(0u16.to_le_bytes());
+        art.extend(scale.to_le_bytes());
+    }
+    art
+}
+
+/// Converts an amplitude to the sustain level of the articulation, which is
+/// in 1/65536 of a tenth of a percent of the 96 dB range of the envelope.
+fn sustain_level(amplitude: f32) -> i32 {
+    let attenuation = crate::sf2::centibels(amplitude) as f32;
+    (((1.0 - attenuation / 960.0).clamp(0.0, 1.0) * 1000.0) as i32) << 16
+}
+
+/// Converts seconds to the 1/65536 timecents of the articulation.
+fn time(seconds: f32) -> i32 {
+    if seconds <= 0.0 {
+        i32::MIN
+    } else {
+        crate::sf2::timecents(seconds).clamp(-0x7fff, 0x7fff) << 16
+    }
+}
+
+fn info(name: &str) -> Vec<u8> {
+    let mut info = b"INFO".to_vec();
+    let mut text = name.as_bytes().to_vec();
+    text.push(0);
+    chunk(&mut info, b"INAM", &text);
+    info
+}
+
+fn chunk(out: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+    out.extend(id);
+    out.extend((body.len() as u32).to_le_bytes());
+    out.extend(body);
+    if body.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+#[test]
+fn layout() {
+    let dls = Dls {
+        name: "Test".to_owned(),
+        samples: vec![Sample {
+            name: "Sample".to_owned(),
+            samples: vec![1, 2, 3, 4],
+            sample_rate: 22050,
+            loop_range: Some(1..4),
+        }],
+        instruments: vec![Instrument {
+            name: "Drums".to_owned(),
+            bank: 128,
+            program: 3,
+            regions: vec![Region {
+                key_range: (0, 127),
+                velocity_range: (0, 127),
+                sample: 0,
+                unity_note: 60,
+                fine_tune: 0,
+                attenuation: 0,
+                looped: true,
+                articulation: Articulation {
+                    attack: 0.0,
+                    decay: 1.0,
+                    sustain_level: 1.0,
+                    release: 1.0,
+                    pan: 0.0,
+                },
+            }],
+        }],
+    };
+
+    for (level, region) in [(Level::Level1, b"rgn "), (Level::Level2, b"rgn2")] {
+        let mut bytes = vec![];
+        dls.write(level, &mut bytes);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize,
+            bytes.len() - 8
+        );
+        assert_eq!(&bytes[8..12], b"DLS ");
+        assert!(bytes.windows(4).any(|x| x == region));
+
+        let insh = bytes.windows(4).position(|x| x == b"insh").unwrap() + 8;
+        assert_eq!(
+            bytes[insh..insh + 12],
+            [1, 0, 0, 0, 0, 0, 0, 0x80, 3, 0, 0, 0]
+        );
+
+        // The first wsmp is the region's, ending with its loop of three samples
+        let wsmp = bytes.windows(4).position(|x| x == b"wsmp").unwrap() + 4;
+        let size = u32::from_le_bytes(bytes[wsmp..wsmp + 4].try_into().unwrap()) as usize;
+        assert_eq!(size, 20 + 16);
+        assert_eq!(
+            bytes[wsmp + 4 + size - 8..wsmp + 4 + size],
+            [1, 0, 0, 0, 3, 0, 0, 0]
+        );
+
+        // The pool table points at the wave list
+        let ptbl = bytes.windows(4).position(|x| x == b"ptbl").unwrap() + 8;
+        let wvpl = bytes.windows(4).position(|x| x == b"wvpl").unwrap() + 4;
+        let offset = u32::from_le_bytes(bytes[ptbl + 8..ptbl + 12].try_into().unwrap());
+        assert_eq!(&bytes[wvpl + offset as usize..][8..12], b"wave");
+    }
+}
+
+#[test]
+fn velocity_layers() {
+    let layer = |velocity_range, sample| Region {
+        key_range: (36, 72),
+        velocity_range,
+        sample,
+        unity_note: 60,
+        fine_tune: 0,
+        attenuation: 0,
+        looped: false,
+        articulation: Articulation {
+            attack: 0.0,
+            decay: 1.0,
+            sustain_level: 1.0,
+            release: 1.0,
+            pan: 0.0,
+        },
+    };
+    let instrument = Instrument {
+        name: "Piano".to_owned(),
+        bank: 0,
+        program: 0,
+        regions: vec![
+            layer((0, 63), 0),
+            layer((64, 127), 1),
+            Region {
+                key_range: (73, 96),
+                ..layer((0, 127), 1)
+            },
+        ],
+    };
+    let kept = level1_regions(&instrument);
+    assert_eq!(kept, [&instrument.regions[0], &instrument.regions[2]]);
+
+    let dls = Dls {
+        name: "Test".to_owned(),
+        samples: vec![
+            Sample {
+                name: "Soft".to_owned(),
+                samples: vec![0; 4],
+                sample_rate: 22050,
+                loop_range: None,
+            };
+            2
+        ],
+        instruments: vec![instrument],
+    };
+    for (level, id, count) in [(Level::Level1, b"rgn ", 2), (Level::Level2, b"rgn2", 3)] {
+        let mut bytes = vec![];
+        dls.write(level, &mut bytes);
+        assert_eq!(bytes.windows(4).filter(|x| x == id).count(), count);
+        let insh = bytes.windows(4).position(|x| x == b"insh").unwrap() + 8;
+        assert_eq!(bytes[insh], count as u8);
+    }
+}
+
+#[test]
+fn sustain() {
+    assert_eq!(sustain_level(1.0), 1000 << 16);
+    assert_eq!(sustain_level(0.0), 0);
+    // -48 dB is half of the envelope's range
+    assert_eq!(sustain_level(0.003_981) >> 16, 500);
+}
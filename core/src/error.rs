@@ -0,0 +1,143 @@
+use std::fmt;
+
+/// Everything that can go wrong while parsing one of the supported formats.
+///
+/// Offsets are from the start of the buffer given to the parser.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The file ended before `field` could be read.
+    Truncated {
+        field: &'static str,
+        offset: usize,
+        size: usize,
+        len: usize,
+    },
+    /// The magic number at `offset` doesn't match the format.
+    BadMagic {
+        format: &'static str,
+        offset: usize,
+        expected: [u8; 4],
+        found: [u8; 4],
+    },
+    UnsupportedVersion {
+        format: &'static str,
+        version: u32,
+    },
+    /// The file is valid, but uses something the tools can't handle.
+    Unsupported {
+        format: &'static str,
+        feature: &'static str,
+    },
+    /// A sequence event that can't be read, like a data byte with no running status.
+    BadEvent {
+        format: &'static str,
+        offset: usize,
+        status: u8,
+    },
+    /// A line of a text listing that can't be assembled.
+    Syntax {
+        line: usize,
+        reason: String,
+    },
+    /// A sequence whose loops don't nest or that never ends.
+    BadTrack {
+        format: &'static str,
+        reason: &'static str,
+    },
+    /// A chunk that the format requires is missing.
+    MissingChunk {
+        format: &'static str,
+        chunk: [u8; 4],
+    },
+    /// An index in the file refers to an entry that doesn't exist.
+    IndexOutOfRange {
+        field: &'static str,
+        index: i64,
+        len: usize,
+    },
+    /// An offset or size in the file points past `limit`, usually the end of the file.
+    OffsetOutOfRange {
+        field: &'static str,
+        offset: usize,
+        limit: usize,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated {
+                field,
+                offset,
+                size,
+                len,
+            } => write!(
+                f,
+                "truncated at offset {offset:#x} reading `{field}` ({size} bytes, file is {len:#x} bytes)"
+            ),
+            Self::BadMagic {
+                format,
+                offset,
+                expected,
+                found,
+            } => write!(
+                f,
+                "bad {format} magic number at offset {offset:#x}, expected \"{}\", found \"{}\"",
+                expected.escape_ascii(),
+                found.escape_ascii(),
+            ),
+            Self::UnsupportedVersion { format, version } => {
+                write!(f, "unsupported {format} version {version}")
+            }
+            Self::Unsupported { format, feature } => {
+                write!(f, "unsupported {format} file, {feature}")
+            }
+            Self::BadEvent {
+                format,
+                offset,
+                status,
+            } => write!(f, "bad {format} event {status:#04x} at offset {offset:#x}"),
+            Self::Syntax { line, reason } => write!(f, "line {line}: {reason}"),
+            Self::BadTrack { format, reason } => write!(f, "bad {format} track, {reason}"),
+            Self::MissingChunk { format, chunk } => {
+                write!(f, "{format} file has no \"{}\" chunk", chunk.escape_ascii())
+            }
+            Self::IndexOutOfRange { field, index, len } => {
+                write!(f, "`{field}` {index} is out of range, only {len} available")
+            }
+            Self::OffsetOutOfRange {
+                field,
+                offset,
+                limit,
+            } => write!(f, "`{field}` {offset:#x} is out of bounds, limit is {limit:#x}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[test]
+fn display() {
+    assert_eq!(
+        Error::Truncated {
+            field: "num_zones",
+            offset: 0x10,
+            size: 4,
+            len: 0x12
+        }
+        .to_string(),
+        "truncated at offset 0x10 reading `num_zones` (4 bytes, file is 0x12 bytes)"
+    );
+    assert_eq!(
+        Error::BadMagic {
+            format: "VAG",
+            offset: 0,
+            expected: *b"VAGp",
+            found: [0, 1, b'A', b'B'],
+        }
+        .to_string(),
+        "bad VAG magic number at offset 0x0, expected \"VAGp\", found \"\\x00\\x01AB\""
+    );
+}
@@ -0,0 +1,110 @@
+//! Telling the formats apart by their magic numbers, for files whose
+//! extension is wrong or missing.
+
+use std::fmt::Display;
+
+use crate::{ads, cds, msq, mus, seq, snd, vab, vag};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Ads,
+    Cds,
+    Msq,
+    Mus,
+    Sam,
+    Seq,
+    Smp,
+    Snd,
+    Vab,
+    Vag,
+}
+
+impl Format {
+    /// Finds the format of a file from its first bytes. SAM and SMP files are
+    /// only found when they start with their optional magic number.
+    #[must_use]
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        let magic = bytes.get(0..4)?;
+        Some(match magic {
+            _ if magic == ads::MAGIC => Self::Ads,
+            _ if magic == cds::MAGIC && is_msq(bytes) => Self::Msq,
+            _ if magic == cds::MAGIC => Self::Cds,
+            _ if magic == msq::MAGIC.to_le_bytes() => Self::Msq,
+            _ if magic == mus::HEADER_MAGIC.to_be_bytes() => Self::Mus,
+            _ if magic == mus::SAM_HAS_HEADER_MAGIC_NUMBER => Self::Sam,
+            _ if magic == seq::MAGIC => Self::Seq,
+            _ if magic == snd::SMP_MAGIC.to_le_bytes() => Self::Smp,
+            _ if magic == snd::MAGIC.to_le_bytes() => Self::Snd,
+            _ if magic == vab::MAGIC => Self::Vab,
+            _ if magic == vag::MAGIC || magic == vag::MAGIC_INTERLEAVED => Self::Vag,
+            _ => return None,
+        })
+    }
+
+    /// The extension files of the format usually have.
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ads => "ads",
+            Self::Cds => "cds",
+            Self::Msq => "msq",
+            Self::Mus => "mus",
+            Self::Sam => "sam",
+            Self::Seq => "seq",
+            Self::Smp => "smp",
+            Self::Snd => "snd",
+            Self::Vab => "vab",
+            Self::Vag => "vag",
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension().to_ascii_uppercase())
+    }
+}
+
+/// Whether a file starting with the CDS magic number is an MSQ file, which
+/// is the case when the first track starts right after the track offsets.
+fn is_msq(bytes: &[u8]) -> bool {
+    let (Some(num_tracks), Some(first_track)) = (bytes.get(12..14), bytes.get(16..20)) else {
+        return false;
+    };
+    let num_tracks = u16::from_le_bytes([num_tracks[0], num_tracks[1]]) as usize;
+    num_tracks != 0 && first_track == ((msq::HEADER_SIZE + 4 * num_tracks) as u32).to_le_bytes()
+}
+
+#[test]
+fn detection() {
+    let mut vag = vec![];
+    vag::VagHeader::new(0x6270, 22050).write(&mut vag);
+    assert_eq!(Format::detect(&vag), Some(Format::Vag));
+
+    let header = msq::MsqHeader {
+        magic: msq::CDS_MAGIC,
+        quarter_note_time: 500_000,
+        ppqn: 480,
+        version: 0x0101,
+        num_tracks: 0,
+        _padding: 0,
+    };
+    let mut msq = vec![];
+    msq::MsqFile::write(&header, &[&[0, 0xff, 0x2f, 0]], &mut msq);
+    assert_eq!(Format::detect(&msq), Some(Format::Msq));
+
+    let mut cds = vec![];
+    header.cds_header().write(&mut cds);
+    cds.extend([0, 0x90, 60, 100, 0, 0xff, 0x2f, 0]);
+    assert_eq!(Format::detect(&cds), Some(Format::Cds));
+    assert_eq!(Format::detect(&cds[0..12]), Some(Format::Cds));
+
+    assert_eq!(Format::detect(b"Mus!"), Some(Format::Mus));
+    assert_eq!(Format::detect(b"DNSa"), Some(Format::Snd));
+    assert_eq!(Format::detect(b"PMSa"), Some(Format::Smp));
+    assert_eq!(Format::detect(b"RIFF"), None);
+    assert_eq!(Format::detect(b"VAGi"), Some(Format::Vag));
+    assert_eq!(Format::detect(b"VAG"), None);
+    assert_eq!(Format::Msq.to_string(), "MSQ");
+}
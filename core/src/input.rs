@@ -0,0 +1,73 @@
+//! Which files the tools read from an input folder, with the options shared by all of them.
+
+use std::path::Path;
+
+use glob::Pattern;
+
+// Input options, flattened into the arguments of every tool that reads folders.
+// Not a doc comment, as clap would use it for the description of the tool.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct InputArgs {
+    /// Also read the files in the folders inside an input folder.
+    #[clap(short, long)]
+    pub recursive: bool,
+    /// Only read files in an input folder with these extensions, instead of the ones the tool
+    /// usually reads. `*` reads every file.
+    #[clap(long, value_delimiter = ',')]
+    pub ext: Vec<String>,
+    /// Only read files in an input folder whose path inside it matches one of these patterns,
+    /// like `*.vag` or `music/**`.
+    #[clap(long)]
+    pub glob: Vec<Pattern>,
+    /// Follow symbolic links to folders while looking for files.
+    #[clap(long)]
+    pub follow_symlinks: bool,
+}
+
+impl InputArgs {
+    /// Whether a file found in an input folder should be read, where
+    /// `relative` is its path inside the folder and `extensions` the ones
+    /// the tool reads by default, any when empty.
+    #[must_use]
+    pub fn matches(&self, relative: &Path, extensions: &[&str]) -> bool {
+        let extensions = if self.ext.is_empty() {
+            extensions.iter().map(|ext| (*ext).to_owned()).collect()
+        } else {
+            self.ext.clone()
+        };
+        let has_extension = extensions.is_empty()
+            || extensions.iter().any(|ext| {
+                ext == "*"
+                    || relative.extension().is_some_and(|found| {
+                        found
+                            .to_string_lossy()
+                            .eq_ignore_ascii_case(ext.trim_start_matches('.'))
+                    })
+            });
+        has_extension
+            && (self.glob.is_empty()
+                || self
+                    .glob
+                    .iter()
+                    .any(|pattern| pattern.matches_path(relative)))
+    }
+}
+
+#[test]
+fn matching() {
+    let mut input = InputArgs::default();
+    assert!(input.matches(Path::new("a.bin"), &[]));
+    assert!(input.matches(Path::new("sub/a.VAG"), &["vag"]));
+    assert!(!input.matches(Path::new("a.txt"), &["vag"]));
+    assert!(!input.matches(Path::new("vag"), &["vag"]));
+
+    input.ext = vec![".txt".to_owned()];
+    assert!(input.matches(Path::new("a.txt"), &["vag"]));
+    assert!(!input.matches(Path::new("a.vag"), &["vag"]));
+    input.ext = vec!["*".to_owned()];
+    assert!(input.matches(Path::new("a.vag"), &["vag"]));
+
+    input.glob = vec![Pattern::new("music/**").unwrap()];
+    assert!(input.matches(Path::new("music/sub/a.vag"), &["vag"]));
+    assert!(!input.matches(Path::new("sfx/a.vag"), &["vag"]));
+}
@@ -7,40 +7,125 @@ use simplelog::TermLogger;
 
 pub use clap;
 pub use log;
+pub use serde_json;
+
+pub mod adpcm;
+pub mod ads;
+pub mod adsr;
+pub mod batch;
+pub mod cds;
+pub mod dls;
+mod error;
+pub mod format;
+pub mod input;
+pub mod listing;
+pub mod midi;
+pub mod msq;
+pub mod mus;
+pub mod output;
+mod reader;
+pub mod report;
+pub mod seq;
+pub mod sf2;
+pub mod snd;
+pub mod vab;
+pub mod vag;
+pub mod wav;
+
+pub use error::{Error, Result};
+pub use reader::Reader;
 
 /// Perform initialisation functions that are common across
 /// all unLoKable projects. Primarily initalising debugging.
 pub fn init() {
-    TermLogger::init(
-        if cfg!(debug_assertions) {
-            log::LevelFilter::Debug
-        } else {
-            log::LevelFilter::Info
-        },
+    let level = if cfg!(debug_assertions) {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Info
+    };
+    log::set_boxed_logger(Box::new(batch::Logger(TermLogger::new(
+        level,
         simplelog::ConfigBuilder::new()
             .set_thread_level(log::LevelFilter::Off)
             .build(),
         simplelog::TerminalMode::Mixed,
         simplelog::ColorChoice::Auto,
-    )
+    ))))
     .unwrap();
+    log::set_max_level(level);
 }
 
 /// Takes a file path and returns either an iterator of the file path,
-/// or if the path is to a directory a list of the files in the path.
+/// or if the path is to a directory a sorted list of the files in the path
+/// that `input` selects, using the tool's usual `extensions`.
 ///
 /// Displays an error and quits if there is an error.
-#[must_use] pub fn get_files(path: &Path) -> Vec<PathBuf> {
-    if path.is_dir() {
-        std::fs::read_dir(path)
-            .unwrap_or_else(|e| {
-                log::error!("Unable to load paths `{:?}`, aborting: {e}", path);
+#[must_use]
+pub fn get_files(path: &Path, input: &input::InputArgs, extensions: &[&str]) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return std::iter::once(path.to_owned()).collect();
+    }
+    let mut walk = walkdir::WalkDir::new(path)
+        .min_depth(1)
+        .follow_links(input.follow_symlinks)
+        .sort_by_file_name();
+    if !input.recursive {
+        walk = walk.max_depth(1);
+    }
+    walk.into_iter()
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            // The input folder itself can't be read
+            Err(e) if e.depth() == 0 => {
+                log::error!("Unable to load paths `{path:?}`, aborting: {e}");
                 exit(1)
-            })
-            .flatten()
-            .map(|f| f.path())
-            .collect()
-    } else {
-        std::iter::once(path.to_owned()).collect()
+            }
+            Err(e) => {
+                log::warn!("Unable to load a path, skipping: {e}");
+                None
+            }
+        })
+        .filter(|entry| entry.path().is_file())
+        .map(walkdir::DirEntry::into_path)
+        .filter(|file| input.matches(file.strip_prefix(path).unwrap_or(file), extensions))
+        .collect()
+}
+
+#[test]
+fn files() {
+    let temp = std::env::temp_dir().join(format!("unlokable_files_{}", std::process::id()));
+    std::fs::create_dir_all(temp.join("b/c")).unwrap();
+    for file in ["z.vag", "a.vag", "a.txt", "b/b.vag", "b/c/c.vag"] {
+        std::fs::write(temp.join(file), []).unwrap();
     }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(temp.join("b"), temp.join("link")).unwrap();
+
+    let mut input = input::InputArgs::default();
+    let found = |input: &input::InputArgs| {
+        get_files(&temp, input, &["vag"])
+            .into_iter()
+            .map(|file| file.strip_prefix(&temp).unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(found(&input), [Path::new("a.vag"), Path::new("z.vag")]);
+    input.recursive = true;
+    assert_eq!(
+        found(&input),
+        ["a.vag", "b/b.vag", "b/c/c.vag", "z.vag"].map(Path::new)
+    );
+    #[cfg(unix)]
+    {
+        input.follow_symlinks = true;
+        assert_eq!(found(&input).len(), 6);
+    }
+    input.glob = vec![glob::Pattern::new("b/*").unwrap()];
+    input.ext = vec!["*".to_owned()];
+    assert_eq!(
+        get_files(&temp.join("a.txt"), &input, &["vag"]),
+        [temp.join("a.txt")]
+    );
+    assert_eq!(found(&input), ["b/b.vag", "b/c/c.vag"].map(Path::new));
+
+    std::fs::remove_dir_all(temp).unwrap();
 }
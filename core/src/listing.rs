@@ -0,0 +1,815 @@
+//! A text listing of sequence events, one per line, for reading and hand
+//! editing sequences, which can be assembled back into the file.
+//!
+//! Each event line is the delta time followed by the event name and its
+//! arguments as `name=value` pairs, with the absolute tick and offset in the
+//! file in a trailing comment. Lines starting with `.` hold the header and
+//! anything after a `;` is a comment.
+
+use std::{fmt::Write, ops::Range};
+
+use crate::{
+    cds::{self, Command, Lexeme},
+    midi::{self, LoopMarker},
+    msq::{self, MsqFile, MsqHeader},
+    seq, Error, Reader, Result,
+};
+
+/// Column the comments of event lines start at.
+const COMMENT_COLUMN: usize = 48;
+
+/// Lists a whole CDS file.
+pub fn cds(file: &[u8]) -> Result<String> {
+    let header = cds::Header::parse(&mut Reader::new(file))?;
+    let mut out = String::new();
+    writeln!(
+        out,
+        ".cds quarter_note_time={} ppqn={} version={:#06x}",
+        header.quarter_note_time, header.ppqn, header.version
+    )
+    .unwrap();
+    cds_track(&mut out, file, cds::HEADER_SIZE..file.len());
+    Ok(out)
+}
+
+/// Lists every track of an MSQ file, each after a `.track` line.
+pub fn msq(file: &[u8]) -> Result<String> {
+    let msq = MsqFile::parse(file)?;
+    let mut out = String::new();
+    writeln!(
+        out,
+        ".msq magic={:#010x} quarter_note_time={} ppqn={} version={:#06x} padding={:#06x}",
+        msq.header.magic,
+        msq.header.quarter_note_time,
+        msq.header.ppqn,
+        msq.header.version,
+        msq.header._padding
+    )
+    .unwrap();
+    for (index, track) in msq.tracks.into_iter().enumerate() {
+        writeln!(out, "\n.track {index}").unwrap();
+        cds_track(&mut out, file, track);
+    }
+    Ok(out)
+}
+
+/// Lists a whole SEQ file.
+pub fn seq(file: &[u8]) -> Result<String> {
+    let mut bytes = Reader::new(file);
+    let header = seq::Header::parse(&mut bytes)?;
+    let mut out = String::new();
+    writeln!(
+        out,
+        ".seq version={} ppqn={} tempo={} time_signature={}/{}",
+        header.version, header.ppqn, header.tempo, header.time_signature.0, header.time_signature.1
+    )
+    .unwrap();
+
+    let mut tick = 0;
+    let mut running_status = None;
+    loop {
+        let offset = bytes.position();
+        let event = match seq::read_event(&mut bytes, &mut running_status) {
+            Ok(event) => event,
+            Err(e) => {
+                writeln!(out, "; error: {e}").unwrap();
+                return Ok(out);
+            }
+        };
+        tick += event.delta;
+
+        let mut comment = format!("tick {tick} at {offset:#x}");
+        match event.message.loop_marker() {
+            Some(LoopMarker::Start) => comment += ", loop start",
+            Some(LoopMarker::End) => comment += ", loop end",
+            None => {}
+        }
+        event_line(
+            &mut out,
+            event.delta,
+            0,
+            &message_text(&event.message),
+            &comment,
+        );
+
+        if event.message == midi::Message::EndOfTrack {
+            break;
+        }
+    }
+    trailing_bytes(&mut out, bytes.remaining(), "end of track");
+    Ok(out)
+}
+
+fn cds_track(out: &mut String, file: &[u8], range: Range<usize>) {
+    let body = &file[range.clone()];
+
+    let mut tokens = cds::parse_file(body);
+    let token_count = tokens.len();
+    cds::balance_tokens(&mut tokens);
+    if tokens.len() == token_count {
+        writeln!(out, "; loop tree:").unwrap();
+    } else {
+        writeln!(out, "; loop tree, after balancing the loop markers:").unwrap();
+    }
+    match cds::lex_file(&tokens) {
+        Ok(lexemes) => {
+            for lexeme in &lexemes {
+                loop_tree(out, lexeme, 1);
+            }
+        }
+        Err(e) => writeln!(out, ";   unable to lex the loops: {e}").unwrap(),
+    }
+
+    let mut bytes = Reader::new(body);
+    let mut tick = 0;
+    let mut depth = 0usize;
+    let mut running_status = None;
+    while !bytes.is_empty() {
+        let offset = range.start + bytes.position();
+        let event = match cds::read_event(&mut bytes, &mut running_status) {
+            Ok(event) => event,
+            Err(e) => {
+                writeln!(out, "; error: {e}").unwrap();
+                return;
+            }
+        };
+        tick += event.delta;
+
+        let mut comment = format!("tick {tick} at {offset:#x}");
+        let (text, indent) = match &event.message {
+            cds::Message::Midi(message) => {
+                match message.loop_marker() {
+                    Some(LoopMarker::Start) => comment += ", loop start",
+                    Some(LoopMarker::End) => comment += ", loop end",
+                    None => {}
+                }
+                (message_text(message), depth)
+            }
+            cds::Message::Command(command) => {
+                let indent = match command {
+                    Command::LoopStart(_) => {
+                        depth += 1;
+                        write!(comment, ", depth {depth}").unwrap();
+                        depth - 1
+                    }
+                    Command::LoopFinish | Command::InfiniteLoopFinish(_) => {
+                        if depth == 0 {
+                            comment += ", unbalanced";
+                        } else {
+                            write!(comment, ", depth {depth}").unwrap();
+                            depth -= 1;
+                        }
+                        depth
+                    }
+                    _ => depth,
+                };
+                if !command.is_known() {
+                    comment += ", unknown";
+                }
+                (command_text(command), indent)
+            }
+        };
+        event_line(out, event.delta, indent, &text, &comment);
+
+        if event.message == cds::Message::Command(Command::GlobalEnding) {
+            break;
+        }
+    }
+    trailing_bytes(out, bytes.remaining(), "global ending");
+}
+
+fn loop_tree(out: &mut String, lexeme: &Lexeme, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match lexeme {
+        Lexeme::Data(data) => writeln!(out, "; {indent}data, {} bytes", data.len()).unwrap(),
+        Lexeme::Loop(count, lexemes) => {
+            writeln!(out, "; {indent}loop x{count}").unwrap();
+            for lexeme in lexemes {
+                loop_tree(out, lexeme, depth + 1);
+            }
+        }
+    }
+}
+
+fn trailing_bytes(out: &mut String, remaining: &[u8], end: &str) {
+    if !remaining.is_empty() {
+        writeln!(out, "; {} bytes after the {end}", remaining.len()).unwrap();
+    }
+}
+
+fn event_line(out: &mut String, delta: u32, depth: usize, text: &str, comment: &str) {
+    let event = format!("{delta:<8}{}{text}", "  ".repeat(depth));
+    writeln!(out, "{event:<COMMENT_COLUMN$}; {comment}").unwrap();
+}
+
+fn message_text(message: &midi::Message) -> String {
+    match message {
+        midi::Message::Channel { status, data } => {
+            let channel = status & 0xf;
+            match status & 0xf0 {
+                0x80 => format!("note_off ch={channel} key={} velocity={}", data[0], data[1]),
+                0x90 => format!("note_on ch={channel} key={} velocity={}", data[0], data[1]),
+                0xa0 => format!(
+                    "key_pressure ch={channel} key={} pressure={}",
+                    data[0], data[1]
+                ),
+                0xb0 => format!(
+                    "control ch={channel} controller={} value={}",
+                    data[0], data[1]
+                ),
+                0xc0 => format!("program ch={channel} program={}", data[0]),
+                0xd0 => format!("channel_pressure ch={channel} pressure={}", data[0]),
+                _ => format!(
+                    "pitch_bend ch={channel} value={}",
+                    data[0] as u16 | (data[1] as u16) << 7
+                ),
+            }
+        }
+        midi::Message::Tempo(tempo) => format!("tempo microseconds={tempo}"),
+        midi::Message::TimeSignature(numerator, denominator) => {
+            format!("time_signature numerator={numerator} denominator={denominator}")
+        }
+        midi::Message::EndOfTrack => "end_of_track".to_owned(),
+        midi::Message::Meta { kind, data } => {
+            format!("meta kind={kind:#04x} data={}", hex(data))
+        }
+        midi::Message::SysEx(data) => format!("sysex data={}", hex(data)),
+    }
+}
+
+fn command_text(command: &Command) -> String {
+    match command {
+        Command::Tempo(tempo) => format!("tempo microseconds={tempo}"),
+        Command::LoopStart(count) => format!("loop_start count={count}"),
+        Command::LoopFinish => "loop_finish".to_owned(),
+        Command::InfiniteLoopFinish(value) => format!("infinite_loop_finish value={value}"),
+        Command::GlobalEnding => "global_ending".to_owned(),
+        Command::SysEx(data) => format!("sysex data={}", hex(data)),
+        Command::Undocumented { kind, data } => {
+            format!("undocumented kind={kind:#04x} data={}", hex(data))
+        }
+        Command::Other { kind, data } => format!("command kind={kind:#04x} data={}", hex(data)),
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// The file a listing assembles into, from its first line.
+enum Target {
+    Cds(cds::Header),
+    Msq(MsqHeader),
+    Seq(seq::Header),
+}
+
+/// A file assembled from a listing.
+#[derive(Debug)]
+pub struct Assembled {
+    /// Extension of the file's format, like `cds`.
+    pub extension: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+enum Track {
+    Cds(Vec<cds::Event>),
+    Seq(Vec<midi::Event>),
+}
+
+/// Assembles a listing back into a CDS, MSQ or SEQ file, as chosen by its
+/// header line. Tracks that don't end themselves are given a global ending
+/// or an end of track, and loops must be balanced.
+pub fn assemble(text: &str) -> Result<Assembled> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.split(';').next().unwrap().trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let Some((line, header)) = lines.next() else {
+        return Err(Error::Syntax {
+            line: 1,
+            reason: "no header line".to_owned(),
+        });
+    };
+    let mut words = header.split_whitespace();
+    let kind = words.next().unwrap();
+    let fields = Fields::new(line, words)?;
+    let target = match kind {
+        ".cds" => Target::Cds(cds::Header {
+            quarter_note_time: fields.number("quarter_note_time")?,
+            ppqn: fields.number("ppqn")?,
+            version: fields.number("version")?,
+        }),
+        ".msq" => {
+            let magic = fields.number_or("magic", msq::MAGIC)?;
+            if magic != msq::MAGIC && magic != msq::CDS_MAGIC {
+                return Err(fields.error(format!("`{magic:#010x}` isn't an MSQ magic number")));
+            }
+            Target::Msq(MsqHeader {
+                magic,
+                quarter_note_time: fields.number("quarter_note_time")?,
+                ppqn: fields.number("ppqn")?,
+                version: fields.number("version")?,
+                num_tracks: 0,
+                _padding: fields.number_or("padding", 0)?,
+            })
+        }
+        ".seq" => {
+            let time_signature = fields.get("time_signature")?;
+            let Some((numerator, denominator)) =
+                time_signature
+                    .split_once('/')
+                    .and_then(|(numerator, denominator)| {
+                        Some((number(numerator)?, number(denominator)?))
+                    })
+            else {
+                return Err(fields.error(format!("bad time signature `{time_signature}`")));
+            };
+            Target::Seq(seq::Header {
+                version: fields.number("version")?,
+                ppqn: fields.number("ppqn")?,
+                tempo: fields.number("tempo")?,
+                time_signature: (numerator, denominator),
+            })
+        }
+        _ => {
+            return Err(Error::Syntax {
+                line,
+                reason: format!("`{kind}` isn't a CDS, MSQ or SEQ header"),
+            })
+        }
+    };
+
+    let new_track = || match target {
+        Target::Cds(_) | Target::Msq(_) => Track::Cds(vec![]),
+        Target::Seq(_) => Track::Seq(vec![]),
+    };
+    let mut tracks = vec![];
+    let mut track = (!matches!(target, Target::Msq(_))).then(new_track);
+    let mut depth = 0;
+    for (line, text) in lines {
+        let mut words = text.split_whitespace();
+        let first = words.next().unwrap();
+        if first == ".track" {
+            if !matches!(target, Target::Msq(_)) {
+                return Err(Error::Syntax {
+                    line,
+                    reason: "only MSQ files have tracks".to_owned(),
+                });
+            }
+            tracks.extend(track.replace(new_track()));
+            check_loops(line, depth)?;
+            continue;
+        }
+
+        let delta = number(first).ok_or_else(|| Error::Syntax {
+            line,
+            reason: format!("bad delta time `{first}`"),
+        })?;
+        let Some(name) = words.next() else {
+            return Err(Error::Syntax {
+                line,
+                reason: "missing event name".to_owned(),
+            });
+        };
+        let fields = Fields::new(line, words)?;
+        match &mut track {
+            Some(Track::Cds(events)) => {
+                let message = cds_message(name, &fields)?;
+                match message {
+                    cds::Message::Command(Command::LoopStart(_)) => depth += 1,
+                    // Game files can have extra finishes, which the listing
+                    // marks as unbalanced and which are written through as-is.
+                    cds::Message::Command(Command::LoopFinish | Command::InfiniteLoopFinish(_)) => {
+                        depth = depth.saturating_sub(1);
+                    }
+                    _ => {}
+                }
+                events.push(cds::Event { delta, message });
+            }
+            Some(Track::Seq(events)) => {
+                let message = seq_message(name, &fields)?;
+                events.push(midi::Event { delta, message });
+            }
+            None => return Err(fields.error("events before the first track".to_owned())),
+        }
+    }
+    check_loops(text.lines().count(), depth)?;
+    tracks.extend(track);
+
+    let tracks = tracks
+        .into_iter()
+        .map(|track| {
+            let mut out = vec![];
+            match track {
+                Track::Cds(mut events) => {
+                    if events.last().map(|event| &event.message)
+                        != Some(&cds::Message::Command(Command::GlobalEnding))
+                    {
+                        events.push(cds::Event {
+                            delta: 0,
+                            message: cds::Message::Command(Command::GlobalEnding),
+                        });
+                    }
+                    cds::write_events(&events, &mut out);
+                }
+                Track::Seq(mut events) => {
+                    if events.last().map(|event| &event.message) != Some(&midi::Message::EndOfTrack)
+                    {
+                        events.push(midi::Event {
+                            delta: 0,
+                            message: midi::Message::EndOfTrack,
+                        });
+                    }
+                    seq::write_events(&events, &mut out);
+                }
+            }
+            out
+        })
+        .collect::<Vec<_>>();
+
+    let mut bytes = vec![];
+    let extension = match target {
+        Target::Cds(header) => {
+            header.write(&mut bytes);
+            bytes.extend(tracks.into_iter().flatten());
+            "cds"
+        }
+        Target::Seq(header) => {
+            header.write(&mut bytes);
+            bytes.extend(tracks.into_iter().flatten());
+            "seq"
+        }
+        Target::Msq(header) => {
+            let tracks = tracks.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            MsqFile::write(&header, &tracks, &mut bytes);
+            "msq"
+        }
+    };
+    Ok(Assembled { extension, bytes })
+}
+
+fn check_loops(line: usize, depth: usize) -> Result<()> {
+    if depth == 0 {
+        Ok(())
+    } else {
+        Err(Error::Syntax {
+            line,
+            reason: format!("loops left unfinished at depth {depth}"),
+        })
+    }
+}
+
+/// Reads a decimal or `0x` prefixed hexadecimal number.
+fn number<T: TryFrom<u64>>(text: &str) -> Option<T> {
+    let value = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => text.parse().ok()?,
+    };
+    value.try_into().ok()
+}
+
+/// The `name=value` arguments of a line.
+struct Fields<'a> {
+    line: usize,
+    values: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(line: usize, words: impl Iterator<Item = &'a str>) -> Result<Self> {
+        let values = words
+            .map(|word| {
+                word.split_once('=').ok_or_else(|| Error::Syntax {
+                    line,
+                    reason: format!("`{word}` isn't a `name=value` argument"),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { line, values })
+    }
+
+    fn error(&self, reason: String) -> Error {
+        Error::Syntax {
+            line: self.line,
+            reason,
+        }
+    }
+
+    fn get(&self, name: &str) -> Result<&'a str> {
+        self.values
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| *value)
+            .ok_or_else(|| self.error(format!("missing `{name}`")))
+    }
+
+    fn number<T: TryFrom<u64>>(&self, name: &str) -> Result<T> {
+        let value = self.get(name)?;
+        number(value).ok_or_else(|| self.error(format!("bad `{name}` value `{value}`")))
+    }
+
+    /// A number that older listings may leave out, `default` if they do.
+    fn number_or<T: TryFrom<u64>>(&self, name: &str, default: T) -> Result<T> {
+        if self.values.iter().any(|(field, _)| *field == name) {
+            self.number(name)
+        } else {
+            Ok(default)
+        }
+    }
+
+    /// A MIDI data byte, which can't have the top bit set.
+    fn data(&self, name: &str) -> Result<u8> {
+        let value = self.number(name)?;
+        if value < 0x80 {
+            Ok(value)
+        } else {
+            Err(self.error(format!("`{name}` must be below 128")))
+        }
+    }
+
+    fn hex(&self, name: &str) -> Result<Vec<u8>> {
+        let value = self.get(name)?;
+        (0..value.len())
+            .step_by(2)
+            .map(|i| {
+                value
+                    .get(i..i + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(|| self.error(format!("bad `{name}` bytes `{value}`")))
+    }
+}
+
+fn channel_message(name: &str, fields: &Fields) -> Result<Option<midi::Message>> {
+    let status = match name {
+        "note_off" => 0x80,
+        "note_on" => 0x90,
+        "key_pressure" => 0xa0,
+        "control" => 0xb0,
+        "program" => 0xc0,
+        "channel_pressure" => 0xd0,
+        "pitch_bend" => 0xe0,
+        _ => return Ok(None),
+    };
+    let channel: u8 = fields.number("ch")?;
+    if channel >= 16 {
+        return Err(fields.error("`ch` must be below 16".to_owned()));
+    }
+    let data = match status {
+        0x80 | 0x90 => [fields.data("key")?, fields.data("velocity")?],
+        0xa0 => [fields.data("key")?, fields.data("pressure")?],
+        0xb0 => [fields.data("controller")?, fields.data("value")?],
+        0xc0 => [fields.data("program")?, 0],
+        0xd0 => [fields.data("pressure")?, 0],
+        _ => {
+            let value: u16 = fields.number("value")?;
+            if value >= 0x4000 {
+                return Err(fields.error("`value` must be below 16384".to_owned()));
+            }
+            [(value & 0x7f) as u8, (value >> 7) as u8]
+        }
+    };
+    Ok(Some(midi::Message::Channel {
+        status: status | channel,
+        data,
+    }))
+}
+
+fn tempo(fields: &Fields) -> Result<u32> {
+    let tempo = fields.number("microseconds")?;
+    if tempo < 1 << 24 {
+        Ok(tempo)
+    } else {
+        Err(fields.error("`microseconds` must fit in 3 bytes".to_owned()))
+    }
+}
+
+fn cds_message(name: &str, fields: &Fields) -> Result<cds::Message> {
+    if let Some(message) = channel_message(name, fields)? {
+        return Ok(cds::Message::Midi(message));
+    }
+    let command = match name {
+        "tempo" => Command::Tempo(tempo(fields)?),
+        "loop_start" => Command::LoopStart(fields.number("count")?),
+        "loop_finish" => Command::LoopFinish,
+        "infinite_loop_finish" => Command::InfiniteLoopFinish(fields.number("value")?),
+        "global_ending" => Command::GlobalEnding,
+        "sysex" => Command::SysEx(command_data(fields)?),
+        "undocumented" => {
+            let kind = fields.number("kind")?;
+            let data = command_data(fields)?;
+            if cds::command_length(kind) != Some(data.len() as u8) {
+                return Err(fields.error(format!(
+                    "command {kind:#04x} with {} bytes isn't one found in the games",
+                    data.len()
+                )));
+            }
+            Command::Undocumented { kind, data }
+        }
+        "command" => Command::Other {
+            kind: fields.number("kind")?,
+            data: command_data(fields)?,
+        },
+        _ => return Err(fields.error(format!("`{name}` isn't a CDS event"))),
+    };
+    Ok(cds::Message::Command(command))
+}
+
+fn command_data(fields: &Fields) -> Result<Vec<u8>> {
+    let data = fields.hex("data")?;
+    if data.len() > 0xff {
+        return Err(fields.error("commands hold at most 255 bytes".to_owned()));
+    }
+    Ok(data)
+}
+
+fn seq_message(name: &str, fields: &Fields) -> Result<midi::Message> {
+    if let Some(message) = channel_message(name, fields)? {
+        return Ok(message);
+    }
+    Ok(match name {
+        "tempo" => midi::Message::Tempo(tempo(fields)?),
+        "time_signature" => {
+            midi::Message::TimeSignature(fields.number("numerator")?, fields.number("denominator")?)
+        }
+        "end_of_track" => midi::Message::EndOfTrack,
+        "meta" => midi::Message::Meta {
+            kind: fields.number("kind")?,
+            data: fields.hex("data")?,
+        },
+        "sysex" => midi::Message::SysEx(fields.hex("data")?),
+        _ => return Err(fields.error(format!("`{name}` isn't a SEQ event"))),
+    })
+}
+
+#[cfg(test)]
+const CDS_FILE: &[u8] = &[
+    b'Q', b'E', b'S', b'a', 0x20, 0xa1, 0x07, 0x00, 0xe0, 0x01, 0x01, 0x01, // Header
+    0x00, 0x90, 0x3c, 0x40, // Note on
+    0x00, 0xff, 0x2e, 0x01, 0x02, // Loop start
+    0x10, 0x80, 0x3c, 0x00, // Note off
+    0x00, 0xff, 0x45, 0x02, 0x01, 0x02, // Unknown
+    0x00, 0xff, 0x2f, 0x00, // Loop finish
+    0x00, 0xff, 0x44, 0x00, 0x00, // Global ending and padding
+];
+
+#[test]
+fn cds_listing() {
+    let listing = cds(CDS_FILE).unwrap();
+    let lines = listing.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[0],
+        ".cds quarter_note_time=500000 ppqn=480 version=0x0101"
+    );
+    assert_eq!(lines[2], ";   data, 5 bytes");
+    assert_eq!(lines[3], ";   loop x2");
+    assert_eq!(lines[4], ";     data, 11 bytes");
+    assert!(lines[7].starts_with("0       note_on ch=0 key=60 velocity=64 "));
+    assert!(lines[7].ends_with("; tick 0 at 0xc"));
+    assert!(lines[8].starts_with("0       loop_start count=2 "));
+    assert!(lines[8].ends_with("; tick 0 at 0x10, depth 1"));
+    assert!(lines[9].starts_with("16        note_off ch=0 key=60 velocity=0 "));
+    assert!(lines[10].starts_with("0         command kind=0x45 data=0102 "));
+    assert!(lines[10].ends_with(", unknown"));
+    assert!(lines[11].starts_with("0       loop_finish "));
+    assert!(lines[11].ends_with("; tick 16 at 0x1f, depth 1"));
+    assert!(lines[12].starts_with("0       global_ending "));
+    assert_eq!(lines[13], "; 1 bytes after the global ending");
+}
+
+#[test]
+fn known_command() {
+    let mut file = CDS_FILE.to_vec();
+    // The unknown command becomes one of known length
+    let at = file.len() - 15;
+    file.splice(at..at + 6, [0x00, 0xff, 0xf1, 0x04, 0x01, 0x02, 0x03, 0x04]);
+    let listing = cds(&file).unwrap();
+    let line = listing.lines().nth(10).unwrap();
+    assert!(line.starts_with("0         undocumented kind=0xf1 data=01020304 "));
+    assert!(!line.ends_with(", unknown"));
+    assert_eq!(assemble(&listing).unwrap().bytes, file[..file.len() - 1]);
+    assert!(assemble(&listing.replace("kind=0xf1", "kind=0x60")).is_err());
+}
+
+#[test]
+fn seq_listing() {
+    let mut file = vec![];
+    seq::Header::new(480, 500_000).write(&mut file);
+    file.extend([
+        0x00, 0xb0, 0x63, 0x14, 0x81, 0x00, 0xff, 0x51, 0x07, 0xa1, 0x20, 0x00, 0xe0,
+    ]);
+    let listing = seq(&file).unwrap();
+    let lines = listing.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[0],
+        ".seq version=1 ppqn=480 tempo=500000 time_signature=4/2"
+    );
+    assert!(lines[1].starts_with("0       control ch=0 controller=99 value=20 "));
+    assert!(lines[1].ends_with(", loop start"));
+    assert!(lines[2].starts_with("128     tempo microseconds=500000 "));
+    assert!(lines[3].starts_with("; error: truncated"));
+}
+
+#[test]
+fn round_trip() {
+    let listing = cds(CDS_FILE).unwrap();
+    // Everything but the padding after the global ending
+    let assembled = assemble(&listing).unwrap();
+    assert_eq!(assembled.extension, "cds");
+    assert_eq!(assembled.bytes, CDS_FILE[..CDS_FILE.len() - 1]);
+
+    let mut file = vec![];
+    seq::Header::new(480, 500_000).write(&mut file);
+    file.extend([
+        0x00, 0xb0, 0x63, 0x14, 0x00, 0x06, 0x02, 0x81, 0x00, 0xe0, 0x00, 0x40, 0x00, 0xff, 0x2f,
+        0x00,
+    ]);
+    assert_eq!(assemble(&seq(&file).unwrap()).unwrap().bytes, file);
+
+    let msq_file = |magic, _padding| {
+        let mut msq_file = vec![];
+        MsqFile::write(
+            &MsqHeader {
+                magic,
+                quarter_note_time: 500_000,
+                ppqn: 480,
+                version: 0x0101,
+                num_tracks: 0,
+                _padding,
+            },
+            &[
+                &CDS_FILE[cds::HEADER_SIZE..CDS_FILE.len() - 1],
+                &[0, 0xff, 0x44, 0],
+            ],
+            &mut msq_file,
+        );
+        msq_file
+    };
+    let file = msq_file(msq::MAGIC, 0);
+    assert_eq!(assemble(&msq(&file).unwrap()).unwrap().bytes, file);
+
+    // MSQ files with the magic number of CDS files keep it
+    let file = msq_file(msq::CDS_MAGIC, 0x1234);
+    let listing = msq(&file).unwrap();
+    assert!(listing.starts_with(".msq magic=0x61534551 "));
+    assert_eq!(assemble(&listing).unwrap().bytes, file);
+
+    // Listings from before the magic number was listed
+    let listing = listing
+        .replace("magic=0x61534551 ", "")
+        .replace(" padding=0x1234", "");
+    assert_eq!(assemble(&listing).unwrap().bytes, msq_file(msq::MAGIC, 0));
+    assert!(assemble(&listing.replace(".msq", ".msq magic=0x1")).is_err());
+}
+
+#[test]
+fn unbalanced_round_trip() {
+    let mut file = CDS_FILE[..CDS_FILE.len() - 1].to_vec();
+    // An extra loop finish before the global ending
+    file.splice(file.len() - 4..file.len() - 4, [0x00, 0xff, 0x2f, 0x00]);
+    let listing = cds(&file).unwrap();
+    assert!(listing.contains(", unbalanced"));
+    assert_eq!(assemble(&listing).unwrap().bytes, file);
+}
+
+#[test]
+fn edited() {
+    let assembled = assemble(
+        "
+        ; Hand written
+        .cds quarter_note_time=0x7a120 ppqn=480 version=0x0101
+        0  loop_start count=0
+        48   note_on ch=1 key=60 velocity=100
+        48   note_on ch=1 key=60 velocity=0
+        0  loop_finish
+        ",
+    )
+    .unwrap()
+    .bytes;
+    assert_eq!(
+        assembled[cds::HEADER_SIZE..],
+        [
+            0x00, 0xff, 0x2e, 0x01, 0x00, 0x30, 0x91, 0x3c, 0x64, 0x30, 0x3c, 0x00, 0x00, 0xff,
+            0x2f, 0x00, 0x00, 0xff, 0x44, 0x00,
+        ]
+    );
+
+    let error = |text| match assemble(text) {
+        Err(Error::Syntax { line, reason }) => (line, reason),
+        result => panic!("{result:?}"),
+    };
+    assert_eq!(
+        error(".seq version=1 ppqn=480 tempo=500000 time_signature=4/2\n0 loop_finish"),
+        (2, "`loop_finish` isn't a SEQ event".to_owned())
+    );
+    assert_eq!(
+        error(".cds quarter_note_time=1 ppqn=1 version=1\n0 note_on ch=0 key=128 velocity=1"),
+        (2, "`key` must be below 128".to_owned())
+    );
+    assert_eq!(
+        error(".cds quarter_note_time=1 ppqn=1 version=1\n0 loop_start count=2\n"),
+        (2, "loops left unfinished at depth 1".to_owned())
+    );
+}
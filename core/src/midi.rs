@@ -0,0 +1,332 @@
+//! Standard MIDI Files, and the events shared with the sequence formats.
+
+use crate::{Error, Reader, Result};
+
+/// Controller used for the NRPN parameter number that marks loops.
+pub const CONTROLLER_NRPN_MSB: u8 = 0x63;
+/// NRPN marking the start of a loop, followed by the loop count in a data entry.
+pub const NRPN_LOOP_START: u8 = 0x14;
+/// NRPN marking the end of a loop.
+pub const NRPN_LOOP_END: u8 = 0x1e;
+/// Meta event holding the text of a marker.
+pub const META_MARKER: u8 = 0x06;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// A channel voice message, with the second data byte unused for
+    /// program changes and channel pressure.
+    Channel {
+        status: u8,
+        data: [u8; 2],
+    },
+    /// Microseconds per quarter note.
+    Tempo(u32),
+    /// Numerator and the power of two denominator.
+    TimeSignature(u8, u8),
+    EndOfTrack,
+    /// Any other meta event.
+    Meta {
+        kind: u8,
+        data: Vec<u8>,
+    },
+    /// A system exclusive message, without the leading `F0`.
+    SysEx(Vec<u8>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoopMarker {
+    Start,
+    End,
+}
+
+impl Message {
+    /// The loop marker set by this message, if it's one of the loop NRPNs.
+    #[must_use]
+    pub fn loop_marker(&self) -> Option<LoopMarker> {
+        match self {
+            Self::Channel {
+                status,
+                data: [CONTROLLER_NRPN_MSB, value],
+            } if status & 0xf0 == 0xb0 => match *value {
+                NRPN_LOOP_START => Some(LoopMarker::Start),
+                NRPN_LOOP_END => Some(LoopMarker::End),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Channel { status, data } => {
+                out.push(*status);
+                out.extend(&data[..data_len(*status)]);
+            }
+            Self::Tempo(tempo) => {
+                out.extend([0xff, 0x51, 0x03]);
+                out.extend(&tempo.to_be_bytes()[1..]);
+            }
+            Self::TimeSignature(numerator, denominator) => {
+                // Also 24 clocks per metronome click and 8 32nd notes per quarter
+                out.extend([0xff, 0x58, 0x04, *numerator, *denominator, 24, 8]);
+            }
+            Self::EndOfTrack => out.extend([0xff, 0x2f, 0x00]),
+            Self::Meta { kind, data } => {
+                out.extend([0xff, *kind]);
+                write_var_len(data.len() as u32, out);
+                out.extend(data);
+            }
+            Self::SysEx(data) => {
+                out.push(0xf0);
+                write_var_len(data.len() as u32, out);
+                out.extend(data);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// Ticks since the previous event.
+    pub delta: u32,
+    pub message: Message,
+}
+
+/// Number of data bytes following a channel status byte.
+#[must_use]
+pub fn data_len(status: u8) -> usize {
+    match status & 0xf0 {
+        0xc0 | 0xd0 => 1,
+        _ => 2,
+    }
+}
+
+/// Reads a channel message, where `first` is either its status byte or,
+/// with running status, its first data byte.
+pub(crate) fn read_channel(
+    bytes: &mut Reader,
+    format: &'static str,
+    first: u8,
+    running_status: &mut Option<u8>,
+) -> Result<Message> {
+    let offset = bytes.position() - 1;
+    let (status, first_data) = if first & 0x80 != 0 {
+        *running_status = Some(first);
+        (first, bytes.u8("data")?)
+    } else {
+        let status = running_status.ok_or(Error::BadEvent {
+            format,
+            offset,
+            status: first,
+        })?;
+        (status, first)
+    };
+    let mut data = [first_data, 0];
+    if data_len(status) == 2 {
+        data[1] = bytes.u8("data")?;
+    }
+    Ok(Message::Channel { status, data })
+}
+
+pub fn write_var_len(value: u32, out: &mut Vec<u8>) {
+    let mut groups = vec![(value & 0x7f) as u8];
+    let mut value = value >> 7;
+    while value != 0 {
+        groups.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.extend(groups.into_iter().rev());
+}
+
+/// A Standard MIDI File.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Smf {
+    /// 0 for a single track, 1 for simultaneous tracks.
+    pub format: u16,
+    pub ppqn: u16,
+    pub tracks: Vec<Vec<Event>>,
+}
+
+impl Smf {
+    /// Builds a type 1 file from simultaneous tracks, after a tempo track
+    /// starting with `tempo` and `time_signature`. Tempo changes are moved
+    /// from the other tracks into the tempo track, ignoring the ones that
+    /// don't change the tempo of their own track.
+    #[must_use]
+    pub fn with_tempo_track(
+        ppqn: u16,
+        tempo: u32,
+        time_signature: (u8, u8),
+        tracks: Vec<Vec<Event>>,
+    ) -> Self {
+        let mut tempo_changes = vec![];
+        let mut merged = vec![];
+        for track in tracks {
+            let mut time = 0;
+            let mut carried = 0;
+            let mut track_tempo = tempo;
+            let mut events = vec![];
+            for event in track {
+                time += event.delta;
+                if let Message::Tempo(tempo) = event.message {
+                    if tempo != track_tempo {
+                        tempo_changes.push((time, tempo));
+                        track_tempo = tempo;
+                    }
+                    carried += event.delta;
+                } else {
+                    events.push(Event {
+                        delta: carried + event.delta,
+                        message: event.message,
+                    });
+                    carried = 0;
+                }
+            }
+            merged.push(events);
+        }
+        tempo_changes.sort_by_key(|(time, _)| *time);
+
+        let mut tempo_track = vec![
+            Event {
+                delta: 0,
+                message: Message::Tempo(tempo),
+            },
+            Event {
+                delta: 0,
+                message: Message::TimeSignature(time_signature.0, time_signature.1),
+            },
+        ];
+        let mut current = (0, tempo);
+        for (time, tempo) in tempo_changes {
+            if tempo != current.1 {
+                tempo_track.push(Event {
+                    delta: time - current.0,
+                    message: Message::Tempo(tempo),
+                });
+                current = (time, tempo);
+            }
+        }
+        merged.insert(0, tempo_track);
+
+        Self {
+            format: 1,
+            ppqn,
+            tracks: merged,
+        }
+    }
+
+    /// Writes the file, ending every track that doesn't already end itself.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(b"MThd");
+        out.extend(6u32.to_be_bytes());
+        out.extend(self.format.to_be_bytes());
+        out.extend((self.tracks.len() as u16).to_be_bytes());
+        out.extend(self.ppqn.to_be_bytes());
+
+        for track in &self.tracks {
+            let mut body = vec![];
+            for event in track {
+                write_var_len(event.delta, &mut body);
+                event.message.write(&mut body);
+            }
+            if track.last().map(|event| &event.message) != Some(&Message::EndOfTrack) {
+                body.extend([0, 0xff, 0x2f, 0x00]);
+            }
+
+            out.extend(b"MTrk");
+            out.extend((body.len() as u32).to_be_bytes());
+            out.extend(body);
+        }
+    }
+}
+
+#[test]
+fn var_len() {
+    for (value, bytes) in [
+        (0, &[0x00][..]),
+        (0x7f, &[0x7f]),
+        (0x80, &[0x81, 0x00]),
+        (0x0fff_ffff, &[0xff, 0xff, 0xff, 0x7f]),
+    ] {
+        let mut out = vec![];
+        write_var_len(value, &mut out);
+        assert_eq!(out, bytes);
+        assert_eq!(Reader::new(bytes).var_len("delta"), Ok(value));
+    }
+}
+
+#[test]
+fn smf() {
+    let smf = Smf {
+        format: 0,
+        ppqn: 480,
+        tracks: vec![vec![
+            Event {
+                delta: 0,
+                message: Message::Tempo(500_000),
+            },
+            Event {
+                delta: 0x80,
+                message: Message::Channel {
+                    status: 0xc1,
+                    data: [5, 0],
+                },
+            },
+        ]],
+    };
+    let mut out = vec![];
+    smf.write(&mut out);
+    assert_eq!(
+        out,
+        [
+            b"MThd".as_slice(),
+            &[0, 0, 0, 6, 0, 0, 0, 1, 0x01, 0xe0],
+            b"MTrk",
+            &[0, 0, 0, 15],
+            &[0, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20],
+            &[0x81, 0x00, 0xc1, 5],
+            &[0, 0xff, 0x2f, 0x00],
+        ]
+        .concat()
+    );
+}
+
+#[test]
+fn tempo_track() {
+    let note = |delta| Event {
+        delta,
+        message: Message::Channel {
+            status: 0x90,
+            data: [60, 100],
+        },
+    };
+    let tempo = |delta, tempo| Event {
+        delta,
+        message: Message::Tempo(tempo),
+    };
+    let smf = Smf::with_tempo_track(
+        480,
+        500_000,
+        (4, 2),
+        vec![
+            vec![note(0), tempo(10, 500_000), note(10), tempo(20, 500_000)],
+            vec![note(5), tempo(15, 400_000), tempo(0, 400_000), note(0)],
+        ],
+    );
+    assert_eq!(smf.format, 1);
+    assert_eq!(
+        smf.tracks,
+        [
+            vec![
+                tempo(0, 500_000),
+                Event {
+                    delta: 0,
+                    message: Message::TimeSignature(4, 2),
+                },
+                tempo(20, 400_000),
+            ],
+            vec![note(0), note(20)],
+            vec![note(5), note(15)],
+        ]
+    );
+}
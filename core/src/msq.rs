@@ -0,0 +1,169 @@
+//! Crystal Dynamics MSQ files, which hold several CDS tracks that share one header.
+
+use std::ops::Range;
+
+use log::debug;
+
+use crate::{cds, reader::check_range, Error, Reader, Result};
+
+/// `QSMa` read as a little endian number.
+pub const MAGIC: u32 = 0x614d_5351;
+/// `QESa` read as a little endian number, used by some MSQ files too.
+pub const CDS_MAGIC: u32 = 0x6153_4551;
+pub const HEADER_SIZE: usize = 16;
+
+#[derive(Debug, serde::Serialize)]
+pub struct MsqHeader {
+    pub magic: u32,
+    pub quarter_note_time: u32,
+    pub ppqn: u16,
+    pub version: u16,
+    pub num_tracks: u16,
+    #[serde(skip)]
+    pub _padding: u16,
+}
+
+impl MsqHeader {
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        Ok(MsqHeader {
+            magic: bytes.u32_le("magic")?,
+            quarter_note_time: bytes.u32_le("quarter_note_time")?,
+            ppqn: bytes.u16_le("ppqn")?,
+            version: bytes.u16_le("version")?,
+            num_tracks: bytes.u16_le("num_tracks")?,
+            _padding: bytes.u16_le("_padding")?,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.magic.to_le_bytes());
+        out.extend(self.quarter_note_time.to_le_bytes());
+        out.extend(self.ppqn.to_le_bytes());
+        out.extend(self.version.to_le_bytes());
+        out.extend(self.num_tracks.to_le_bytes());
+        out.extend(self._padding.to_le_bytes());
+    }
+
+    /// The header of the CDS files that each track is split into.
+    #[must_use]
+    pub fn cds_header(&self) -> cds::Header {
+        cds::Header {
+            quarter_note_time: self.quarter_note_time,
+            ppqn: self.ppqn,
+            version: self.version,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MsqFile {
+    pub header: MsqHeader,
+    pub tracks: Vec<Range<usize>>,
+}
+
+impl MsqFile {
+    pub fn parse(file: &[u8]) -> Result<Self> {
+        let bytes = &mut Reader::new(file);
+
+        let header = MsqHeader::parse(bytes)?;
+        if header.magic != MAGIC && header.magic != CDS_MAGIC {
+            return Err(Error::BadMagic {
+                format: "MSQ",
+                offset: 0,
+                expected: MAGIC.to_le_bytes(),
+                found: header.magic.to_le_bytes(),
+            });
+        }
+        debug!("{header:#?}");
+
+        let track_offsets = (0..header.num_tracks)
+            .map(|_| bytes.u32_le("track_offset"))
+            .collect::<Result<Vec<_>>>()?;
+        debug!("{track_offsets:#?}");
+
+        let tracks = track_offsets
+            .iter()
+            .enumerate()
+            .map(|(i, offset)| {
+                let end = track_offsets
+                    .get(i + 1)
+                    .map_or(file.len(), |next| *next as usize);
+                check_range("track_offset", *offset as usize..end, file.len())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        debug!("{tracks:#?}");
+
+        Ok(Self { header, tracks })
+    }
+
+    /// Writes an entire MSQ file, with the header's track count replaced by the number of tracks.
+    pub fn write(header: &MsqHeader, tracks: &[&[u8]], out: &mut Vec<u8>) {
+        MsqHeader {
+            num_tracks: tracks.len() as u16,
+            ..*header
+        }
+        .write(out);
+        let mut offset = HEADER_SIZE + 4 * tracks.len();
+        for track in tracks {
+            out.extend((offset as u32).to_le_bytes());
+            offset += track.len();
+        }
+        for track in tracks {
+            out.extend_from_slice(track);
+        }
+    }
+}
+
+#[test]
+fn round_trip() {
+    let header = MsqHeader {
+        magic: MAGIC,
+        quarter_note_time: 500_000,
+        ppqn: 480,
+        version: 0x0101,
+        num_tracks: 0,
+        _padding: 0,
+    };
+    let tracks: [&[u8]; 2] = [&[0, 0x90, 60, 100], &[0, 0xff, 0x2f, 0]];
+    let mut bytes = vec![];
+    MsqFile::write(&header, &tracks, &mut bytes);
+
+    let msq = MsqFile::parse(&bytes).unwrap();
+    assert_eq!(msq.header.num_tracks, 2);
+    assert_eq!(msq.header.quarter_note_time, 500_000);
+    assert_eq!(&bytes[msq.tracks[0].clone()], tracks[0]);
+    assert_eq!(&bytes[msq.tracks[1].clone()], tracks[1]);
+}
+
+#[test]
+fn bad_magic() {
+    let mut bytes = vec![];
+    MsqFile::write(
+        &MsqHeader {
+            magic: 0x1234_5678,
+            quarter_note_time: 500_000,
+            ppqn: 480,
+            version: 0x0101,
+            num_tracks: 0,
+            _padding: 0,
+        },
+        &[],
+        &mut bytes,
+    );
+    assert!(matches!(
+        MsqFile::parse(&bytes),
+        Err(Error::BadMagic {
+            format: "MSQ",
+            found: [0x78, 0x56, 0x34, 0x12],
+            ..
+        })
+    ));
+    assert!(matches!(
+        MsqFile::parse(&bytes[0..10]),
+        Err(Error::Truncated {
+            field: "version",
+            offset: 10,
+            ..
+        })
+    ));
+}
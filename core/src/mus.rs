@@ -0,0 +1,739 @@
+//! Crystal Dynamics MUS sound banks and their SAM sample files.
+
+use std::ops::Range;
+
+use crate::{reader::check_range, Error, Reader, Result};
+
+/// `Mus!` read as a big endian number.
+pub const HEADER_MAGIC: i32 = 0x4D75_7321;
+
+pub const HEADER_VERSION_1_8: i32 = 264;
+pub const HEADER_VERSION_1_14: i32 = 270;
+pub const HEADER_VERSION_1_20: i32 = 276;
+
+// If this value is present at the start of the `sam` file, we have a 8 byte long header
+// Sam!
+pub const SAM_HAS_HEADER_MAGIC_NUMBER: [u8; 4] = [0x53, 0x61, 0x6D, 0x21];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Platform {
+    Console,
+    #[default]
+    PC,
+}
+
+pub fn parse_name(bytes: &mut Reader) -> Result<[char; 20]> {
+    let raw = bytes.array::<20>("name")?;
+    let mut encountered_garbage = false;
+    let mut name = raw.map(|c| {
+        if !encountered_garbage && !WaveEntry::valid_char(&c) {
+            encountered_garbage = true;
+        }
+        if encountered_garbage {
+            '\0'
+        } else {
+            c.into()
+        }
+    });
+    for c in name.iter_mut().rev() {
+        if *c == ' ' {
+            *c = '\0';
+        } else if WaveEntry::valid_char(&(*c as u8)) {
+            break;
+        }
+    }
+
+    Ok(name)
+}
+
+#[test]
+fn test_parse_name() {
+    assert_eq!(
+        parse_name(&mut Reader::new(
+            b"C Hit          \0\0\0\0\0\0\0\0\0\0\0\0\0\0\0"
+        ))
+        .unwrap()[6],
+        '\0'
+    );
+    assert_eq!(
+        parse_name(&mut Reader::new(
+            b"C Hit\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0"
+        ))
+        .unwrap()[2],
+        'H'
+    );
+}
+
+fn write_name(name: &[char; 20], out: &mut Vec<u8>) {
+    out.extend(name.map(|c| c as u8));
+}
+
+#[must_use]
+pub fn name_to_str(name: &[char; 20]) -> String {
+    name.iter().take_while(|x| **x != '\0').collect()
+}
+
+#[derive(Debug)]
+pub struct MusFile {
+    pub header: MusHeader,
+    pub msq_tables: Vec<MsqTable>,
+    pub layers: Vec<i32>,
+    pub wave_entries: Vec<WaveEntry>,
+    pub program_entries: Vec<ProgramEntry>,
+    pub program_zones: Vec<Vec<ProgramZone>>,
+    pub preset_entries: Vec<PresetEntry>,
+    pub preset_zones: Vec<Vec<PresetZone>>,
+    pub sequences: Vec<Range<usize>>,
+    pub labels: Vec<u32>,
+}
+
+impl MusFile {
+    pub fn parse(file: &[u8]) -> Result<Self> {
+        let bytes = &mut Reader::new(file);
+        let header = MusHeader::parse(bytes)?;
+        if header.magic != HEADER_MAGIC {
+            return Err(Error::BadMagic {
+                format: "MUS",
+                offset: 0,
+                expected: HEADER_MAGIC.to_be_bytes(),
+                found: header.magic.to_be_bytes(),
+            });
+        }
+        if ![HEADER_VERSION_1_8, HEADER_VERSION_1_14, HEADER_VERSION_1_20]
+            .contains(&header.version_number)
+        {
+            return Err(Error::UnsupportedVersion {
+                format: "MUS",
+                version: header.version_number as u32,
+            });
+        }
+
+        let msq_tables = (0..header.num_sequences)
+            .map(|_| {
+                Ok(MsqTable {
+                    index: bytes.i32_le("index")?,
+                    offset: bytes.i32_le("offset")?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let layers = (0..header.num_presets + header.num_programs)
+            .map(|_| bytes.i32_le("layer"))
+            .collect::<Result<Vec<_>>>()?;
+
+        let wave_entries = (0..header.num_waves)
+            .map(|_| WaveEntry::parse(bytes))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut program_entries = Vec::with_capacity(header.num_programs as usize);
+        let mut program_zones = Vec::with_capacity(header.num_programs as usize);
+        for _ in 0..header.num_programs {
+            let entry = ProgramEntry {
+                name: parse_name(bytes)?,
+                num_zones: bytes.i32_le("num_zones")?,
+            };
+            let zones = (0..entry.num_zones)
+                .map(|_| ProgramZone::parse(bytes, header.version_number))
+                .collect::<Result<Vec<_>>>()?;
+            for zone in &zones {
+                check_index("wave_index", zone.wave_index, wave_entries.len())?;
+            }
+            program_zones.push(zones);
+            program_entries.push(entry);
+        }
+
+        let mut preset_entries = Vec::with_capacity(header.num_presets as usize);
+        let mut preset_zones = Vec::with_capacity(header.num_presets as usize);
+        for _ in 0..header.num_presets {
+            let entry = PresetEntry {
+                name: parse_name(bytes)?,
+                midi_bank_number: bytes.i32_le("midi_bank_number")?,
+                midi_preset_number: bytes.i32_le("midi_preset_number")?,
+                num_zones: bytes.i32_le("num_zones")?,
+            };
+            let zones = (0..entry.num_zones)
+                .map(|_| PresetZone::parse(bytes))
+                .collect::<Result<Vec<_>>>()?;
+            for zone in &zones {
+                check_index("program_index", zone.program_index, program_entries.len())?;
+            }
+            preset_zones.push(zones);
+            preset_entries.push(entry);
+        }
+
+        let labels_start = header.offset_to_labels_offsets_table as usize;
+        let sequences = msq_tables
+            .iter()
+            .enumerate()
+            .map(|(i, table)| {
+                let end = msq_tables
+                    .get(i + 1)
+                    .map_or(labels_start, |next| next.offset as usize);
+                check_range("msq_offset", table.offset as usize..end, file.len())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if header.num_labels != 0 {
+            bytes.seek("offset_to_labels_offsets_table", labels_start)?;
+        }
+        let labels = (0..header.num_labels)
+            .map(|_| bytes.u32_le("label"))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            header,
+            msq_tables,
+            layers,
+            wave_entries,
+            program_entries,
+            program_zones,
+            preset_entries,
+            preset_zones,
+            sequences,
+            labels,
+        })
+    }
+
+    /// Writes the header and tables, the sequences and labels have to be
+    /// placed after at the offsets the header and tables point to.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        self.header.write(out);
+        for table in &self.msq_tables {
+            out.extend(table.index.to_le_bytes());
+            out.extend(table.offset.to_le_bytes());
+        }
+        for layer in &self.layers {
+            out.extend(layer.to_le_bytes());
+        }
+        for wave_entry in &self.wave_entries {
+            wave_entry.write(out);
+        }
+        for (entry, zones) in self.program_entries.iter().zip(&self.program_zones) {
+            write_name(&entry.name, out);
+            out.extend(entry.num_zones.to_le_bytes());
+            for zone in zones {
+                zone.write(out);
+            }
+        }
+        for (entry, zones) in self.preset_entries.iter().zip(&self.preset_zones) {
+            write_name(&entry.name, out);
+            out.extend(entry.midi_bank_number.to_le_bytes());
+            out.extend(entry.midi_preset_number.to_le_bytes());
+            out.extend(entry.num_zones.to_le_bytes());
+            for zone in zones {
+                zone.write(out);
+            }
+        }
+    }
+
+    /// Ranges of each of the waves in the SAM file.
+    pub fn wave_ranges(&self, sam_file: &[u8]) -> Result<Vec<Range<usize>>> {
+        // Check if there is any header at the start of the `sam` file.
+        let offset = if sam_file.starts_with(&SAM_HAS_HEADER_MAGIC_NUMBER) {
+            8
+        } else {
+            0
+        };
+        self.wave_entries
+            .iter()
+            .map(|wave_entry| {
+                let start = (wave_entry.offset as usize).saturating_add(offset);
+                let end = start.saturating_add(wave_entry.size as usize);
+                check_range("wave_offset", start..end, sam_file.len())
+            })
+            .collect()
+    }
+}
+
+/// Checks that an index read from the file refers to one of `len` entries.
+fn check_index(field: &'static str, index: i32, len: usize) -> Result<()> {
+    if (0..len as i64).contains(&(index as i64)) {
+        Ok(())
+    } else {
+        Err(Error::IndexOutOfRange {
+            field,
+            index: index as i64,
+            len,
+        })
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct MusHeader {
+    pub magic: i32,
+    pub header_size: i32,
+    pub version_number: i32,
+    pub reverb_volume: i32,
+    pub reverb_type: i32,
+    pub reverb_multiply: i32,
+    pub num_sequences: i32,
+    pub num_streams: Option<i32>,
+    pub stream_bpm: Option<i32>,
+    pub stream_info_pointer: Option<i32>,
+    pub num_labels: i32,
+    pub offset_to_labels_offsets_table: i32,
+    pub num_waves: i32,
+    pub num_programs: i32,
+    pub num_presets: i32,
+}
+
+impl MusHeader {
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        let magic = i32::from_be_bytes(bytes.array("magic")?);
+        let header_size = bytes.i32_le("header_size")?;
+        let version_number = bytes.i32_le("version_number")?;
+        let has_streams = version_number == HEADER_VERSION_1_20;
+
+        Ok(Self {
+            magic,
+            header_size,
+            version_number,
+            reverb_volume: bytes.i32_le("reverb_volume")?,
+            reverb_type: bytes.i32_le("reverb_type")?,
+            reverb_multiply: bytes.i32_le("reverb_multiply")?,
+            num_sequences: bytes.i32_le("num_sequences")?,
+            num_streams: if has_streams {
+                Some(bytes.i32_le("num_streams")?)
+            } else {
+                None
+            },
+            stream_bpm: if has_streams {
+                Some(bytes.i32_le("stream_bpm")?)
+            } else {
+                None
+            },
+            stream_info_pointer: if has_streams {
+                Some(bytes.i32_le("stream_info_pointer")?)
+            } else {
+                None
+            },
+            num_labels: bytes.i32_le("num_labels")?,
+            offset_to_labels_offsets_table: bytes.i32_le("offset_to_labels_offsets_table")?,
+            num_waves: bytes.i32_le("num_waves")?,
+            num_programs: bytes.i32_le("num_programs")?,
+            num_presets: bytes.i32_le("num_presets")?,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.magic.to_be_bytes());
+        for field in [
+            Some(self.header_size),
+            Some(self.version_number),
+            Some(self.reverb_volume),
+            Some(self.reverb_type),
+            Some(self.reverb_multiply),
+            Some(self.num_sequences),
+            self.num_streams,
+            self.stream_bpm,
+            self.stream_info_pointer,
+            Some(self.num_labels),
+            Some(self.offset_to_labels_offsets_table),
+            Some(self.num_waves),
+            Some(self.num_programs),
+            Some(self.num_presets),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            out.extend(field.to_le_bytes());
+        }
+    }
+
+    #[must_use]
+    pub fn display(
+        &self,
+        platform: Platform,
+        sequence_zones: usize,
+        preset_zones: usize,
+    ) -> String {
+        format!(
+            "MUS header\n\
+            Header bytes: {} bytes\n\
+            MUS version: {}\n\
+            System: {platform:?}\n\
+            Reverb volume: {}\n\
+            Reverb type: {}\n\
+            Sequences: {}\n\
+            {}\
+            {}\
+            Labels: {}\n\
+            Samples: {} ({})\n\
+            Instruments: {}\n\
+            Instrument Zones: {sequence_zones}\n\
+            Presets: {}\n\
+            Preset zones: {preset_zones}\n\
+            ",
+            self.header_size,
+            match self.version_number {
+                HEADER_VERSION_1_8 => "1.8",
+                HEADER_VERSION_1_14 => "1.14",
+                HEADER_VERSION_1_20 => "1.20",
+                _ => "UNKNOWN",
+            },
+            self.reverb_volume,
+            self.reverb_type,
+            self.num_sequences,
+            self.num_streams
+                .map(|num| format!("Streams: {num}\n"))
+                .unwrap_or_default(),
+            self.stream_bpm
+                .map(|bpm| format!("Streams BPM: {bpm}\n"))
+                .unwrap_or_default(),
+            self.num_labels,
+            self.num_waves,
+            match platform {
+                Platform::PC => "PCM16LE",
+                Platform::Console => "SONY_4BIT_ADPCM",
+            },
+            self.num_programs,
+            self.num_presets,
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct MsqTable {
+    pub index: i32,
+    pub offset: i32,
+}
+
+#[derive(Debug)]
+pub struct WaveEntry {
+    pub name: [char; 20],
+    pub offset: i32,
+    pub loop_begin: i32,
+    /// Size of the wave in bytes, stored in the file as a number of 16-bit words.
+    pub size: i32,
+    pub loop_end: i32,
+    pub sample_rate: i32,
+    pub original_pitch: i32,
+    pub loop_info: i32,
+    pub snd_handle: i32,
+}
+
+impl WaveEntry {
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        Ok(Self {
+            name: parse_name(bytes)?,
+            offset: bytes.i32_le("offset")?,
+            loop_begin: bytes.i32_le("loop_begin")?,
+            size: bytes.i32_le("size")? * 2,
+            loop_end: bytes.i32_le("loop_end")?,
+            sample_rate: bytes.i32_le("sample_rate")?,
+            original_pitch: bytes.i32_le("original_pitch")?,
+            loop_info: bytes.i32_le("loop_info")?,
+            snd_handle: bytes.i32_le("snd_handle")?,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        write_name(&self.name, out);
+        for field in [
+            self.offset,
+            self.loop_begin,
+            self.size / 2,
+            self.loop_end,
+            self.sample_rate,
+            self.original_pitch,
+            self.loop_info,
+            self.snd_handle,
+        ] {
+            out.extend(field.to_le_bytes());
+        }
+    }
+
+    #[must_use]
+    pub fn valid_char(c: &u8) -> bool {
+        match c {
+            34 | 36 | 42 | 47 | 58 | 59 | 60 | 62 | 63 | 92 | 94 | 96 => false,
+            32..=126 => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Envelope {
+    pub delay: f32,
+    pub attack: f32,
+    pub hold: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Envelope {
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        Ok(Self {
+            delay: bytes.f32_le("delay")?,
+            attack: bytes.f32_le("attack")?,
+            hold: bytes.f32_le("hold")?,
+            decay: bytes.f32_le("decay")?,
+            sustain: bytes.f32_le("sustain")?,
+            release: bytes.f32_le("release")?,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        for field in [
+            self.delay,
+            self.attack,
+            self.hold,
+            self.decay,
+            self.sustain,
+            self.release,
+        ] {
+            out.extend(field.to_le_bytes());
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProgramZone {
+    pub pitch_finetuning: i32,
+    pub reverb: i32,
+    pub pan_position: f32,
+    pub keynum_hold: i32,
+    pub keynum_decay: i32,
+    pub volume_env: Envelope,
+    pub volume_env_atten: f32,
+    pub vib_delay: f32,
+    pub vib_frequency: f32,
+    pub vib_to_pitch: f32,
+    // usually padded as 0xFFFFFFFF. Copy the value from the "originalPitch" variable from the "waveEntry" structure */
+    pub root_key: i32,
+    pub note_low: u8,
+    pub note_high: u8,
+    pub velocity_low: u8,
+    pub velocity_high: u8,
+    pub wave_index: i32,
+    pub base_priority: Option<f32>,
+    pub modul_env: Option<Envelope>,
+    pub modul_env_to_pitch: Option<f32>,
+}
+
+impl ProgramZone {
+    pub fn parse(bytes: &mut Reader, version_number: i32) -> Result<Self> {
+        let has_modulation = version_number > HEADER_VERSION_1_8;
+        Ok(Self {
+            pitch_finetuning: bytes.i32_le("pitch_finetuning")?,
+            reverb: bytes.i32_le("reverb")?,
+            pan_position: bytes.f32_le("pan_position")?,
+            keynum_hold: bytes.i32_le("keynum_hold")?,
+            keynum_decay: bytes.i32_le("keynum_decay")?,
+            volume_env: Envelope::parse(bytes)?,
+            volume_env_atten: bytes.f32_le("volume_env_atten")?,
+            vib_delay: bytes.f32_le("vib_delay")?,
+            vib_frequency: bytes.f32_le("vib_frequency")?,
+            vib_to_pitch: bytes.f32_le("vib_to_pitch")?,
+            root_key: bytes.i32_le("root_key")?,
+            note_low: bytes.u8("note_low")?,
+            note_high: bytes.u8("note_high")?,
+            velocity_low: bytes.u8("velocity_low")?,
+            velocity_high: bytes.u8("velocity_high")?,
+            wave_index: bytes.i32_le("wave_index")?,
+            base_priority: if has_modulation {
+                Some(bytes.f32_le("base_priority")?)
+            } else {
+                None
+            },
+            modul_env: if has_modulation {
+                Some(Envelope::parse(bytes)?)
+            } else {
+                None
+            },
+            modul_env_to_pitch: if has_modulation {
+                Some(bytes.f32_le("modul_env_to_pitch")?)
+            } else {
+                None
+            },
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.pitch_finetuning.to_le_bytes());
+        out.extend(self.reverb.to_le_bytes());
+        out.extend(self.pan_position.to_le_bytes());
+        out.extend(self.keynum_hold.to_le_bytes());
+        out.extend(self.keynum_decay.to_le_bytes());
+        self.volume_env.write(out);
+        out.extend(self.volume_env_atten.to_le_bytes());
+        out.extend(self.vib_delay.to_le_bytes());
+        out.extend(self.vib_frequency.to_le_bytes());
+        out.extend(self.vib_to_pitch.to_le_bytes());
+        out.extend(self.root_key.to_le_bytes());
+        out.extend([
+            self.note_low,
+            self.note_high,
+            self.velocity_low,
+            self.velocity_high,
+        ]);
+        out.extend(self.wave_index.to_le_bytes());
+        if let Some(base_priority) = self.base_priority {
+            out.extend(base_priority.to_le_bytes());
+        }
+        if let Some(modul_env) = &self.modul_env {
+            modul_env.write(out);
+        }
+        if let Some(modul_env_to_pitch) = self.modul_env_to_pitch {
+            out.extend(modul_env_to_pitch.to_le_bytes());
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProgramEntry {
+    pub name: [char; 20],
+    pub num_zones: i32,
+}
+
+#[derive(Debug)]
+pub struct PresetZone {
+    pub root_key: i32,
+    pub note_low: u8,
+    pub note_high: u8,
+    pub velocity_low: u8,
+    pub velocity_high: u8,
+    pub program_index: i32,
+}
+
+impl PresetZone {
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        Ok(Self {
+            root_key: bytes.i32_le("root_key")?,
+            note_low: bytes.u8("note_low")?,
+            note_high: bytes.u8("note_high")?,
+            velocity_low: bytes.u8("velocity_low")?,
+            velocity_high: bytes.u8("velocity_high")?,
+            program_index: bytes.i32_le("program_index")?,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.root_key.to_le_bytes());
+        out.extend([
+            self.note_low,
+            self.note_high,
+            self.velocity_low,
+            self.velocity_high,
+        ]);
+        out.extend(self.program_index.to_le_bytes());
+    }
+}
+
+#[derive(Debug)]
+pub struct PresetEntry {
+    pub name: [char; 20],
+    pub midi_bank_number: i32,
+    pub midi_preset_number: i32,
+    pub num_zones: i32,
+}
+
+#[test]
+fn round_trip() {
+    let envelope = || Envelope {
+        delay: 0.0,
+        attack: 0.01,
+        hold: 0.0,
+        decay: 1.5,
+        sustain: 80.0,
+        release: 0.3,
+    };
+    let mut mus = MusFile {
+        header: MusHeader {
+            magic: HEADER_MAGIC,
+            header_size: 52,
+            version_number: HEADER_VERSION_1_14,
+            reverb_volume: 0,
+            reverb_type: 0,
+            reverb_multiply: 0,
+            num_sequences: 1,
+            num_streams: None,
+            stream_bpm: None,
+            stream_info_pointer: None,
+            num_labels: 1,
+            offset_to_labels_offsets_table: 0,
+            num_waves: 1,
+            num_programs: 1,
+            num_presets: 1,
+        },
+        msq_tables: vec![MsqTable {
+            index: 0,
+            offset: 0,
+        }],
+        layers: vec![0, 0],
+        wave_entries: vec![WaveEntry {
+            name: parse_name(&mut Reader::new(b"Piano\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0")).unwrap(),
+            offset: 0,
+            loop_begin: 10,
+            size: 64,
+            loop_end: 20,
+            sample_rate: 22050,
+            original_pitch: 60 * 256,
+            loop_info: 1,
+            snd_handle: 0,
+        }],
+        program_entries: vec![ProgramEntry {
+            name: ['\0'; 20],
+            num_zones: 1,
+        }],
+        program_zones: vec![vec![ProgramZone {
+            pitch_finetuning: 0,
+            reverb: 0,
+            pan_position: 0.5,
+            keynum_hold: 0,
+            keynum_decay: 0,
+            volume_env: envelope(),
+            volume_env_atten: 0.0,
+            vib_delay: 0.0,
+            vib_frequency: 0.0,
+            vib_to_pitch: 0.0,
+            root_key: -1,
+            note_low: 0,
+            note_high: 127,
+            velocity_low: 0,
+            velocity_high: 127,
+            wave_index: 0,
+            base_priority: Some(0.0),
+            modul_env: Some(envelope()),
+            modul_env_to_pitch: Some(0.0),
+        }]],
+        preset_entries: vec![PresetEntry {
+            name: ['\0'; 20],
+            midi_bank_number: 1,
+            midi_preset_number: 2,
+            num_zones: 1,
+        }],
+        preset_zones: vec![vec![PresetZone {
+            root_key: -1,
+            note_low: 0,
+            note_high: 127,
+            velocity_low: 0,
+            velocity_high: 127,
+            program_index: 0,
+        }]],
+        sequences: vec![],
+        labels: vec![],
+    };
+    let mut tables = vec![];
+    mus.write(&mut tables);
+    mus.msq_tables[0].offset = tables.len() as i32;
+    mus.header.offset_to_labels_offsets_table = tables.len() as i32 + 4;
+
+    let mut bytes = vec![];
+    mus.write(&mut bytes);
+    bytes.extend(b"QSMa");
+    bytes.extend(8u32.to_le_bytes());
+
+    let parsed = MusFile::parse(&bytes).unwrap();
+    assert_eq!(name_to_str(&parsed.wave_entries[0].name), "Piano");
+    assert_eq!(parsed.wave_entries[0].size, 64);
+    assert_eq!(
+        parsed.program_zones[0][0].modul_env.as_ref().unwrap().decay,
+        1.5
+    );
+    assert_eq!(parsed.preset_entries[0].midi_preset_number, 2);
+    assert_eq!(&bytes[parsed.sequences[0].clone()], b"QSMa");
+    assert_eq!(parsed.labels, [8]);
+}
@@ -0,0 +1,209 @@
+//! Where the tools write their output, with the options shared by all of them.
+
+use std::path::{Path, PathBuf};
+
+use log::{error, info};
+
+use crate::report;
+
+/// What to do when an output file or folder already exists.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Conflict {
+    /// Replace existing files, and write into existing folders.
+    #[default]
+    Overwrite,
+    /// Leave the existing output alone and skip the input.
+    Skip,
+    /// Add `_1`, `_2`, ... to the name until it's unused.
+    Rename,
+}
+
+// Output options, flattened into the arguments of every tool that writes files.
+// Not a doc comment, as clap would use it for the description of the tool.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct OutputArgs {
+    /// Where to write the output of a single input file, instead of next to it.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+    /// Folder to write the outputs to, mirroring the folders of the input.
+    #[clap(long, conflicts_with = "output")]
+    pub output_root: Option<PathBuf>,
+    /// What to do when an output already exists.
+    #[clap(long, value_enum, default_value_t)]
+    pub on_conflict: Conflict,
+}
+
+/// Works out the output paths for the files found from one input path.
+#[derive(Clone, Debug)]
+pub struct Output {
+    args: OutputArgs,
+    /// Folder the input paths are relative to when mirroring them.
+    input_root: PathBuf,
+}
+
+impl Output {
+    /// Displays an error and quits if `-o` is given for a folder of inputs.
+    #[must_use]
+    pub fn new(args: &OutputArgs, input: &Path) -> Self {
+        if args.output.is_some() && input.is_dir() {
+            error!("-o needs a single input file, use --output-root for folders");
+            std::process::exit(1);
+        }
+        let input_root = if input.is_dir() {
+            input.to_owned()
+        } else {
+            input.parent().map(Path::to_owned).unwrap_or_default()
+        };
+        Self {
+            args: args.clone(),
+            input_root,
+        }
+    }
+
+    /// Where to write the main output file, given the path it has next to
+    /// the input. `None` means the input should be skipped.
+    #[must_use]
+    pub fn file(&self, default: &Path) -> Option<PathBuf> {
+        let path = self
+            .args
+            .output
+            .clone()
+            .unwrap_or_else(|| self.mirror(default));
+        self.resolve(path, Path::is_file)
+    }
+
+    /// Where to write another output file next to `main`, with its extension
+    /// changed to `extension`.
+    #[must_use]
+    pub fn extra_file(&self, main: &Path, extension: &str) -> Option<PathBuf> {
+        self.resolve(main.with_extension(extension), Path::is_file)
+    }
+
+    /// Creates the output folder, given the path it has next to the input,
+    /// and returns it. An existing folder is reused unless the conflict
+    /// policy says otherwise.
+    #[must_use]
+    pub fn dir(&self, default: &Path) -> Option<PathBuf> {
+        let path = self
+            .args
+            .output
+            .clone()
+            .unwrap_or_else(|| self.mirror(default));
+        let path = self.resolve(path, Path::exists)?;
+        if let Err(e) = std::fs::create_dir_all(&path) {
+            error!("Unable to create output folder {path:?}: {e}");
+            return None;
+        }
+        Some(path)
+    }
+
+    /// The options for another tool working on `input` alone, with the
+    /// output root moved to the folder `input` is mirrored to.
+    #[must_use]
+    pub fn args_for(&self, input: &Path) -> OutputArgs {
+        OutputArgs {
+            output_root: self
+                .args
+                .output_root
+                .as_ref()
+                .and_then(|_| self.mirror(input).parent().map(Path::to_owned)),
+            ..self.args.clone()
+        }
+    }
+
+    /// Moves `path` from the input folder to the output root.
+    fn mirror(&self, path: &Path) -> PathBuf {
+        let Some(root) = &self.args.output_root else {
+            return path.to_owned();
+        };
+        match path.strip_prefix(&self.input_root) {
+            Ok(relative) => root.join(relative),
+            Err(_) => root.join(path.file_name().unwrap_or_default()),
+        }
+    }
+
+    /// Applies the conflict policy, creates the parent folders and adds the
+    /// path to the report.
+    fn resolve(&self, path: PathBuf, exists: fn(&Path) -> bool) -> Option<PathBuf> {
+        let path = if exists(&path) {
+            match self.args.on_conflict {
+                Conflict::Overwrite => path,
+                Conflict::Skip => {
+                    info!("{path:?} already exists, skipping");
+                    return None;
+                }
+                Conflict::Rename => (1..)
+                    .map(|n| numbered(&path, n))
+                    .find(|path| !path.exists())
+                    .unwrap(),
+            }
+        } else {
+            path
+        };
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                error!("Unable to create output folder {parent:?}: {e}");
+                return None;
+            }
+        }
+        report::written(&path);
+        Some(path)
+    }
+}
+
+/// `path` with `_n` added to the end of its name, before the extension.
+fn numbered(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push(format!("_{n}"));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+#[test]
+fn paths() {
+    let temp = std::env::temp_dir().join(format!("unlokable_output_{}", std::process::id()));
+    let input = temp.join("in");
+    std::fs::create_dir_all(input.join("sub")).unwrap();
+    std::fs::write(input.join("sub/a.seq"), []).unwrap();
+
+    let output = |output, output_root, on_conflict| {
+        Output::new(
+            &OutputArgs {
+                output,
+                output_root,
+                on_conflict,
+            },
+            &input,
+        )
+    };
+    let default = input.join("sub/a.seq");
+
+    let overwrite = output(None, None, Conflict::Overwrite);
+    assert_eq!(overwrite.file(&default), Some(default.clone()));
+    assert_eq!(output(None, None, Conflict::Skip).file(&default), None);
+    let rename = output(None, None, Conflict::Rename);
+    assert_eq!(rename.file(&default), Some(input.join("sub/a_1.seq")));
+    assert_eq!(
+        rename.extra_file(&default, "mid"),
+        Some(input.join("sub/a.mid"))
+    );
+
+    let mirrored = output(None, Some(temp.join("out")), Conflict::Skip);
+    assert_eq!(mirrored.file(&default), Some(temp.join("out/sub/a.seq")));
+    assert!(temp.join("out/sub").is_dir());
+    let args = mirrored.args_for(&input.join("sub/a.cds"));
+    assert_eq!(args.output, None);
+    assert_eq!(args.output_root, Some(temp.join("out/sub")));
+    assert_eq!(args.on_conflict, Conflict::Skip);
+    assert_eq!(
+        mirrored.dir(&input.join("sub/a")),
+        Some(temp.join("out/sub/a"))
+    );
+    assert_eq!(mirrored.dir(&input.join("sub/a")), None);
+    assert_eq!(overwrite.dir(&input.join("sub")), Some(input.join("sub")));
+
+    std::fs::remove_dir_all(temp).unwrap();
+}
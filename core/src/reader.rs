@@ -0,0 +1,175 @@
+use crate::{Error, Result};
+
+/// A cursor over a byte slice used by all of the format parsers.
+///
+/// Every read takes the name of the field being read, so that a truncated
+/// file reports exactly where and what it was expecting.
+#[derive(Clone, Debug)]
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    #[must_use]
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    /// The offset of the next byte to be read.
+    #[must_use]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Total length of the underlying slice.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The bytes that haven't been read yet.
+    #[must_use]
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.position..]
+    }
+
+    pub fn seek(&mut self, field: &'static str, position: usize) -> Result<()> {
+        if position > self.bytes.len() {
+            return Err(Error::OffsetOutOfRange {
+                field,
+                offset: position,
+                limit: self.bytes.len(),
+            });
+        }
+        self.position = position;
+        Ok(())
+    }
+
+    pub fn skip(&mut self, field: &'static str, count: usize) -> Result<()> {
+        self.bytes(field, count).map(|_| ())
+    }
+
+    pub fn bytes(&mut self, field: &'static str, count: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .position
+            .checked_add(count)
+            .and_then(|end| self.bytes.get(self.position..end))
+            .ok_or(Error::Truncated {
+                field,
+                offset: self.position,
+                size: count,
+                len: self.bytes.len(),
+            })?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    pub fn array<const N: usize>(&mut self, field: &'static str) -> Result<[u8; N]> {
+        self.bytes(field, N).map(|bytes| bytes.try_into().unwrap())
+    }
+
+    /// Reads a four byte magic number, failing if it isn't `expected`.
+    pub fn magic(&mut self, format: &'static str, expected: [u8; 4]) -> Result<()> {
+        let offset = self.position;
+        let found = self.array("magic")?;
+        if found != expected {
+            return Err(Error::BadMagic {
+                format,
+                offset,
+                expected,
+                found,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn u8(&mut self, field: &'static str) -> Result<u8> {
+        self.array::<1>(field).map(|[byte]| byte)
+    }
+
+    pub fn u16_le(&mut self, field: &'static str) -> Result<u16> {
+        self.array(field).map(u16::from_le_bytes)
+    }
+
+    pub fn u16_be(&mut self, field: &'static str) -> Result<u16> {
+        self.array(field).map(u16::from_be_bytes)
+    }
+
+    pub fn u32_le(&mut self, field: &'static str) -> Result<u32> {
+        self.array(field).map(u32::from_le_bytes)
+    }
+
+    pub fn u32_be(&mut self, field: &'static str) -> Result<u32> {
+        self.array(field).map(u32::from_be_bytes)
+    }
+
+    /// Reads a MIDI variable length quantity of up to four bytes.
+    pub fn var_len(&mut self, field: &'static str) -> Result<u32> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.u8(field)?;
+            value = value << 7 | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        Ok(value)
+    }
+
+    pub fn i32_le(&mut self, field: &'static str) -> Result<i32> {
+        self.array(field).map(i32::from_le_bytes)
+    }
+
+    pub fn f32_le(&mut self, field: &'static str) -> Result<f32> {
+        self.array(field).map(f32::from_le_bytes)
+    }
+}
+
+/// Checks that `range` lies within a buffer of `len` bytes.
+pub(crate) fn check_range(
+    field: &'static str,
+    range: std::ops::Range<usize>,
+    len: usize,
+) -> Result<std::ops::Range<usize>> {
+    if range.start > range.end {
+        Err(Error::OffsetOutOfRange {
+            field,
+            offset: range.start,
+            limit: range.end,
+        })
+    } else if range.end > len {
+        Err(Error::OffsetOutOfRange {
+            field,
+            offset: range.end,
+            limit: len,
+        })
+    } else {
+        Ok(range)
+    }
+}
+
+#[test]
+fn reading() {
+    let mut reader = Reader::new(&[1, 0, 0, 0, 0, 2, 3]);
+    assert_eq!(reader.u32_le("a"), Ok(1));
+    assert_eq!(reader.u16_be("b"), Ok(2));
+    assert_eq!(reader.position(), 6);
+    assert_eq!(
+        reader.u16_le("c"),
+        Err(Error::Truncated {
+            field: "c",
+            offset: 6,
+            size: 2,
+            len: 7
+        })
+    );
+    assert_eq!(reader.position(), 6);
+    assert_eq!(reader.u8("d"), Ok(3));
+    assert!(reader.remaining().is_empty());
+}
@@ -0,0 +1,134 @@
+//! Machine-readable reports of what the tools read and wrote, for scripts
+//! that would otherwise have to scrape the log.
+
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+}
+
+// Report options, flattened into the arguments of every tool.
+// Not a doc comment, as clap would use it for the description of the tool.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct ReportArgs {
+    /// Print a report of what was read and written to STDOUT instead of the usual output,
+    /// only logging errors.
+    #[clap(long, value_enum)]
+    pub report: Option<Format>,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// The tool the report is from, which the executable's name doesn't say for
+/// the subcommands of `unlokable`.
+static TOOL: Mutex<&str> = Mutex::new("");
+
+thread_local! {
+    static ENTRY: RefCell<Option<Map<String, Value>>> = const { RefCell::new(None) };
+}
+
+impl ReportArgs {
+    /// Starts reporting on behalf of `tool` if it was asked for, quieting
+    /// the log so only the report is on STDOUT.
+    pub fn start(&self, tool: &'static str) {
+        if self.report.is_some() {
+            *TOOL.lock().unwrap() = tool;
+            ENABLED.store(true, Ordering::Relaxed);
+            log::set_max_level(log::LevelFilter::Error);
+        }
+    }
+}
+
+/// Whether a report is being made.
+#[must_use]
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Sets a field in the report of the file being worked on, doing nothing if
+/// no report is being made.
+pub fn set(key: &str, value: impl Serialize) {
+    ENTRY.with_borrow_mut(|entry| {
+        if let Some(entry) = entry {
+            entry.insert(key.to_owned(), to_value(value));
+        }
+    });
+}
+
+/// Adds to a list in the report of the file being worked on.
+pub fn push(key: &str, value: impl Serialize) {
+    ENTRY.with_borrow_mut(|entry| {
+        if let Some(entry) = entry {
+            let list = entry.entry(key).or_insert_with(|| Value::Array(vec![]));
+            if let Value::Array(list) = list {
+                list.push(to_value(value));
+            }
+        }
+    });
+}
+
+fn to_value(value: impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap_or_else(|e| Value::String(format!("error: {e}")))
+}
+
+/// Adds a path to the outputs of the file being worked on.
+pub fn written(path: &Path) {
+    push("written", path);
+}
+
+/// Starts the report on `input`, which the current thread works on.
+pub(crate) fn begin(input: &Path) {
+    let mut entry = Map::new();
+    entry.insert("input".to_owned(), input.to_string_lossy().into());
+    ENTRY.set(Some(entry));
+}
+
+/// Ends the report on the current file, returning it.
+pub(crate) fn finish(ok: bool) -> Value {
+    let mut entry = ENTRY.take().unwrap_or_default();
+    entry.insert("ok".to_owned(), ok.into());
+    Value::Object(entry)
+}
+
+/// Prints the whole report, with the file reports in the order given.
+pub(crate) fn print(files: Vec<Value>, failed: &[PathBuf]) {
+    let report = json!({
+        "tool": *TOOL.lock().unwrap(),
+        "version": env!("CARGO_PKG_VERSION"),
+        "succeeded": files.len() - failed.len(),
+        "failed": failed.len(),
+        "files": files,
+    });
+    println!("{report:#}");
+}
+
+#[test]
+fn entries() {
+    set("ignored", 1);
+    begin(Path::new("music/a.cds"));
+    set("ppqn", 480u16);
+    set("header", json!({ "version": 0x0101 }));
+    written(Path::new("music/a.seq"));
+    written(Path::new("music/a.mid"));
+    assert_eq!(
+        finish(true),
+        json!({
+            "input": "music/a.cds",
+            "ppqn": 480,
+            "header": { "version": 257 },
+            "written": ["music/a.seq", "music/a.mid"],
+            "ok": true,
+        })
+    );
+    assert_eq!(finish(false), json!({ "ok": false }));
+}
@@ -0,0 +1,275 @@
+//! Sony SEQ sequences, a big endian header followed by a single MIDI track.
+//!
+//! The track differs from SMF ones in its meta events: a tempo is `FF 51`
+//! followed directly by the three tempo bytes, and the track ends with `FF 2F 00`.
+
+use crate::{
+    midi::{self, Event, Message, Smf},
+    Error, Reader, Result,
+};
+
+pub const MAGIC: [u8; 4] = *b"pQES";
+pub const HEADER_SIZE: usize = 15;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Header {
+    pub version: u32,
+    pub ppqn: u16,
+    /// Microseconds per quarter note, only the lower 24 bits are stored.
+    pub tempo: u32,
+    /// Numerator and the power of two denominator of the time signature.
+    pub time_signature: (u8, u8),
+}
+
+impl Header {
+    /// The header `cds2seq` gives converted files.
+    #[must_use]
+    pub fn new(ppqn: u16, tempo: u32) -> Self {
+        Self {
+            version: 1,
+            ppqn,
+            tempo,
+            time_signature: (4, 2),
+        }
+    }
+
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        bytes.magic("SEQ", MAGIC)?;
+        Ok(Self {
+            version: bytes.u32_be("version")?,
+            ppqn: bytes.u16_be("ppqn")?,
+            tempo: bytes
+                .array::<3>("tempo")?
+                .into_iter()
+                .fold(0, |acc, x| acc << 8 | x as u32),
+            time_signature: (bytes.u8("time_signature")?, bytes.u8("time_signature")?),
+        })
+    }
+
+    /// The tempo as it's stored in the file and in `FF 51` events.
+    #[must_use]
+    pub fn tempo_bytes(&self) -> [u8; 3] {
+        let [_, a, b, c] = self.tempo.to_be_bytes();
+        [a, b, c]
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(MAGIC);
+        out.extend(self.version.to_be_bytes());
+        out.extend(self.ppqn.to_be_bytes());
+        out.extend(self.tempo_bytes());
+        out.extend([self.time_signature.0, self.time_signature.1]);
+    }
+}
+
+/// A whole SEQ file, with the track read up to its end.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SeqFile {
+    pub header: Header,
+    pub events: Vec<Event>,
+}
+
+impl SeqFile {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut bytes = Reader::new(bytes);
+        let header = Header::parse(&mut bytes)?;
+        let events = parse_events(&mut bytes)?;
+        Ok(Self { header, events })
+    }
+
+    /// Converts to a type 0 MIDI file, with the tempo and time signature of
+    /// the header at the start of the track.
+    #[must_use]
+    pub fn to_smf(&self) -> Smf {
+        let mut track = vec![
+            Event {
+                delta: 0,
+                message: Message::Tempo(self.header.tempo),
+            },
+            Event {
+                delta: 0,
+                message: Message::TimeSignature(
+                    self.header.time_signature.0,
+                    self.header.time_signature.1,
+                ),
+            },
+        ];
+        track.extend(self.events.iter().cloned());
+
+        Smf {
+            format: 0,
+            ppqn: self.header.ppqn,
+            tracks: vec![track],
+        }
+    }
+}
+
+/// Reads events until the end of the track, which is included.
+pub fn parse_events(bytes: &mut Reader) -> Result<Vec<Event>> {
+    let mut events = vec![];
+    let mut running_status = None;
+    loop {
+        let event = read_event(bytes, &mut running_status)?;
+        let end = event.message == Message::EndOfTrack;
+        events.push(event);
+        if end {
+            return Ok(events);
+        }
+    }
+}
+
+/// Reads a single event, keeping track of the running status.
+pub fn read_event(bytes: &mut Reader, running_status: &mut Option<u8>) -> Result<Event> {
+    let delta = bytes.var_len("delta")?;
+    let offset = bytes.position();
+    let message = match bytes.u8("status")? {
+        0xff => match bytes.u8("meta_type")? {
+            0x51 => Message::Tempo(
+                bytes
+                    .array::<3>("tempo")?
+                    .into_iter()
+                    .fold(0, |acc, x| acc << 8 | x as u32),
+            ),
+            0x2f => {
+                // The length byte of the end of track is sometimes left out
+                if bytes.remaining().first() == Some(&0) {
+                    bytes.skip("end_of_track", 1)?;
+                }
+                Message::EndOfTrack
+            }
+            kind => {
+                let len = bytes.var_len("meta_length")?;
+                Message::Meta {
+                    kind,
+                    data: bytes.bytes("meta", len as usize)?.to_vec(),
+                }
+            }
+        },
+        0xf0 => {
+            let len = bytes.var_len("sysex_length")?;
+            Message::SysEx(bytes.bytes("sysex", len as usize)?.to_vec())
+        }
+        status @ 0xf1..=0xfe => {
+            return Err(Error::BadEvent {
+                format: "SEQ",
+                offset,
+                status,
+            })
+        }
+        first => midi::read_channel(bytes, "SEQ", first, running_status)?,
+    };
+    Ok(Event { delta, message })
+}
+
+/// Writes events in the form [`parse_events`] reads, leaving out channel
+/// statuses repeated since the last meta event.
+pub fn write_events(events: &[Event], out: &mut Vec<u8>) {
+    let mut running_status = None;
+    for event in events {
+        midi::write_var_len(event.delta, out);
+        match &event.message {
+            Message::Channel { status, data } => {
+                if running_status != Some(*status) {
+                    out.push(*status);
+                    running_status = Some(*status);
+                }
+                out.extend(&data[..midi::data_len(*status)]);
+            }
+            Message::Tempo(tempo) => {
+                out.extend([0xff, 0x51]);
+                out.extend(&tempo.to_be_bytes()[1..]);
+                running_status = None;
+            }
+            message => {
+                message.write(out);
+                running_status = None;
+            }
+        }
+    }
+}
+
+#[test]
+fn header() {
+    let file = include_bytes!("../tests/with_loop_normal.seq");
+    let header = Header::parse(&mut Reader::new(file)).unwrap();
+    assert_eq!(header.ppqn, 480);
+    assert_eq!(header.tempo, 0x07a120);
+    assert_eq!(header.time_signature, (4, 2));
+
+    let mut written = vec![];
+    header.write(&mut written);
+    assert_eq!(written, file[0..HEADER_SIZE]);
+}
+
+#[cfg(test)]
+fn seq_file(track: &[u8]) -> Vec<u8> {
+    let mut file = vec![];
+    Header::new(480, 500_000).write(&mut file);
+    file.extend(track);
+    file
+}
+
+#[test]
+fn events() {
+    let file = seq_file(&[
+        0x00, 0xb0, 0x63, 0x14, // Loop start
+        0x00, 0x06, 0x00, // Running status data entry
+        0x00, 0xc0, 0x05, // Program change
+        0x81, 0x00, 0x90, 0x3c, 0x64, // Note on after 128 ticks
+        0x60, 0x3c, 0x00, // Running status note off
+        0x00, 0xff, 0x51, 0x07, 0xa1, 0x20, // Tempo
+        0x00, 0xb0, 0x63, 0x1e, // Loop end
+        0x00, 0xff, 0x2f, 0x00,
+    ]);
+    let seq = SeqFile::parse(&file).unwrap();
+    let messages = seq
+        .events
+        .iter()
+        .map(|event| &event.message)
+        .collect::<Vec<_>>();
+    assert_eq!(messages.len(), 8);
+    assert_eq!(messages[0].loop_marker(), Some(midi::LoopMarker::Start));
+    assert_eq!(
+        *messages[1],
+        Message::Channel {
+            status: 0xb0,
+            data: [0x06, 0x00]
+        }
+    );
+    assert_eq!(seq.events[3].delta, 128);
+    assert_eq!(
+        *messages[4],
+        Message::Channel {
+            status: 0x90,
+            data: [0x3c, 0x00]
+        }
+    );
+    assert_eq!(*messages[5], Message::Tempo(500_000));
+    assert_eq!(messages[6].loop_marker(), Some(midi::LoopMarker::End));
+    assert_eq!(*messages[7], Message::EndOfTrack);
+
+    let mut written = vec![];
+    write_events(&seq.events, &mut written);
+    assert_eq!(written, file[HEADER_SIZE..]);
+
+    let mut smf = vec![];
+    seq.to_smf().write(&mut smf);
+    assert_eq!(&smf[0..4], b"MThd");
+    assert!(smf.ends_with(&[0xff, 0x2f, 0x00]));
+}
+
+#[test]
+fn bad_events() {
+    assert!(matches!(
+        SeqFile::parse(&seq_file(&[0x00, 0x3c, 0x64])),
+        Err(Error::BadEvent {
+            offset: 16,
+            status: 0x3c,
+            ..
+        })
+    ));
+    assert!(matches!(
+        SeqFile::parse(&seq_file(&[0x00, 0x90, 0x3c])),
+        Err(Error::Truncated { .. })
+    ));
+}
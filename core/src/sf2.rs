@@ -0,0 +1,345 @@
+//! SoundFont 2 banks, written from the instrument data of the other formats.
+//!
+//! Only what the converters need is supported: mono 16-bit samples, and
+//! zones made of generators without any modulators or global zones.
+
+use std::ops::Range;
+
+/// Samples of silence that have to follow every sample in the `smpl` chunk.
+const SAMPLE_PADDING: usize = 46;
+
+/// The generators used by the converters, with their SF2 operator numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u16)]
+pub enum Generator {
+    VibLfoToPitch = 6,
+    ModEnvToPitch = 7,
+    ReverbEffectsSend = 16,
+    Pan = 17,
+    DelayVibLfo = 23,
+    FreqVibLfo = 24,
+    DelayModEnv = 25,
+    AttackModEnv = 26,
+    HoldModEnv = 27,
+    DecayModEnv = 28,
+    SustainModEnv = 29,
+    ReleaseModEnv = 30,
+    DelayVolEnv = 33,
+    AttackVolEnv = 34,
+    HoldVolEnv = 35,
+    DecayVolEnv = 36,
+    SustainVolEnv = 37,
+    ReleaseVolEnv = 38,
+    KeynumToVolEnvHold = 39,
+    KeynumToVolEnvDecay = 40,
+    KeyRange = 43,
+    VelRange = 44,
+    InitialAttenuation = 48,
+    CoarseTune = 51,
+    FineTune = 52,
+    SampleModes = 54,
+    OverridingRootKey = 58,
+}
+
+const GENERATOR_INSTRUMENT: u16 = 41;
+const GENERATOR_SAMPLE_ID: u16 = 53;
+
+/// Converts a duration to the timecents used by envelope generators.
+#[must_use]
+pub fn timecents(seconds: f32) -> i32 {
+    (1200.0 * f32::log2(seconds.max(0.001))) as i32
+}
+
+/// Converts a linear amplitude to an attenuation in centibels.
+#[must_use]
+pub fn centibels(amplitude: f32) -> i32 {
+    (-200.0 * f32::log10(amplitude.max(0.00001))) as i32
+}
+
+/// A zone of an instrument or preset, playing the sample or instrument at `index`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Zone {
+    pub generators: Vec<(Generator, i16)>,
+    pub index: u16,
+}
+
+impl Zone {
+    #[must_use]
+    pub fn new(index: u16) -> Self {
+        Self {
+            generators: vec![],
+            index,
+        }
+    }
+
+    /// Adds a generator, clamping the amount to what fits in the file.
+    pub fn push(&mut self, generator: Generator, amount: i32) {
+        self.generators.push((
+            generator,
+            amount.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+        ));
+    }
+
+    /// Adds a key or velocity range.
+    pub fn push_range(&mut self, generator: Generator, low: u8, high: u8) {
+        self.generators
+            .push((generator, i16::from_le_bytes([low, high])));
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sample {
+    pub name: String,
+    pub samples: Vec<i16>,
+    pub sample_rate: u32,
+    /// Loop points relative to the start of the sample, with the end exclusive.
+    pub loop_range: Option<Range<u32>>,
+    pub original_key: u8,
+    /// Pitch correction in cents.
+    pub correction: i8,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instrument {
+    pub name: String,
+    pub zones: Vec<Zone>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Preset {
+    pub name: String,
+    pub bank: u16,
+    pub program: u16,
+    pub zones: Vec<Zone>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SoundFont {
+    pub name: String,
+    /// Name of the sound engine the bank targets.
+    pub engine: String,
+    /// Name of the tool that created the bank.
+    pub software: String,
+    pub samples: Vec<Sample>,
+    pub instruments: Vec<Instrument>,
+    pub presets: Vec<Preset>,
+}
+
+impl SoundFont {
+    pub fn write(&self, out: &mut Vec<u8>) {
+        let mut info = b"INFO".to_vec();
+        chunk(&mut info, b"ifil", &[2, 0, 1, 0]);
+        chunk(&mut info, b"isng", &zstr(&self.engine));
+        chunk(&mut info, b"INAM", &zstr(&self.name));
+        chunk(&mut info, b"ISFT", &zstr(&self.software));
+
+        let mut smpl = vec![];
+        let mut shdr = vec![];
+        for sample in &self.samples {
+            let start = (smpl.len() / 2) as u32;
+            let end = start + sample.samples.len() as u32;
+            let loop_range = sample
+                .loop_range
+                .clone()
+                .map_or(start..end, |range| start + range.start..start + range.end);
+            for value in &sample.samples {
+                smpl.extend(value.to_le_bytes());
+            }
+            smpl.extend([0; 2 * SAMPLE_PADDING]);
+
+            shdr.extend(name(&sample.name));
+            for field in [
+                start,
+                end,
+                loop_range.start,
+                loop_range.end,
+                sample.sample_rate,
+            ] {
+                shdr.extend(field.to_le_bytes());
+            }
+            shdr.push(sample.original_key);
+            shdr.push(sample.correction as u8);
+            // Sample link, then the type of a mono sample
+            shdr.extend(0u16.to_le_bytes());
+            shdr.extend(1u16.to_le_bytes());
+        }
+        shdr.extend([0; 46]);
+        shdr[self.samples.len() * 46..][..3].copy_from_slice(b"EOS");
+
+        let mut sdta = b"sdta".to_vec();
+        chunk(&mut sdta, b"smpl", &smpl);
+
+        let mut inst = vec![];
+        let mut ibag = vec![];
+        let mut igen = vec![];
+        for instrument in &self.instruments {
+            inst.extend(name(&instrument.name));
+            inst.extend(((ibag.len() / 4) as u16).to_le_bytes());
+            write_zones(&instrument.zones, GENERATOR_SAMPLE_ID, &mut ibag, &mut igen);
+        }
+        inst.extend(name("EOI"));
+        inst.extend(((ibag.len() / 4) as u16).to_le_bytes());
+        terminate_zones(&mut ibag, &mut igen);
+
+        let mut phdr = vec![];
+        let mut pbag = vec![];
+        let mut pgen = vec![];
+        for preset in &self.presets {
+            phdr.extend(name(&preset.name));
+            phdr.extend(preset.program.to_le_bytes());
+            phdr.extend(preset.bank.to_le_bytes());
+            phdr.extend(((pbag.len() / 4) as u16).to_le_bytes());
+            // Library, genre and morphology are reserved
+            phdr.extend([0; 12]);
+            write_zones(&preset.zones, GENERATOR_INSTRUMENT, &mut pbag, &mut pgen);
+        }
+        phdr.extend(name("EOP"));
+        phdr.extend([0; 4]);
+        phdr.extend(((pbag.len() / 4) as u16).to_le_bytes());
+        phdr.extend([0; 12]);
+        terminate_zones(&mut pbag, &mut pgen);
+
+        let mut pdta = b"pdta".to_vec();
+        chunk(&mut pdta, b"phdr", &phdr);
+        chunk(&mut pdta, b"pbag", &pbag);
+        chunk(&mut pdta, b"pmod", &[0; 10]);
+        chunk(&mut pdta, b"pgen", &pgen);
+        chunk(&mut pdta, b"inst", &inst);
+        chunk(&mut pdta, b"ibag", &ibag);
+        chunk(&mut pdta, b"imod", &[0; 10]);
+        chunk(&mut pdta, b"igen", &igen);
+        chunk(&mut pdta, b"shdr", &shdr);
+
+        let mut body = b"sfbk".to_vec();
+        chunk(&mut body, b"LIST", &info);
+        chunk(&mut body, b"LIST", &sdta);
+        chunk(&mut body, b"LIST", &pdta);
+        chunk(out, b"RIFF", &body);
+    }
+}
+
+/// Writes the bags and generators of the zones, with the ranges first and
+/// the `index` generator last as the specification requires.
+fn write_zones(zones: &[Zone], index_generator: u16, bags: &mut Vec<u8>, generators: &mut Vec<u8>) {
+    for zone in zones {
+        bags.extend(((generators.len() / 4) as u16).to_le_bytes());
+        bags.extend(0u16.to_le_bytes());
+
+        let (mut ranges, rest): (Vec<_>, Vec<_>) =
+            zone.generators.iter().partition(|(generator, _)| {
+                matches!(generator, Generator::KeyRange | Generator::VelRange)
+            });
+        ranges.sort_by_key(|(generator, _)| *generator);
+        for (generator, amount) in ranges.into_iter().chain(rest) {
+            generators.extend((generator as u16).to_le_bytes());
+            generators.extend(amount.to_le_bytes());
+        }
+        generators.extend(index_generator.to_le_bytes());
+        generators.extend(zone.index.to_le_bytes());
+    }
+}
+
+/// Adds the terminal bag and generator records.
+fn terminate_zones(bags: &mut Vec<u8>, generators: &mut Vec<u8>) {
+    bags.extend(((generators.len() / 4) as u16).to_le_bytes());
+    bags.extend(0u16.to_le_bytes());
+    generators.extend([0; 4]);
+}
+
+fn chunk(out: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+    out.extend(id);
+    out.extend((body.len() as u32).to_le_bytes());
+    out.extend(body);
+    if body.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// A zero terminated string padded to an even length.
+fn zstr(text: &str) -> Vec<u8> {
+    let mut bytes = text.as_bytes().to_vec();
+    bytes.push(0);
+    if bytes.len() % 2 == 1 {
+        bytes.push(0);
+    }
+    bytes
+}
+
+/// A fixed size name field, truncated to leave room for the terminator.
+fn name(text: &str) -> [u8; 20] {
+    let mut bytes = [0; 20];
+    for (byte, &c) in bytes.iter_mut().zip(text.as_bytes().iter().take(19)) {
+        *byte = c;
+    }
+    bytes
+}
+
+#[test]
+fn layout() {
+    let mut zone = Zone::new(0);
+    zone.push(Generator::Pan, 100_000);
+    zone.push_range(Generator::KeyRange, 10, 20);
+    let soundfont = SoundFont {
+        name: "Test".to_owned(),
+        engine: "EMU8000".to_owned(),
+        software: "test".to_owned(),
+        samples: vec![Sample {
+            name: "Sample".to_owned(),
+            samples: vec![1, 2, 3],
+            sample_rate: 22050,
+            loop_range: Some(1..3),
+            original_key: 60,
+            correction: 0,
+        }],
+        instruments: vec![Instrument {
+            name: "Instrument".to_owned(),
+            zones: vec![zone.clone()],
+        }],
+        presets: vec![Preset {
+            name: "Preset".to_owned(),
+            bank: 0,
+            program: 5,
+            zones: vec![Zone::new(0)],
+        }],
+    };
+    let mut bytes = vec![];
+    soundfont.write(&mut bytes);
+
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(
+        u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize,
+        bytes.len() - 8
+    );
+    assert_eq!(&bytes[8..12], b"sfbk");
+
+    let find = |id: &[u8]| {
+        let offset = bytes.windows(4).position(|x| x == id).unwrap() + 8;
+        let size = u32::from_le_bytes(bytes[offset - 4..offset].try_into().unwrap()) as usize;
+        &bytes[offset..offset + size]
+    };
+    assert_eq!(find(b"smpl").len(), 2 * (3 + SAMPLE_PADDING));
+    // Key range first, the clamped pan, the sample ID, then the terminator
+    assert_eq!(
+        find(b"igen"),
+        [43, 0, 10, 20, 17, 0, 0xff, 0x7f, 53, 0, 0, 0, 0, 0, 0, 0]
+    );
+    assert_eq!(find(b"ibag"), [0, 0, 0, 0, 3, 0, 0, 0]);
+    let shdr = find(b"shdr");
+    assert_eq!(shdr.len(), 2 * 46);
+    // Start, end, loop start and loop end
+    assert_eq!(
+        shdr[20..36],
+        [0, 0, 0, 0, 3, 0, 0, 0, 1, 0, 0, 0, 3, 0, 0, 0]
+    );
+    assert_eq!(&shdr[46..49], b"EOS");
+    assert_eq!(find(b"phdr").len(), 2 * 38);
+}
+
+#[test]
+fn conversions() {
+    assert_eq!(timecents(1.0), 0);
+    assert_eq!(timecents(2.0), 1200);
+    assert_eq!(timecents(0.0), -11958);
+    assert_eq!(centibels(1.0), 0);
+    assert_eq!(centibels(0.5), 60);
+}
@@ -0,0 +1,469 @@
+//! Crystal Dynamics SND sound banks and their SMP sample files.
+
+use std::{fmt::Display, ops::Range};
+
+use crate::{reader::check_range, Error, Reader, Result};
+
+/// `DNSa` read as a little endian number.
+pub const MAGIC: u32 = 0x6153_4e44;
+/// `PMSa` read as a little endian number, only present in some SMP files.
+pub const SMP_MAGIC: u32 = 0x6153_4d50;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Version {
+    #[default]
+    SoulReaver,
+    Prototype,
+    Gex,
+}
+
+impl Version {
+    /// The first version the whole SND file parses as, since the header
+    /// doesn't say which one it is.
+    #[must_use]
+    pub fn detect(file: &[u8]) -> Option<Self> {
+        [Self::SoulReaver, Self::Prototype, Self::Gex]
+            .into_iter()
+            .find(|version| SndFile::parse(file, *version).is_ok())
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct HeaderSize {
+    pub size: i64,
+    pub original: Option<i64>,
+}
+
+impl HeaderSize {
+    pub fn new(x: impl Into<i64>) -> Self {
+        let x = x.into();
+        if x % 4 == 0 {
+            Self {
+                size: x,
+                original: None,
+            }
+        } else {
+            Self {
+                size: x - x % 4 + 4,
+                original: Some(x),
+            }
+        }
+    }
+
+    /// The size as it was stored in the file.
+    #[must_use]
+    pub fn stored(&self) -> i64 {
+        self.original.unwrap_or(self.size)
+    }
+}
+
+impl Display for HeaderSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.original {
+            Some(original) => write!(f, "{original} ({}) bytes", self.size),
+            None => write!(f, "{} bytes", self.size),
+        }
+    }
+}
+
+#[test]
+fn rounding() {
+    assert_eq!(HeaderSize::new(40).size, 40);
+    assert_eq!(HeaderSize::new(41u8).size, 44);
+    assert_eq!(HeaderSize::new(42u16).size, 44);
+    assert_eq!(HeaderSize::new(43u32).size, 44);
+    assert_eq!(HeaderSize::new(44).size, 44);
+    assert_eq!(HeaderSize::new(45).size, 48);
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct SndHeader {
+    pub magic_number: u32,
+    pub header_size: HeaderSize,
+    pub bank_version: Option<u32>,
+    pub num_programs: u32,
+    pub num_zones: u32,
+    pub num_waves: u32,
+    pub num_sequences: u32,
+    pub num_labels: u32,
+    pub reverb_mode: u32,
+    pub reverb_depth: u32,
+}
+
+impl SndHeader {
+    pub fn parse(bytes: &mut Reader, version: Version) -> Result<Self> {
+        Ok(match version {
+            Version::SoulReaver => Self {
+                magic_number: bytes.u32_le("magic_number")?,
+                header_size: HeaderSize::new(bytes.u32_le("header_size")?),
+                bank_version: Some(bytes.u32_le("bank_version")?),
+                num_programs: bytes.u32_le("num_programs")?,
+                num_zones: bytes.u32_le("num_zones")?,
+                num_waves: bytes.u32_le("num_waves")?,
+                num_sequences: bytes.u32_le("num_sequences")?,
+                num_labels: bytes.u32_le("num_labels")?,
+                reverb_mode: bytes.u32_le("reverb_mode")?,
+                reverb_depth: bytes.u32_le("reverb_depth")?,
+            },
+            Version::Prototype => Self {
+                magic_number: bytes.u32_le("magic_number")?,
+                header_size: HeaderSize::new(bytes.u32_le("header_size")?),
+                bank_version: Some(bytes.u16_le("bank_version")? as u32),
+                num_programs: bytes.array::<2>("num_programs")?[1] as u32,
+                num_zones: bytes.u16_le("num_zones")? as u32,
+                num_waves: bytes.u16_le("num_waves")? as u32,
+                num_sequences: bytes.u16_le("num_sequences")? as u32,
+                num_labels: bytes.u16_le("num_labels")? as u32,
+                reverb_mode: bytes.u16_le("reverb_mode")? as u32,
+                reverb_depth: bytes.u16_le("reverb_depth")? as u32,
+            },
+            Version::Gex => Self {
+                magic_number: bytes.u32_le("magic_number")?,
+                header_size: HeaderSize::new(bytes.u16_le("header_size")?),
+                bank_version: None,
+                num_programs: bytes.array::<2>("num_programs")?[1] as u32,
+                num_zones: bytes.u16_le("num_zones")? as u32,
+                num_waves: bytes.u16_le("num_waves")? as u32,
+                num_sequences: bytes.u16_le("num_sequences")? as u32,
+                num_labels: bytes.u16_le("num_labels")? as u32,
+                reverb_mode: bytes.u16_le("reverb_mode")? as u32,
+                reverb_depth: bytes.u16_le("reverb_depth")? as u32,
+            },
+        })
+    }
+
+    pub fn write(&self, version: Version, out: &mut Vec<u8>) {
+        out.extend(self.magic_number.to_le_bytes());
+        let counts = [
+            self.num_zones,
+            self.num_waves,
+            self.num_sequences,
+            self.num_labels,
+            self.reverb_mode,
+            self.reverb_depth,
+        ];
+        match version {
+            Version::SoulReaver => {
+                out.extend((self.header_size.stored() as u32).to_le_bytes());
+                out.extend(self.bank_version.unwrap_or_default().to_le_bytes());
+                out.extend(self.num_programs.to_le_bytes());
+                for count in counts {
+                    out.extend(count.to_le_bytes());
+                }
+            }
+            Version::Prototype | Version::Gex => {
+                if version == Version::Prototype {
+                    out.extend((self.header_size.stored() as u32).to_le_bytes());
+                    out.extend((self.bank_version.unwrap_or_default() as u16).to_le_bytes());
+                } else {
+                    out.extend((self.header_size.stored() as u16).to_le_bytes());
+                }
+                out.extend([0, self.num_programs as u8]);
+                for count in counts {
+                    out.extend((count as u16).to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SndProgram {
+    pub num_zones: u16,
+    pub first_tone: u16,
+    pub volume: u8,
+    pub pan_pos: u8,
+}
+
+impl SndProgram {
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        let program = Self {
+            num_zones: bytes.u16_le("num_zones")?,
+            first_tone: bytes.u16_le("first_tone")?,
+            volume: bytes.u8("volume")?,
+            pan_pos: bytes.u8("pan_pos")?,
+        };
+
+        bytes.skip("padding", 2)?;
+
+        Ok(program)
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.num_zones.to_le_bytes());
+        out.extend(self.first_tone.to_le_bytes());
+        out.extend([self.volume, self.pan_pos, 0, 0]);
+    }
+}
+
+#[derive(Debug)]
+pub struct SndZone {
+    pub priority: u8,
+    pub parent_program: u8,
+    pub volume: u8,
+    pub pan_pos: u8,
+    pub root_key: u8,
+    pub pitch_fine_tuning: u8,
+    pub note_low: u8,
+    pub note_high: u8,
+    pub mode: u8,
+    pub max_pitch_range: u8,
+    pub adsr1: u16,
+    pub adsr2: u16,
+    /// Index of the wave in the SMP file, this is zero based unlike VAB sample numbers.
+    pub wave_index: u16,
+}
+
+impl SndZone {
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        Ok(Self {
+            priority: bytes.u8("priority")?,
+            parent_program: bytes.u8("parent_program")?,
+            volume: bytes.u8("volume")?,
+            pan_pos: bytes.u8("pan_pos")?,
+            root_key: bytes.u8("root_key")?,
+            pitch_fine_tuning: bytes.u8("pitch_fine_tuning")?,
+            note_low: bytes.u8("note_low")?,
+            note_high: bytes.u8("note_high")?,
+            mode: bytes.u8("mode")?,
+            max_pitch_range: bytes.u8("max_pitch_range")?,
+            adsr1: bytes.u16_le("adsr1")?,
+            adsr2: bytes.u16_le("adsr2")?,
+            wave_index: bytes.u16_le("wave_index")?,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend([
+            self.priority,
+            self.parent_program,
+            self.volume,
+            self.pan_pos,
+            self.root_key,
+            self.pitch_fine_tuning,
+            self.note_low,
+            self.note_high,
+            self.mode,
+            self.max_pitch_range,
+        ]);
+        out.extend(self.adsr1.to_le_bytes());
+        out.extend(self.adsr2.to_le_bytes());
+        out.extend(self.wave_index.to_le_bytes());
+    }
+}
+
+#[derive(Debug)]
+pub struct SndFile {
+    pub header: SndHeader,
+    pub programs: Vec<SndProgram>,
+    pub zones: Vec<SndZone>,
+    /// Offsets of the waves as stored, the SMP file is relative to the first one.
+    pub wave_offsets: Vec<u32>,
+    pub sequence_offsets: Vec<u32>,
+    pub labels: Vec<u32>,
+    pub sequences: Vec<Range<usize>>,
+}
+
+impl SndFile {
+    pub fn parse(file: &[u8], version: Version) -> Result<Self> {
+        let bytes = &mut Reader::new(file);
+        let header = SndHeader::parse(bytes, version)?;
+        if header.magic_number != MAGIC {
+            return Err(Error::BadMagic {
+                format: "SND",
+                offset: 0,
+                expected: MAGIC.to_le_bytes(),
+                found: header.magic_number.to_le_bytes(),
+            });
+        }
+
+        if (bytes.position() as i64) < header.header_size.size {
+            bytes.seek("header_size", header.header_size.size as usize)?;
+        }
+
+        // Because some zones can access more zones than are available, we have to skip them in a lot of things
+        // The way we "skip" it is by setting the number of zones a program requests to zero.
+        let mut requested_zones = 0;
+        let programs = (0..header.num_programs)
+            .map(|_| {
+                let mut program = SndProgram::parse(bytes)?;
+                if requested_zones + program.num_zones > header.num_zones as u16 {
+                    program.num_zones = 0;
+                } else {
+                    requested_zones += program.num_zones;
+                }
+                Ok(program)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let zones = (0..header.num_zones)
+            .map(|_| SndZone::parse(bytes))
+            .collect::<Result<Vec<_>>>()?;
+        let wave_offsets = (0..header.num_waves)
+            .map(|_| bytes.u32_le("wave_offset"))
+            .collect::<Result<Vec<_>>>()?;
+        let sequence_offsets = (0..header.num_sequences)
+            .map(|_| bytes.u32_le("sequence_offset"))
+            .collect::<Result<Vec<_>>>()?;
+        let labels = (0..header.num_labels)
+            .map(|_| bytes.u32_le("label"))
+            .collect::<Result<Vec<_>>>()?;
+
+        let sequences_start = bytes.position();
+        let sequences = (0..sequence_offsets.len())
+            .map(|i| {
+                let start = sequences_start + sequence_offsets[i] as usize;
+                let end = sequence_offsets
+                    .get(i + 1)
+                    .map_or(file.len(), |next| sequences_start + *next as usize);
+                check_range("sequence_offset", start..end, file.len())
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            header,
+            programs,
+            zones,
+            wave_offsets,
+            sequence_offsets,
+            labels,
+            sequences,
+        })
+    }
+
+    /// Writes the header and tables, the sequences have to be appended after.
+    pub fn write(&self, version: Version, out: &mut Vec<u8>) {
+        let start = out.len();
+        self.header.write(version, out);
+        out.resize(start + self.header.header_size.size as usize, 0);
+        for program in &self.programs {
+            program.write(out);
+        }
+        for zone in &self.zones {
+            zone.write(out);
+        }
+        for offset in self
+            .wave_offsets
+            .iter()
+            .chain(&self.sequence_offsets)
+            .chain(&self.labels)
+        {
+            out.extend(offset.to_le_bytes());
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SmpFile {
+    pub magic_number: Option<u32>,
+    pub body_size: u32,
+    pub waves: Vec<Range<usize>>,
+}
+
+impl SmpFile {
+    pub fn parse(snd: &SndFile, file: &[u8]) -> Result<Self> {
+        let bytes = &mut Reader::new(file);
+        let first_bytes = bytes.u32_le("body_size")?;
+
+        let (magic_number, body_size) = if first_bytes == SMP_MAGIC {
+            (Some(first_bytes), bytes.u32_le("body_size")?)
+        } else {
+            (None, first_bytes)
+        };
+        let header_size = bytes.position();
+
+        let first_offset = snd.wave_offsets.first().copied().unwrap_or_default();
+        let offsets = snd
+            .wave_offsets
+            .iter()
+            .map(|offset| {
+                offset
+                    .checked_sub(first_offset)
+                    .ok_or(Error::OffsetOutOfRange {
+                        field: "wave_offset",
+                        offset: *offset as usize,
+                        limit: first_offset as usize,
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            magic_number,
+            body_size,
+            waves: (0..offsets.len())
+                .map(|i| {
+                    let start = header_size + offsets[i] as usize;
+                    let end = offsets
+                        .get(i + 1)
+                        .map_or(file.len(), |next| header_size + *next as usize);
+                    check_range("wave_offset", start..end, file.len())
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Writes the header of the SMP file, the waves have to be appended after.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        if let Some(magic) = self.magic_number {
+            out.extend(magic.to_le_bytes());
+        }
+        out.extend(self.body_size.to_le_bytes());
+    }
+}
+
+#[test]
+fn snd_round_trip() {
+    for version in [Version::SoulReaver, Version::Prototype, Version::Gex] {
+        let snd = SndFile {
+            header: SndHeader {
+                magic_number: MAGIC,
+                header_size: HeaderSize::new(if version == Version::Gex { 22 } else { 40 }),
+                bank_version: (version != Version::Gex).then_some(0x0102),
+                num_programs: 1,
+                num_zones: 1,
+                num_waves: 2,
+                num_sequences: 1,
+                num_labels: 1,
+                reverb_mode: 3,
+                reverb_depth: 4,
+            },
+            programs: vec![SndProgram {
+                num_zones: 1,
+                first_tone: 0,
+                volume: 0x7f,
+                pan_pos: 0x40,
+            }],
+            zones: vec![SndZone {
+                priority: 1,
+                parent_program: 0,
+                volume: 0x7f,
+                pan_pos: 0x40,
+                root_key: 60,
+                pitch_fine_tuning: 0,
+                note_low: 0,
+                note_high: 127,
+                mode: 0,
+                max_pitch_range: 2,
+                adsr1: 0x80ff,
+                adsr2: 0x5fc0,
+                wave_index: 1,
+            }],
+            wave_offsets: vec![0x100, 0x200],
+            sequence_offsets: vec![0],
+            labels: vec![8],
+            sequences: vec![],
+        };
+        let mut bytes = vec![];
+        snd.write(version, &mut bytes);
+        bytes.extend(b"QESa");
+
+        let parsed = SndFile::parse(&bytes, version).unwrap();
+        assert_eq!(parsed.header.num_programs, 1);
+        assert_eq!(parsed.header.bank_version, snd.header.bank_version);
+        assert_eq!(parsed.zones[0].adsr2, 0x5fc0);
+        assert_eq!(parsed.wave_offsets, snd.wave_offsets);
+        assert_eq!(parsed.labels, snd.labels);
+        assert_eq!(&bytes[parsed.sequences[0].clone()], b"QESa");
+        assert_eq!(Version::detect(&bytes), Some(version));
+    }
+}
@@ -0,0 +1,397 @@
+//! Sony VAB sound banks, made up of a `vh` header section and a `vb` body of VAG data.
+
+use std::ops::Range;
+
+use crate::{reader::check_range, Error, Reader, Result};
+
+pub const MAGIC: [u8; 4] = *b"pBAV";
+/// The number of slots in the program table.
+pub const MAX_PROGRAMS: usize = 128;
+/// The number of slots in the tone table of each program.
+pub const MAX_TONES: usize = 16;
+/// The number of entries in the VAG size table, including the unused first one.
+pub const MAX_VAGS: usize = 256;
+
+#[derive(Debug)]
+pub struct VabFile {
+    pub header: VabHeader,
+    pub programs: Vec<Program>,
+    pub tones: Vec<Vec<Tone>>,
+    pub vag_sizes: Vec<usize>,
+    pub vag_ranges: Vec<Range<usize>>,
+}
+
+impl VabFile {
+    /// The length of the header section of a bank with `programs_number` programs.
+    #[must_use]
+    pub fn header_size(programs_number: usize) -> usize {
+        32 + 16 * MAX_PROGRAMS + 32 * MAX_TONES * programs_number + 2 * MAX_VAGS
+    }
+
+    pub fn parse(file: &[u8]) -> Result<Self> {
+        let bytes = &mut Reader::new(file);
+        let header = VabHeader::parse(bytes)?;
+        if header.magic_number != u32::from_le_bytes(MAGIC) {
+            return Err(Error::BadMagic {
+                format: "VAB",
+                offset: 0,
+                expected: MAGIC,
+                found: header.magic_number.to_le_bytes(),
+            });
+        }
+        if file.len() < header.total_size as usize {
+            return Err(Error::OffsetOutOfRange {
+                field: "total_size",
+                offset: header.total_size as usize,
+                limit: file.len(),
+            });
+        }
+
+        let mut programs = Vec::with_capacity(header.programs_number as usize);
+        let mut index = 0;
+        while programs.len() < header.programs_number as usize {
+            if index == MAX_PROGRAMS {
+                return Err(Error::IndexOutOfRange {
+                    field: "programs_number",
+                    index: header.programs_number as i64,
+                    len: programs.len(),
+                });
+            }
+            let program = Program::parse(bytes, index as u8)?;
+            if program.tones_number != 0 {
+                programs.push(program);
+            }
+            index += 1;
+        }
+        bytes.skip("program", 16 * (MAX_PROGRAMS - index))?;
+
+        let tones = programs
+            .iter()
+            .map(|program| {
+                if program.tones_number as usize > MAX_TONES {
+                    return Err(Error::IndexOutOfRange {
+                        field: "tones_number",
+                        index: program.tones_number as i64,
+                        len: MAX_TONES,
+                    });
+                }
+                let tones = (0..program.tones_number)
+                    .map(|_| Tone::parse(bytes))
+                    .collect::<Result<Vec<_>>>()?;
+                bytes.skip("tone", 32 * (MAX_TONES - program.tones_number as usize))?;
+
+                Ok(tones)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if header.vags_number as usize >= MAX_VAGS {
+            return Err(Error::IndexOutOfRange {
+                field: "vags_number",
+                index: header.vags_number as i64,
+                len: MAX_VAGS - 1,
+            });
+        }
+        bytes.skip("vag_size", 2)?;
+        let vag_sizes = (0..header.vags_number)
+            .map(|_| Ok(bytes.u16_le("vag_size")? as usize * 8))
+            .collect::<Result<Vec<_>>>()?;
+        bytes.skip("vag_size", 2 * (MAX_VAGS - vag_sizes.len() - 1))?;
+
+        let mut cursor = bytes.position();
+        let vag_ranges = vag_sizes
+            .iter()
+            .map(|size| {
+                let range = check_range("vag_size", cursor..cursor + size, file.len())?;
+                cursor = range.end;
+                Ok(range)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            header,
+            programs,
+            tones,
+            vag_sizes,
+            vag_ranges,
+        })
+    }
+
+    /// Writes the header section into the start of `out`, growing it if needed.
+    ///
+    /// Unused program and tone slots are left untouched, so writing back over
+    /// a parsed file only changes the records that were parsed.
+    pub fn write_header(&self, out: &mut Vec<u8>) {
+        let used_programs = self.programs.iter().filter(|p| p.tones_number != 0);
+        let size = Self::header_size(used_programs.count());
+        if out.len() < size {
+            out.resize(size, 0);
+        }
+
+        let mut bytes = Vec::with_capacity(32);
+        self.header.write(&mut bytes);
+        out[0..32].copy_from_slice(&bytes);
+
+        for program in &self.programs {
+            let start = 32 + 16 * program.index as usize;
+            bytes.clear();
+            program.write(&mut bytes);
+            out[start..start + 16].copy_from_slice(&bytes);
+        }
+
+        let tone_blocks = self
+            .programs
+            .iter()
+            .zip(&self.tones)
+            .filter(|(program, _)| program.tones_number != 0);
+        let mut cursor = 32 + 16 * MAX_PROGRAMS;
+        for (_, tones) in tone_blocks {
+            for (i, tone) in tones.iter().enumerate() {
+                let start = cursor + 32 * i;
+                bytes.clear();
+                tone.write(&mut bytes);
+                out[start..start + 32].copy_from_slice(&bytes);
+            }
+            cursor += 32 * MAX_TONES;
+        }
+
+        out[cursor..cursor + 2].fill(0);
+        for (i, size) in self.vag_sizes.iter().enumerate() {
+            let start = cursor + 2 * (i + 1);
+            out[start..start + 2].copy_from_slice(&((size / 8) as u16).to_le_bytes());
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct VabHeader {
+    pub magic_number: u32,
+    pub version: u32,
+    pub vab_id: u32,
+    pub total_size: u32,
+    pub _pad0: u16,
+    pub programs_number: u16,
+    pub tones_number: u16,
+    pub vags_number: u16,
+    pub master_volume: u8,
+    pub master_pan: u8,
+    pub bank_attributes_1: u8,
+    pub bank_attributes_2: u8,
+    pub _pad1: u32,
+}
+
+impl VabHeader {
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        Ok(Self {
+            magic_number: bytes.u32_le("magic_number")?,
+            version: bytes.u32_le("version")?,
+            vab_id: bytes.u32_le("vab_id")?,
+            total_size: bytes.u32_le("total_size")?,
+            _pad0: bytes.u16_le("_pad0")?,
+            programs_number: bytes.u16_le("programs_number")?,
+            tones_number: bytes.u16_le("tones_number")?,
+            vags_number: bytes.u16_le("vags_number")?,
+            master_volume: bytes.u8("master_volume")?,
+            master_pan: bytes.u8("master_pan")?,
+            bank_attributes_1: bytes.u8("bank_attributes_1")?,
+            bank_attributes_2: bytes.u8("bank_attributes_2")?,
+            _pad1: bytes.u32_le("_pad1")?,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.magic_number.to_le_bytes());
+        out.extend(self.version.to_le_bytes());
+        out.extend(self.vab_id.to_le_bytes());
+        out.extend(self.total_size.to_le_bytes());
+        out.extend(self._pad0.to_le_bytes());
+        out.extend(self.programs_number.to_le_bytes());
+        out.extend(self.tones_number.to_le_bytes());
+        out.extend(self.vags_number.to_le_bytes());
+        out.extend([
+            self.master_volume,
+            self.master_pan,
+            self.bank_attributes_1,
+            self.bank_attributes_2,
+        ]);
+        out.extend(self._pad1.to_le_bytes());
+    }
+}
+
+#[derive(Debug)]
+pub struct Program {
+    /// Slot of the program in the program table, which is also its MIDI program number.
+    pub index: u8,
+    pub tones_number: u8,
+    pub volume: u8,
+    pub priority: u8,
+    pub mode: u8,
+    pub pan: u8,
+    pub _pad0: u8,
+    pub attribute: u16,
+    pub _pad1: u32,
+    pub _pad2: u32,
+}
+
+impl Program {
+    pub fn parse(bytes: &mut Reader, index: u8) -> Result<Self> {
+        Ok(Self {
+            index,
+            tones_number: bytes.u8("tones_number")?,
+            volume: bytes.u8("volume")?,
+            priority: bytes.u8("priority")?,
+            mode: bytes.u8("mode")?,
+            pan: bytes.u8("pan")?,
+            _pad0: bytes.u8("_pad0")?,
+            attribute: bytes.u16_le("attribute")?,
+            _pad1: bytes.u32_le("_pad1")?,
+            _pad2: bytes.u32_le("_pad2")?,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend([
+            self.tones_number,
+            self.volume,
+            self.priority,
+            self.mode,
+            self.pan,
+            self._pad0,
+        ]);
+        out.extend(self.attribute.to_le_bytes());
+        out.extend(self._pad1.to_le_bytes());
+        out.extend(self._pad2.to_le_bytes());
+    }
+}
+
+#[derive(Debug)]
+pub struct Tone {
+    pub priority: u8,
+    pub reverb_mode: u8,
+    pub volume: u8,
+    pub pan: u8,
+    pub unity_key: u8,
+    pub pitch_tune: u8,
+    pub key_low: u8,
+    pub key_high: u8,
+    pub vibrato_width: u8,
+    pub vibrato_time: u8,
+    pub port_width: u8,
+    pub port_hold: u8,
+    pub pitch_bend_minimum: u8,
+    pub pitch_bend_maximum: u8,
+    pub _pad0: u8,
+    pub _pad1: u8,
+    pub adsr1: u16,
+    pub adsr2: u16,
+    pub parent_program: u16,
+    pub sample_number: u16,
+    pub _pad2: u16,
+    pub _pad3: u16,
+    pub _pad4: u16,
+    pub _pad5: u16,
+}
+
+impl Tone {
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        Ok(Self {
+            priority: bytes.u8("priority")?,
+            reverb_mode: bytes.u8("reverb_mode")?,
+            volume: bytes.u8("volume")?,
+            pan: bytes.u8("pan")?,
+            unity_key: bytes.u8("unity_key")?,
+            pitch_tune: bytes.u8("pitch_tune")?,
+            key_low: bytes.u8("key_low")?,
+            key_high: bytes.u8("key_high")?,
+            vibrato_width: bytes.u8("vibrato_width")?,
+            vibrato_time: bytes.u8("vibrato_time")?,
+            port_width: bytes.u8("port_width")?,
+            port_hold: bytes.u8("port_hold")?,
+            pitch_bend_minimum: bytes.u8("pitch_bend_minimum")?,
+            pitch_bend_maximum: bytes.u8("pitch_bend_maximum")?,
+            _pad0: bytes.u8("_pad0")?,
+            _pad1: bytes.u8("_pad1")?,
+            adsr1: bytes.u16_le("adsr1")?,
+            adsr2: bytes.u16_le("adsr2")?,
+            parent_program: bytes.u16_le("parent_program")?,
+            sample_number: bytes.u16_le("sample_number")?,
+            _pad2: bytes.u16_le("_pad2")?,
+            _pad3: bytes.u16_le("_pad3")?,
+            _pad4: bytes.u16_le("_pad4")?,
+            _pad5: bytes.u16_le("_pad5")?,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend([
+            self.priority,
+            self.reverb_mode,
+            self.volume,
+            self.pan,
+            self.unity_key,
+            self.pitch_tune,
+            self.key_low,
+            self.key_high,
+            self.vibrato_width,
+            self.vibrato_time,
+            self.port_width,
+            self.port_hold,
+            self.pitch_bend_minimum,
+            self.pitch_bend_maximum,
+            self._pad0,
+            self._pad1,
+        ]);
+        for field in [
+            self.adsr1,
+            self.adsr2,
+            self.parent_program,
+            self.sample_number,
+            self._pad2,
+            self._pad3,
+            self._pad4,
+            self._pad5,
+        ] {
+            out.extend(field.to_le_bytes());
+        }
+    }
+}
+
+#[test]
+fn test_file() {
+    let vab = include_bytes!("../tests/test.vab");
+    let vab_file = VabFile::parse(vab).unwrap();
+    assert_eq!(vab_file.header.total_size, vab.len() as u32);
+    assert_eq!(vab_file.tones.len(), 2);
+    assert_eq!(vab_file.programs[1].index, 1);
+}
+
+#[test]
+fn cursor() {
+    let vab = include_bytes!("../tests/test.vab");
+    let vab_file = VabFile::parse(vab).unwrap();
+    let cursor_index = vab_file.vag_ranges[0].start;
+    assert_eq!(cursor_index, 0xe20);
+    assert_eq!(cursor_index, VabFile::header_size(2));
+    assert_eq!(vab.len() - cursor_index, 0xa0b0);
+}
+
+#[test]
+fn header_round_trip() {
+    let vab = include_bytes!("../tests/test.vab");
+    let vab_file = VabFile::parse(vab).unwrap();
+    let mut out = vab.to_vec();
+    vab_file.write_header(&mut out);
+    assert_eq!(out, vab);
+}
+
+#[test]
+fn truncated() {
+    let vab = include_bytes!("../tests/test.vab");
+    assert_eq!(
+        VabFile::parse(&vab[0..0x20]).unwrap_err(),
+        Error::OffsetOutOfRange {
+            field: "total_size",
+            offset: vab.len(),
+            limit: 0x20,
+        }
+    );
+}
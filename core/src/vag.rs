@@ -0,0 +1,273 @@
+//! Sony VAG files, a single stream of 4-bit ADPCM blocks with a big endian header.
+
+use crate::{adpcm, Error, Reader, Result};
+
+pub const MAGIC: [u8; 4] = *b"VAGp";
+/// Magic number of the variant with interleaved channels.
+pub const MAGIC_INTERLEAVED: [u8; 4] = *b"VAGi";
+/// Length of the standard header, the long variant adds another 16 bytes of padding.
+pub const HEADER_SIZE: usize = 48;
+pub const LONG_HEADER_SIZE: usize = HEADER_SIZE + BLOCK_SIZE;
+/// Offset of the data in `VAGi` files, whose header is padded to a sector.
+pub const INTERLEAVED_START: usize = 0x800;
+/// Bytes of a channel before the next one's when the header doesn't say.
+pub const DEFAULT_INTERLEAVE: usize = 0x800;
+/// Length of an ADPCM block, which holds 28 samples.
+pub const BLOCK_SIZE: usize = 16;
+pub const SAMPLES_PER_BLOCK: usize = 28;
+
+/// Flag byte of a block that starts a loop.
+pub const FLAG_LOOP_START: u8 = 6;
+/// Flag byte of a block that ends a loop.
+pub const FLAG_LOOP_END: u8 = 3;
+/// Flag byte of the block marking the end of a sample.
+pub const FLAG_END: u8 = 7;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct VagHeader {
+    #[serde(serialize_with = "serialize_name")]
+    pub magic: [u8; 4],
+    pub version: u32,
+    /// Bytes of a channel before the next one's, only set by `VAGi` files.
+    pub interleave: u32,
+    /// Size of the ADPCM data following the header in bytes.
+    pub data_size: u32,
+    pub sample_rate: u32,
+    /// Number of interleaved channels, only set by some variants and 0 for
+    /// the usual mono files.
+    pub channels: u8,
+    #[serde(serialize_with = "serialize_name")]
+    pub name: [u8; 16],
+}
+
+/// Reports the name as text, up to the first null byte.
+fn serialize_name<S: serde::Serializer, const N: usize>(
+    name: &[u8; N],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let end = name
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(name.len());
+    serializer.serialize_str(&String::from_utf8_lossy(&name[..end]))
+}
+
+impl VagHeader {
+    #[must_use]
+    pub fn new(data_size: u32, sample_rate: u32) -> Self {
+        Self {
+            magic: MAGIC,
+            version: 3,
+            interleave: 0,
+            data_size,
+            sample_rate,
+            channels: 0,
+            name: [0; 16],
+        }
+    }
+
+    pub fn parse(bytes: &mut Reader) -> Result<Self> {
+        let magic = bytes.array("magic")?;
+        if magic != MAGIC && magic != MAGIC_INTERLEAVED {
+            return Err(Error::BadMagic {
+                format: "VAG",
+                offset: 0,
+                expected: MAGIC,
+                found: magic,
+            });
+        }
+        let version = bytes.u32_be("version")?;
+        let interleave = bytes.u32_le("interleave")?;
+        let data_size = bytes.u32_be("data_size")?;
+        let sample_rate = bytes.u32_be("sample_rate")?;
+        bytes.skip("reserved", 10)?;
+        let channels = bytes.u8("channels")?;
+        bytes.skip("reserved", 1)?;
+        let name = bytes.array("name")?;
+
+        Ok(Self {
+            magic,
+            version,
+            interleave,
+            data_size,
+            sample_rate,
+            channels,
+            name,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(
+            [
+                self.magic,
+                self.version.to_be_bytes(),
+                self.interleave.to_le_bytes(),
+                self.data_size.to_be_bytes(),
+                self.sample_rate.to_be_bytes(),
+                [0; 4],
+                [0; 4],
+                [0, 0, self.channels, 0],
+            ]
+            .into_iter()
+            .flatten()
+            .chain(self.name),
+        );
+    }
+}
+
+/// Where the ADPCM blocks of a file start and how its channels are interleaved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Layout {
+    /// Offset of the first block.
+    pub start: usize,
+    pub channels: usize,
+    /// Bytes of a channel before the next one's, 0 for mono files.
+    pub interleave: usize,
+}
+
+impl Layout {
+    /// Finds the layout of a file from its header. `VAGp` files have the long
+    /// header when the data size leaves exactly one block of zeros after the
+    /// standard header.
+    pub fn detect(header: &VagHeader, file: &[u8]) -> Result<Self> {
+        let interleaved = header.magic == MAGIC_INTERLEAVED;
+        let channels = match header.channels {
+            0 | 1 if interleaved => 2,
+            0 => 1,
+            channels => channels as usize,
+        };
+
+        let padding = file.get(HEADER_SIZE..LONG_HEADER_SIZE);
+        let long = file.len().checked_sub(header.data_size as usize) == Some(LONG_HEADER_SIZE)
+            && padding.is_some_and(|padding| padding.iter().all(|byte| *byte == 0));
+        let layout = Self {
+            start: match () {
+                () if interleaved => INTERLEAVED_START,
+                () if long => LONG_HEADER_SIZE,
+                () => HEADER_SIZE,
+            },
+            channels,
+            interleave: match header.interleave as usize {
+                _ if channels == 1 => 0,
+                0 => DEFAULT_INTERLEAVE,
+                interleave => interleave.next_multiple_of(BLOCK_SIZE),
+            },
+        };
+        if layout.start > file.len() {
+            return Err(Error::OffsetOutOfRange {
+                field: "data",
+                offset: layout.start,
+                limit: file.len(),
+            });
+        }
+        Ok(layout)
+    }
+
+    /// The channel of the block at `offset` bytes into the data.
+    #[must_use]
+    pub fn channel(&self, offset: usize) -> usize {
+        match self.interleave {
+            0 => 0,
+            interleave => offset / interleave % self.channels,
+        }
+    }
+
+    /// Offset in the file of a block of a channel.
+    #[must_use]
+    pub fn offset(&self, channel: usize, block: usize) -> usize {
+        let offset = block * BLOCK_SIZE;
+        match self.interleave {
+            0 => self.start + offset,
+            interleave => {
+                let turn = offset / interleave * self.channels + channel;
+                self.start + turn * interleave + offset % interleave
+            }
+        }
+    }
+
+    /// The blocks of each channel of a file.
+    #[must_use]
+    pub fn deinterleave(&self, file: &[u8]) -> Vec<Vec<u8>> {
+        adpcm::deinterleave(&file[self.start..], self.channels, self.interleave)
+    }
+}
+
+/// Removes the loop flag of an ADPCM block and returns true if any change was made.
+pub fn remove_loop(block: &mut [u8]) -> bool {
+    if block.len() > 1 && block[1] != FLAG_END && block[1] != 0 {
+        block[1] = 0;
+        true
+    } else {
+        false
+    }
+}
+
+#[test]
+fn header_round_trip() {
+    let header = VagHeader::new(0x6270, 22050);
+    let mut bytes = vec![];
+    header.write(&mut bytes);
+    assert_eq!(bytes.len(), HEADER_SIZE);
+    assert_eq!(&bytes[0..4], b"VAGp");
+    assert_eq!(VagHeader::parse(&mut Reader::new(&bytes)), Ok(header));
+
+    let stereo = VagHeader {
+        channels: 2,
+        ..VagHeader::new(0x6270, 44100)
+    };
+    let mut bytes = vec![];
+    stereo.write(&mut bytes);
+    assert_eq!(bytes[0x1e], 2);
+    assert_eq!(VagHeader::parse(&mut Reader::new(&bytes)), Ok(stereo));
+}
+
+#[test]
+fn layouts() {
+    let mut file = vec![];
+    VagHeader::new(32, 22050).write(&mut file);
+    file.extend([0; 2 * BLOCK_SIZE]);
+    let header = VagHeader::parse(&mut Reader::new(&file)).unwrap();
+    let layout = Layout::detect(&header, &file).unwrap();
+    assert_eq!((layout.start, layout.channels), (HEADER_SIZE, 1));
+    assert_eq!(layout.channel(BLOCK_SIZE), 0);
+
+    file.extend([0; BLOCK_SIZE]);
+    assert_eq!(
+        Layout::detect(&header, &file).unwrap().start,
+        LONG_HEADER_SIZE
+    );
+    file[HEADER_SIZE] = 0x0c;
+    assert_eq!(Layout::detect(&header, &file).unwrap().start, HEADER_SIZE);
+
+    let stereo = VagHeader {
+        channels: 2,
+        ..header.clone()
+    };
+    let layout = Layout::detect(&stereo, &file).unwrap();
+    assert_eq!(
+        (layout.start, layout.interleave),
+        (HEADER_SIZE, DEFAULT_INTERLEAVE)
+    );
+
+    let interleaved = VagHeader {
+        magic: MAGIC_INTERLEAVED,
+        interleave: 0x10,
+        ..header
+    };
+    let mut bytes = vec![];
+    interleaved.write(&mut bytes);
+    assert_eq!(
+        VagHeader::parse(&mut Reader::new(&bytes)),
+        Ok(interleaved.clone())
+    );
+    assert!(Layout::detect(&interleaved, &bytes).is_err());
+    bytes.resize(INTERLEAVED_START + 4 * BLOCK_SIZE, 0);
+    let layout = Layout::detect(&interleaved, &bytes).unwrap();
+    assert_eq!((layout.start, layout.channels), (INTERLEAVED_START, 2));
+    assert_eq!(
+        (0..4)
+            .map(|block| layout.channel(block * BLOCK_SIZE))
+            .collect::<Vec<_>>(),
+        [0, 1, 0, 1]
+    );
+}
@@ -0,0 +1,243 @@
+//! RIFF WAVE files of 16-bit PCM, with an optional `smpl` chunk holding the
+//! unity note and loop points for samplers.
+
+use crate::{adpcm, Error, Reader, Result};
+
+pub const MAGIC: [u8; 4] = *b"RIFF";
+
+/// The loop and tuning information stored in the `smpl` chunk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sampler {
+    /// MIDI note the sample plays back at its original pitch.
+    pub unity_note: u8,
+    pub loops: Vec<Loop>,
+}
+
+/// A forward loop, with both ends being sample offsets and the end inclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Loop {
+    pub start: u32,
+    pub end: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Wav {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Interleaved samples of every channel.
+    pub samples: Vec<i16>,
+    pub sampler: Option<Sampler>,
+}
+
+/// Reads 16-bit little endian PCM samples, ignoring a trailing odd byte.
+#[must_use]
+pub fn pcm16_samples(body: &[u8]) -> Vec<i16> {
+    body.chunks_exact(2)
+        .map(|x| i16::from_le_bytes([x[0], x[1]]))
+        .collect()
+}
+
+impl Wav {
+    /// Decodes a mono ADPCM body, taking the loop from its block flags.
+    #[must_use]
+    pub fn from_adpcm(body: &[u8], sample_rate: u32, unity_note: u8) -> Self {
+        let decoded = adpcm::decode(body);
+        let loops = match (decoded.loop_start, decoded.loop_end) {
+            (Some(start), Some(end)) if start < end => vec![Loop {
+                start: start as u32,
+                end: end as u32 - 1,
+            }],
+            _ => vec![],
+        };
+
+        Self {
+            sample_rate,
+            channels: 1,
+            samples: decoded.samples,
+            sampler: Some(Sampler { unity_note, loops }),
+        }
+    }
+
+    /// Reads mono 16-bit little endian PCM.
+    #[must_use]
+    pub fn from_pcm16(body: &[u8], sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            channels: 1,
+            samples: pcm16_samples(body),
+            sampler: None,
+        }
+    }
+
+    /// Reads a 16-bit PCM file, keeping the `smpl` chunk if there is one.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut bytes = Reader::new(bytes);
+        bytes.magic("WAV", MAGIC)?;
+        bytes.skip("riff_size", 4)?;
+        bytes.magic("WAV", *b"WAVE")?;
+
+        let mut format = None;
+        let mut data = None;
+        let mut sampler = None;
+        while bytes.remaining().len() >= 8 {
+            let id = bytes.array::<4>("chunk_id")?;
+            let size = bytes.u32_le("chunk_size")? as usize;
+            let mut chunk = Reader::new(bytes.bytes("chunk", size)?);
+            // Chunks are padded to an even length
+            if size % 2 == 1 && !bytes.remaining().is_empty() {
+                bytes.skip("chunk_padding", 1)?;
+            }
+
+            match &id {
+                b"fmt " => {
+                    let codec = chunk.u16_le("codec")?;
+                    let channels = chunk.u16_le("channels")?;
+                    let sample_rate = chunk.u32_le("sample_rate")?;
+                    chunk.skip("byte_rate", 4)?;
+                    chunk.skip("block_align", 2)?;
+                    let bits_per_sample = chunk.u16_le("bits_per_sample")?;
+                    if codec != 1 || bits_per_sample != 16 {
+                        return Err(Error::Unsupported {
+                            format: "WAV",
+                            feature: "only 16-bit PCM is handled",
+                        });
+                    }
+                    format = Some((channels, sample_rate));
+                }
+                b"data" => data = Some(chunk.remaining()),
+                b"smpl" => {
+                    chunk.skip("smpl_header", 12)?;
+                    let unity_note = chunk.u32_le("unity_note")?;
+                    chunk.skip("smpl_tuning", 12)?;
+                    let num_loops = chunk.u32_le("num_loops")?;
+                    chunk.skip("sampler_data", 4)?;
+                    let loops = (0..num_loops)
+                        .map(|_| {
+                            chunk.skip("loop_header", 8)?;
+                            let start = chunk.u32_le("loop_start")?;
+                            let end = chunk.u32_le("loop_end")?;
+                            chunk.skip("loop_footer", 8)?;
+                            Ok(Loop { start, end })
+                        })
+                        .collect::<Result<_>>()?;
+                    sampler = Some(Sampler {
+                        unity_note: unity_note.min(127) as u8,
+                        loops,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let (channels, sample_rate) = format.ok_or(Error::MissingChunk {
+            format: "WAV",
+            chunk: *b"fmt ",
+        })?;
+        let data = data.ok_or(Error::MissingChunk {
+            format: "WAV",
+            chunk: *b"data",
+        })?;
+
+        Ok(Self {
+            sample_rate,
+            channels,
+            samples: pcm16_samples(data),
+            sampler,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        let data_size = 2 * self.samples.len() as u32;
+        let smpl_size = self
+            .sampler
+            .as_ref()
+            .map_or(0, |sampler| 8 + 36 + 24 * sampler.loops.len() as u32);
+        let block_align = 2 * self.channels;
+
+        out.extend(b"RIFF");
+        out.extend((4 + 8 + 16 + 8 + data_size + smpl_size).to_le_bytes());
+        out.extend(b"WAVE");
+
+        out.extend(b"fmt ");
+        out.extend(16u32.to_le_bytes());
+        out.extend(1u16.to_le_bytes());
+        out.extend(self.channels.to_le_bytes());
+        out.extend(self.sample_rate.to_le_bytes());
+        out.extend((self.sample_rate * block_align as u32).to_le_bytes());
+        out.extend(block_align.to_le_bytes());
+        out.extend(16u16.to_le_bytes());
+
+        out.extend(b"data");
+        out.extend(data_size.to_le_bytes());
+        for sample in &self.samples {
+            out.extend(sample.to_le_bytes());
+        }
+
+        if let Some(sampler) = &self.sampler {
+            out.extend(b"smpl");
+            out.extend((smpl_size - 8).to_le_bytes());
+            for field in [
+                0,
+                0,
+                1_000_000_000 / self.sample_rate.max(1),
+                sampler.unity_note as u32,
+                0,
+                0,
+                0,
+                sampler.loops.len() as u32,
+                0,
+            ] {
+                out.extend(field.to_le_bytes());
+            }
+            for (i, sample_loop) in sampler.loops.iter().enumerate() {
+                for field in [i as u32, 0, sample_loop.start, sample_loop.end, 0, 0] {
+                    out.extend(field.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn layout() {
+    let mut body = [0; 3 * crate::vag::BLOCK_SIZE];
+    body[crate::vag::BLOCK_SIZE + 1] = crate::vag::FLAG_LOOP_START;
+    body[2 * crate::vag::BLOCK_SIZE + 1] = crate::vag::FLAG_LOOP_END;
+    let wav = Wav::from_adpcm(&body, 22050, 60);
+    assert_eq!(
+        wav.sampler.as_ref().unwrap().loops,
+        [Loop { start: 28, end: 83 }]
+    );
+
+    let mut bytes = vec![];
+    wav.write(&mut bytes);
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(
+        u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize,
+        bytes.len() - 8
+    );
+    assert_eq!(&bytes[36..40], b"data");
+    let smpl = 44 + 2 * 84;
+    assert_eq!(&bytes[smpl..smpl + 4], b"smpl");
+    assert_eq!(bytes[smpl + 20], 60);
+    assert_eq!(bytes.len(), smpl + 8 + 36 + 24);
+}
+
+#[test]
+fn round_trip() {
+    let wav = Wav {
+        sample_rate: 44100,
+        channels: 1,
+        samples: vec![0, 1, -1, i16::MAX, i16::MIN],
+        sampler: Some(Sampler {
+            unity_note: 72,
+            loops: vec![Loop { start: 1, end: 3 }],
+        }),
+    };
+    let mut bytes = vec![];
+    wav.write(&mut bytes);
+    assert_eq!(Wav::parse(&bytes), Ok(wav));
+
+    bytes[20] = 3;
+    assert!(matches!(Wav::parse(&bytes), Err(Error::Unsupported { .. })));
+}
@@ -0,0 +1,182 @@
+use std::{fs::File, io::Write, path::PathBuf};
+
+use core::{
+    batch::JobArgs,
+    clap::{self, Parser},
+    input::InputArgs,
+    log::{error, info},
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// The `mul` file to read from.
+    input: PathBuf,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+
+    let output = Output::new(&args.output, &args.input);
+
+    let files = core::get_files(&args.input, &args.files, &["mul"]);
+    core::batch::run(files, args.batch.jobs, |file_path| {
+        info!("{file_path:?}");
+        let mul_file = std::fs::read(file_path).unwrap();
+
+        let sample_rate = u32::from_le_bytes([mul_file[0], mul_file[1], mul_file[2], mul_file[3]]);
+        let channels = u32::from_le_bytes([mul_file[12], mul_file[13], mul_file[14], mul_file[15]]);
+
+        let mut body = &mul_file[0x800..];
+        let mut audio_slices = (0..channels).map(|_| vec![]).collect::<Vec<_>>();
+        let mut data_slices = vec![];
+        let mut audio_chunks = 0;
+        let mut padding_chunks = 0;
+        while let Some(current_chunk) = Chunk::parse(&mut body) {
+            match current_chunk {
+                Chunk::Audio { size } => {
+                    audio_chunks += 1;
+                    let split_size = size / channels;
+                    let bytes = get_bytes(&mut body, size as usize);
+                    for (i, slice) in bytes.chunks(split_size as usize).enumerate() {
+                        audio_slices[i].push(slice);
+                    }
+                }
+                Chunk::Data { size } => data_slices.push(get_bytes(&mut body, size as usize)),
+                Chunk::Padding { size } => {
+                    padding_chunks += 1;
+                    get_bytes(&mut body, size as usize);
+                }
+            }
+        }
+
+        let project_name = file_path.file_stem().unwrap().to_string_lossy();
+        let Some(output_dir) = output.dir(&file_path.with_extension("")) else {
+            return;
+        };
+        info!("Writing to {output_dir:?}");
+        if !audio_slices.is_empty() {
+            for (i, slices) in audio_slices.iter().enumerate() {
+                let out_path = output_dir.join(format!("{project_name}_audio_ch{i}.bin"));
+                let mut out = File::create(&out_path).unwrap();
+                report::written(&out_path);
+                out.write_all(
+                    &slices
+                        .iter()
+                        .copied()
+                        .flatten()
+                        .copied()
+                        .collect::<Vec<_>>(),
+                )
+                .unwrap();
+            }
+        }
+        if !data_slices.is_empty() {
+            let out_path = output_dir.join(format!("{project_name}_data.bin"));
+            let mut out = File::create(&out_path).unwrap();
+            report::written(&out_path);
+            out.write_all(
+                &data_slices
+                    .iter()
+                    .flat_map(|data| data.iter())
+                    .copied()
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+        }
+
+        let rate_path = output_dir.join(format!("{project_name}_rate.txt"));
+        let mut rate_file = File::create(&rate_path).unwrap();
+        report::written(&rate_path);
+        for i in 0..channels {
+            write!(
+                &mut rate_file,
+                "{project_name}_audio_ch{i}.bin 1 {sample_rate} 0 16\r\n",
+            )
+            .unwrap();
+        }
+
+        report::set("sample_rate", sample_rate);
+        report::set("channels", channels);
+        report::set("audio_chunks", audio_chunks);
+        report::set("data_chunks", data_slices.len());
+        report::set("padding_chunks", padding_chunks);
+
+        info!("Audio channels: {channels}");
+        info!("Audio sample rate: {sample_rate}");
+        info!(
+            "Total chunks: {}",
+            data_slices.len() + audio_chunks + padding_chunks
+        );
+        info!("Data chunks: {}", data_slices.len());
+        info!("Audio chunks: {audio_chunks}");
+        info!("Padding chunks: {padding_chunks}");
+    });
+}
+
+enum Chunk {
+    Audio { size: u32 },
+    Data { size: u32 },
+    Padding { size: u32 },
+}
+
+impl Chunk {
+    fn parse(bytes: &mut &[u8]) -> Option<Self> {
+        let variant = parse_u32(bytes)?;
+        match variant {
+            0 => {
+                let audio = Self::Audio {
+                    size: parse_u32(bytes)? - 16,
+                };
+                get_bytes(bytes, 8 + 16);
+                Some(audio)
+            }
+            1 => {
+                let data = Self::Data {
+                    size: parse_u32(bytes)?,
+                };
+                get_bytes(bytes, 8);
+                Some(data)
+            }
+            2 => {
+                let data = Self::Padding {
+                    size: parse_u32(bytes)?,
+                };
+                get_bytes(bytes, 8);
+                Some(data)
+            }
+            _ => {
+                error!("Unsupported variant: {variant}");
+                None
+            }
+        }
+    }
+}
+
+fn parse_u32(bytes: &mut &[u8]) -> Option<u32> {
+    let data = Some(u32::from_le_bytes([
+        bytes.first().copied()?,
+        bytes.get(1).copied()?,
+        bytes.get(2).copied()?,
+        bytes.get(3).copied()?,
+    ]));
+    *bytes = &bytes[4..];
+    data
+}
+
+fn get_bytes<'a>(bytes: &mut &'a [u8], count: usize) -> &'a [u8] {
+    let data = &bytes[0..count];
+    *bytes = &bytes[count..];
+    data
+}
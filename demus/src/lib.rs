@@ -0,0 +1,874 @@
+use std::{
+    fs::File,
+    io::Write,
+    num::NonZeroUsize,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use core::{
+    adpcm,
+    ads::{self, AdsHeader},
+    clap::{self, Parser},
+    dls::{self, Articulation, Dls},
+    log::{debug, error, info, trace},
+    mus::{name_to_str, MusFile, Platform, ProgramZone, WaveEntry, HEADER_VERSION_1_8},
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    serde_json::json,
+    sf2::{self, Generator, SoundFont},
+    wav::{self, Loop, Sampler, Wav},
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// `mus` file to read
+    mus_path: PathBuf,
+    /// `sam` file to read
+    sam_path: PathBuf,
+    /// Tells program to use PC format. This is the default.
+    #[clap(long, short)]
+    pc: bool,
+    /// Tells program to use console format.
+    #[clap(long, short)]
+    console: bool,
+    #[clap(flatten)]
+    output: OutputArgs,
+    /// Write the samples as WAV files, with the loop points and root key in a `smpl` chunk.
+    #[clap(long)]
+    wav: bool,
+    /// Also write the bank as a SoundFont, next to the SF2Comp text file.
+    #[clap(long)]
+    sf2: bool,
+    /// Also write the bank as DLS.
+    #[clap(long)]
+    dls: bool,
+    /// Which DLS level to write, defaults to level 2.
+    #[clap(long, value_enum)]
+    dls_level: Option<dls::Level>,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+fn secs_to_timecent(seconds: f32) -> i32 {
+    (1200.0 * f32::log2(seconds.max(0.001))) as i32
+}
+
+fn semitone_tuning(note: i32) -> i32 {
+    note / 256
+}
+
+fn bytes_to_cents_rescale(note: i32) -> i32 {
+    note * 100 / 256
+}
+
+fn cents_tuning(note: i32) -> i32 {
+    (note % 256) * 100 / 256
+}
+
+fn pan_convert(pan: f32) -> i32 {
+    (pan * 1000.0 - 500.0) as i32
+}
+
+/// Decodes a wave, taking the loop from the MUS entry rather than the ADPCM flags.
+fn wav_from_wave(body: &[u8], wave_entry: &WaveEntry, platform: Platform, unity_note: i32) -> Wav {
+    let loops = wave_loop(wave_entry)
+        .map(|range| Loop {
+            start: range.start,
+            end: range.end - 1,
+        })
+        .into_iter()
+        .collect();
+
+    Wav {
+        sample_rate: wave_entry.sample_rate as u32,
+        channels: 1,
+        samples: decode_wave(body, platform),
+        sampler: Some(Sampler {
+            unity_note: unity_note.clamp(0, 127) as u8,
+            loops,
+        }),
+    }
+}
+
+/// Decodes a wave the same way for the WAV files, the SoundFont and the DLS.
+fn decode_wave(body: &[u8], platform: Platform) -> Vec<i16> {
+    match platform {
+        Platform::PC => wav::pcm16_samples(body),
+        Platform::Console => adpcm::decode(body).samples,
+    }
+}
+
+/// The loop of a wave, with the end exclusive, if the MUS entry has a valid one.
+fn wave_loop(wave_entry: &WaveEntry) -> Option<Range<u32>> {
+    (wave_entry.loop_info != 0 && (0..wave_entry.loop_end).contains(&wave_entry.loop_begin))
+        .then_some(wave_entry.loop_begin as u32..wave_entry.loop_end as u32)
+}
+
+/// Builds a DLS bank with an instrument for each preset.
+///
+/// DLS has no layers of instruments, so the program zones of every preset
+/// zone are flattened into regions covering the overlap of both ranges.
+fn dls_from_mus(
+    mus: &MusFile,
+    waves: &[Range<usize>],
+    sam_file: &[u8],
+    platform: Platform,
+    name: String,
+) -> Dls {
+    let samples = waves
+        .iter()
+        .zip(&mus.wave_entries)
+        .map(|(wave, wave_entry)| dls::Sample {
+            name: name_to_str(&wave_entry.name),
+            samples: decode_wave(&sam_file[wave.clone()], platform),
+            sample_rate: wave_entry.sample_rate as u32,
+            loop_range: wave_loop(wave_entry),
+        })
+        .collect::<Vec<_>>();
+
+    let has_velocity = mus.header.version_number > HEADER_VERSION_1_8;
+    let intersect = |a: (u8, u8), b: (u8, u8)| {
+        let range = (a.0.max(b.0), a.1.min(b.1));
+        (range.0 <= range.1).then_some(range)
+    };
+    let instruments = mus
+        .preset_entries
+        .iter()
+        .zip(&mus.preset_zones)
+        .map(|(preset_entry, preset_zones)| {
+            let mut regions = vec![];
+            for preset_zone in preset_zones {
+                for program_zone in &mus.program_zones[preset_zone.program_index as usize] {
+                    let velocity_range = if has_velocity {
+                        intersect(
+                            (preset_zone.velocity_low, preset_zone.velocity_high),
+                            (program_zone.velocity_low, program_zone.velocity_high),
+                        )
+                    } else {
+                        Some((0, 127))
+                    };
+                    let (Some(key_range), Some(velocity_range)) = (
+                        intersect(
+                            (preset_zone.note_low, preset_zone.note_high),
+                            (program_zone.note_low, program_zone.note_high),
+                        ),
+                        velocity_range,
+                    ) else {
+                        continue;
+                    };
+
+                    let wave_entry = &mus.wave_entries[program_zone.wave_index as usize];
+                    let unity_note = if program_zone.root_key == -1 {
+                        semitone_tuning(wave_entry.original_pitch)
+                    } else {
+                        program_zone.root_key
+                    };
+                    let fine_tune = semitone_tuning(program_zone.pitch_finetuning) * 100
+                        + cents_tuning(program_zone.pitch_finetuning)
+                        + cents_tuning(wave_entry.original_pitch);
+                    let volume_env = &program_zone.volume_env;
+                    regions.push(dls::Region {
+                        key_range,
+                        velocity_range,
+                        sample: program_zone.wave_index as u32,
+                        unity_note: unity_note.clamp(0, 127) as u8,
+                        fine_tune: fine_tune as i16,
+                        attenuation: (10.0 * program_zone.volume_env_atten) as i32,
+                        looped: wave_entry.loop_info != 0,
+                        articulation: Articulation {
+                            attack: volume_env.attack,
+                            decay: volume_env.decay,
+                            // The same attenuation as the SoundFont's sustain
+                            sustain_level: 10f32.powf(-(100.0 - volume_env.sustain) / 20.0),
+                            release: volume_env.release,
+                            pan: pan_convert(program_zone.pan_position) as f32 / 500.0,
+                        },
+                    });
+                }
+            }
+
+            dls::Instrument {
+                name: name_to_str(&preset_entry.name),
+                bank: preset_entry.midi_bank_number as u16,
+                program: preset_entry.midi_preset_number as u8,
+                regions,
+            }
+        })
+        .collect();
+
+    Dls {
+        name,
+        samples,
+        instruments,
+    }
+}
+
+/// The generators of an instrument zone, shared by the SF2Comp text file and
+/// the SoundFont so that both describe the same bank. Ranges are packed like
+/// SoundFont amounts, with the low end in the low byte.
+fn zone_generators(mus: &MusFile, program_zone: &ProgramZone) -> Vec<(Generator, i32)> {
+    let volume_env = &program_zone.volume_env;
+    let range = |low: u8, high: u8| i16::from_le_bytes([low, high]) as i32;
+    let mut generators = vec![
+        (
+            Generator::CoarseTune,
+            semitone_tuning(program_zone.pitch_finetuning),
+        ),
+        (
+            Generator::FineTune,
+            cents_tuning(program_zone.pitch_finetuning),
+        ),
+        (Generator::ReverbEffectsSend, program_zone.reverb * 10),
+        (Generator::Pan, pan_convert(program_zone.pan_position)),
+        (
+            Generator::KeynumToVolEnvHold,
+            bytes_to_cents_rescale(program_zone.keynum_hold),
+        ),
+        (
+            Generator::KeynumToVolEnvDecay,
+            bytes_to_cents_rescale(program_zone.keynum_decay),
+        ),
+        (Generator::AttackVolEnv, secs_to_timecent(volume_env.attack)),
+        (Generator::HoldVolEnv, secs_to_timecent(volume_env.hold)),
+        (Generator::DecayVolEnv, secs_to_timecent(volume_env.decay)),
+        (
+            Generator::SustainVolEnv,
+            ((100.0 - volume_env.sustain) * 10.0) as i32,
+        ),
+        (
+            Generator::ReleaseVolEnv,
+            secs_to_timecent(volume_env.release),
+        ),
+        (Generator::DelayVolEnv, secs_to_timecent(volume_env.delay)),
+    ];
+    if let Some(modul_env) = &program_zone.modul_env {
+        generators.push((Generator::DelayModEnv, secs_to_timecent(modul_env.delay)));
+    }
+    generators.extend([
+        (
+            Generator::InitialAttenuation,
+            (10.0 * program_zone.volume_env_atten) as i32,
+        ),
+        (
+            Generator::DelayVibLfo,
+            secs_to_timecent(program_zone.vib_delay),
+        ),
+        (
+            Generator::FreqVibLfo,
+            secs_to_timecent(program_zone.vib_frequency / 8.176),
+        ),
+        (
+            Generator::VibLfoToPitch,
+            bytes_to_cents_rescale(program_zone.vib_to_pitch as i32),
+        ),
+        (
+            Generator::KeyRange,
+            range(program_zone.note_low, program_zone.note_high),
+        ),
+    ]);
+    if mus.header.version_number > HEADER_VERSION_1_8 {
+        generators.push((
+            Generator::VelRange,
+            range(program_zone.velocity_low, program_zone.velocity_high),
+        ));
+    }
+    if let Some(modul_env) = &program_zone.modul_env {
+        generators.extend([
+            (Generator::AttackModEnv, secs_to_timecent(modul_env.attack)),
+            (Generator::HoldModEnv, secs_to_timecent(modul_env.hold)),
+            (Generator::DecayModEnv, secs_to_timecent(modul_env.decay)),
+            (Generator::SustainModEnv, (modul_env.sustain * 10.0) as i32),
+            (
+                Generator::ReleaseModEnv,
+                secs_to_timecent(modul_env.release),
+            ),
+        ]);
+    }
+    if let Some(modul_env_to_pitch) = program_zone.modul_env_to_pitch {
+        generators.push((
+            Generator::ModEnvToPitch,
+            bytes_to_cents_rescale(modul_env_to_pitch as i32),
+        ));
+    }
+    if program_zone.root_key != -1 {
+        generators.push((Generator::OverridingRootKey, program_zone.root_key));
+    }
+    generators.push((
+        Generator::SampleModes,
+        mus.wave_entries[program_zone.wave_index as usize].loop_info,
+    ));
+    generators
+}
+
+/// The lines of the SF2Comp text file setting a generator of an instrument zone.
+fn sf2comp_generator(generator: Generator, amount: i32) -> String {
+    let name = match generator {
+        Generator::KeyRange | Generator::VelRange => {
+            let [low, high] = (amount as i16).to_le_bytes();
+            let name = match generator {
+                Generator::KeyRange => "Key",
+                _ => "Velocity",
+            };
+            return format!("            Z_Low{name}={low}\r\n            Z_High{name}={high}\r\n");
+        }
+        Generator::VibLfoToPitch => "vibLfoToPitch",
+        Generator::ModEnvToPitch => "modEnvToPitch",
+        Generator::ReverbEffectsSend => "reverbEffectsSend",
+        Generator::Pan => "pan",
+        Generator::DelayVibLfo => "delayVibLFO",
+        Generator::FreqVibLfo => "freqVibLFO",
+        Generator::DelayModEnv => "delayModEnv",
+        Generator::AttackModEnv => "attackModEnv",
+        Generator::HoldModEnv => "holdModEnv",
+        Generator::DecayModEnv => "decayModEnv",
+        Generator::SustainModEnv => "sustainModEnv",
+        Generator::ReleaseModEnv => "releaseModEnv",
+        Generator::DelayVolEnv => "delayVolEnv",
+        Generator::AttackVolEnv => "attackVolEnv",
+        Generator::HoldVolEnv => "holdVolEnv",
+        Generator::DecayVolEnv => "decayVolEnv",
+        Generator::SustainVolEnv => "sustainVolEnv",
+        Generator::ReleaseVolEnv => "releaseVolEnv",
+        Generator::KeynumToVolEnvHold => "keynumToVolEnvHold",
+        Generator::KeynumToVolEnvDecay => "keynumToVolEnvDecay",
+        Generator::InitialAttenuation => "initialAttenuation",
+        Generator::CoarseTune => "coarseTune",
+        Generator::FineTune => "fineTune",
+        Generator::SampleModes => "sampleModes",
+        Generator::OverridingRootKey => "overridingRootKey",
+    };
+    format!("            Z_{name}={amount}\r\n")
+}
+
+/// Builds the same bank that SF2Comp would compile from the text file.
+fn soundfont_from_mus(
+    mus: &MusFile,
+    waves: &[Range<usize>],
+    sam_file: &[u8],
+    platform: Platform,
+    name: String,
+) -> SoundFont {
+    let samples = waves
+        .iter()
+        .zip(&mus.wave_entries)
+        .map(|(wave, wave_entry)| {
+            let body = &sam_file[wave.clone()];
+            sf2::Sample {
+                name: name_to_str(&wave_entry.name),
+                samples: decode_wave(body, platform),
+                sample_rate: wave_entry.sample_rate as u32,
+                loop_range: wave_loop(wave_entry),
+                original_key: semitone_tuning(wave_entry.original_pitch).clamp(0, 127) as u8,
+                correction: cents_tuning(wave_entry.original_pitch) as i8,
+            }
+        })
+        .collect();
+
+    let has_velocity = mus.header.version_number > HEADER_VERSION_1_8;
+    let instruments = mus
+        .program_entries
+        .iter()
+        .zip(&mus.program_zones)
+        .map(|(program_entry, program_zones)| sf2::Instrument {
+            name: name_to_str(&program_entry.name),
+            zones: program_zones
+                .iter()
+                .map(|program_zone| {
+                    let mut zone = sf2::Zone::new(program_zone.wave_index as u16);
+                    for (generator, amount) in zone_generators(mus, program_zone) {
+                        zone.push(generator, amount);
+                    }
+                    zone
+                })
+                .collect(),
+        })
+        .collect();
+
+    let presets = mus
+        .preset_entries
+        .iter()
+        .zip(&mus.preset_zones)
+        .map(|(preset_entry, preset_zones)| sf2::Preset {
+            name: name_to_str(&preset_entry.name),
+            bank: preset_entry.midi_bank_number as u16,
+            program: preset_entry.midi_preset_number as u16,
+            zones: preset_zones
+                .iter()
+                .map(|preset_zone| {
+                    let mut zone = sf2::Zone::new(preset_zone.program_index as u16);
+                    zone.push_range(
+                        Generator::KeyRange,
+                        preset_zone.note_low,
+                        preset_zone.note_high,
+                    );
+                    if has_velocity {
+                        zone.push_range(
+                            Generator::VelRange,
+                            preset_zone.velocity_low,
+                            preset_zone.velocity_high,
+                        );
+                    }
+                    zone
+                })
+                .collect(),
+        })
+        .collect();
+
+    SoundFont {
+        name,
+        engine: "EMU8000".to_owned(),
+        software: "Demus".to_owned(),
+        samples,
+        instruments,
+        presets,
+    }
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+
+    let output = Output::new(&args.output, &args.mus_path);
+    let summary = core::batch::run(vec![args.mus_path.clone()], NonZeroUsize::MIN, |_| {
+        extract(&args, &output);
+    });
+    if !summary.failed.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Splits one pair of MUS and SAM files with the default options.
+pub fn convert_file(mus_path: &Path, sam_path: &Path, output: &Output) {
+    let args = Args {
+        mus_path: mus_path.to_owned(),
+        sam_path: sam_path.to_owned(),
+        pc: false,
+        console: false,
+        output: OutputArgs::default(),
+        wav: false,
+        sf2: false,
+        dls: false,
+        dls_level: None,
+        report: ReportArgs::default(),
+    };
+    extract(&args, output);
+}
+
+/// Splits the MUS and SAM files into sequences, samples and the bank description.
+fn extract(args: &Args, output: &Output) {
+    let platform = if args.console {
+        Platform::Console
+    } else {
+        Platform::PC
+    };
+
+    let mus_file = match std::fs::read(&args.mus_path) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Unable to load MUS file {:?}: {e}", &args.mus_path);
+            return;
+        }
+    };
+    let mut sam_file = match std::fs::read(&args.sam_path) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Unable to load SAM file {:?}: {e}", &args.sam_path);
+            return;
+        }
+    };
+
+    let mus = match MusFile::parse(&mus_file) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Unable to parse MUS file {:?}: {e}", &args.mus_path);
+            return;
+        }
+    };
+    let waves = match mus.wave_ranges(&sam_file) {
+        Ok(value) => value,
+        Err(e) => {
+            error!(
+                "Unable to find samples in SAM file {:?}: {e}",
+                &args.sam_path
+            );
+            return;
+        }
+    };
+    let MusFile {
+        header,
+        msq_tables,
+        layers,
+        wave_entries,
+        program_entries,
+        program_zones,
+        preset_entries,
+        preset_zones,
+        ..
+    } = &mus;
+    report::set("header", header);
+    debug!("Header: {header:?}");
+    debug!("MSQ tables: {msq_tables:#?}");
+    debug!("Layers: {layers:?}");
+    debug!("Wave entries: {wave_entries:#?}");
+    debug!("Program entries: {program_entries:#?}");
+    debug!("Program zones: {program_zones:#?}");
+    debug!("Preset entries: {preset_entries:#?}");
+    debug!("Preset zones: {preset_zones:#?}");
+
+    // Convert the ranges into actual slices
+    let sequences = mus
+        .sequences
+        .iter()
+        .map(|range| &mus_file[range.clone()])
+        .collect::<Vec<_>>();
+    trace!("Sequences: {sequences:?}");
+
+    let Some(output_dir) = output.dir(&args.mus_path.with_extension("")) else {
+        return;
+    };
+
+    if header.num_labels != 0 {
+        let labels_path = output_dir
+            .join(args.mus_path.with_extension("lbl").file_name().unwrap())
+            .with_extension("lbl");
+        let mut labels_file = match File::create(&labels_path) {
+            Ok(value) => value,
+            Err(e) => {
+                error!("Unable to create labels file at {labels_path:?}: {e}");
+                return;
+            }
+        };
+        labels_file.write_all(&[0x4C, 0x42, 0x4C, 0x61]).unwrap();
+        labels_file
+            .write_all(&header.num_labels.to_le_bytes())
+            .unwrap();
+        for label in &mus.labels {
+            labels_file.write_all(&label.to_le_bytes()).unwrap();
+        }
+        report::written(&labels_path);
+    }
+
+    let sequences_dir = output_dir.join("sequences");
+    let samples_dir = output_dir.join("samples");
+    if let Err(e) = std::fs::create_dir_all(&sequences_dir) {
+        error!("Unable to create sequences directory {sequences_dir:?}: {e}");
+        return;
+    }
+    for (i, sequence) in sequences.into_iter().enumerate() {
+        let path = sequences_dir.join(format!(
+            "{}_{:04}.msq",
+            args.mus_path.file_stem().unwrap().to_string_lossy(),
+            i,
+        ));
+        let mut file = match File::create(&path) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("Unable to create sequence file {path:?}: {e}");
+                continue;
+            }
+        };
+        file.write_all(sequence).unwrap();
+        report::written(&path);
+        report::push("sequences", json!({ "size": sequence.len() }));
+    }
+
+    if platform == Platform::Console {
+        for wave_range in waves.iter().filter(|range| range.len() >= 16) {
+            let check_index = wave_range.end - 16;
+            if sam_file[check_index..check_index + 16]
+                == [
+                    0x07, 0x00, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77,
+                    0x77, 0x77, 0x77,
+                ]
+            {
+                sam_file[check_index + 1] = 0x07;
+            }
+        }
+    }
+
+    if let Err(e) = std::fs::create_dir_all(&samples_dir) {
+        error!("Unable to create samples directory {samples_dir:?}: {e}");
+        return;
+    }
+    for (i, (wave, wave_entry)) in waves.iter().zip(wave_entries).enumerate() {
+        let path = samples_dir.join(format!(
+            "{}.{}",
+            name_to_str(&wave_entry.name),
+            if args.wav { "wav" } else { "ads" }
+        ));
+        let mut sample_file = match File::create(&path) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("Unable to create wave file {path:?}: {e}");
+                continue;
+            }
+        };
+        report::written(&path);
+        report::push(
+            "samples",
+            json!({
+                "name": name_to_str(&wave_entry.name),
+                "size": wave.len(),
+                "sample_rate": wave_entry.sample_rate,
+                "loop_start": (wave_entry.loop_info != 0).then_some(wave_entry.loop_begin),
+                "loop_end": (wave_entry.loop_info != 0).then_some(wave_entry.loop_end),
+            }),
+        );
+
+        if args.wav {
+            let unity_note = program_zones
+                .iter()
+                .flatten()
+                .find(|zone| zone.wave_index as usize == i && zone.root_key != -1)
+                .map_or(semitone_tuning(wave_entry.original_pitch), |zone| {
+                    zone.root_key
+                });
+            let mut bytes = vec![];
+            wav_from_wave(&sam_file[wave.clone()], wave_entry, platform, unity_note)
+                .write(&mut bytes);
+            sample_file.write_all(&bytes).unwrap();
+            continue;
+        }
+
+        let mut header = vec![];
+        AdsHeader {
+            codec: match platform {
+                Platform::PC => ads::CODEC_PCM16,
+                Platform::Console => ads::CODEC_VAG,
+            },
+            sample_rate: wave_entry.sample_rate as u32,
+            channels: 1,
+            interleave: 0,
+            loop_start: None,
+            loop_end: None,
+            body_size: wave_entry.size as u32,
+        }
+        .write(&mut header);
+        sample_file.write_all(&header).unwrap();
+        sample_file.write_all(&sam_file[wave.clone()]).unwrap();
+    }
+
+    let smp_loop_info_path = output_dir.join(format!(
+        "{}_smploopinfo.txt",
+        args.mus_path
+            .with_extension("")
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+    ));
+    let mut smp_loop_info = match File::create(&smp_loop_info_path) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Unable to create loop info file {smp_loop_info_path:?}: {e}");
+            return;
+        }
+    };
+    report::written(&smp_loop_info_path);
+    for entry in wave_entries {
+        if entry.loop_info != 0 {
+            smp_loop_info
+                .write_all(
+                    format!(
+                        "{} {} {}.wav\r\n",
+                        entry.loop_begin,
+                        entry.loop_end - 1,
+                        name_to_str(&entry.name),
+                    )
+                    .as_bytes(),
+                )
+                .unwrap();
+        }
+    }
+
+    let info_path = output_dir.join(format!(
+        "{}.txt",
+        args.mus_path
+            .with_extension("")
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+    ));
+    let mut info_file = match File::create(&info_path) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Unable to create info file {info_path:?}: {e}");
+            return;
+        }
+    };
+    report::written(&info_path);
+    write!(&mut info_file, "[Samples]\r\n").unwrap();
+    // TODO Make more compact with a single statement
+    for wave_entry in wave_entries {
+        write!(
+            &mut info_file,
+            "\r\n    SampleName={}\r\n",
+            name_to_str(&wave_entry.name),
+        )
+        .unwrap();
+        write!(
+            &mut info_file,
+            "        SampleRate={}\r\n",
+            wave_entry.sample_rate,
+        )
+        .unwrap();
+        write!(
+            &mut info_file,
+            "        Key={}\r\n",
+            semitone_tuning(wave_entry.original_pitch),
+        )
+        .unwrap();
+        write!(
+            &mut info_file,
+            "        FineTune={}\r\n",
+            cents_tuning(wave_entry.original_pitch)
+        )
+        .unwrap();
+        write!(&mut info_file, "        Type=1\r\n").unwrap();
+    }
+
+    write!(&mut info_file, "\r\n\r\n[Instruments]\r\n").unwrap();
+    for (i, program_entry) in program_entries.iter().enumerate() {
+        write!(
+            &mut info_file,
+            "\r\n    InstrumentName={}\r\n",
+            name_to_str(&program_entry.name)
+        )
+        .unwrap();
+        for program_zone in &program_zones[i] {
+            write!(
+                &mut info_file,
+                "\r\n        Sample={}\r\n",
+                name_to_str(&wave_entries[program_zone.wave_index as usize].name)
+            )
+            .unwrap();
+            for (generator, amount) in zone_generators(&mus, program_zone) {
+                write!(&mut info_file, "{}", sf2comp_generator(generator, amount)).unwrap();
+            }
+        }
+
+        write!(&mut info_file, "\r\n        GlobalZone\r\n\r\n").unwrap();
+    }
+
+    write!(&mut info_file, "\r\n[Presets]").unwrap();
+    for (i, preset_entry) in preset_entries.iter().enumerate() {
+        write!(
+            &mut info_file,
+            "\r\n\r\n    PresetName={}\r\n",
+            name_to_str(&preset_entry.name),
+        )
+        .unwrap();
+        write!(
+            &mut info_file,
+            "        Bank={}\r\n",
+            preset_entry.midi_bank_number,
+        )
+        .unwrap();
+        write!(
+            &mut info_file,
+            "        Program={}\r\n\r\n",
+            preset_entry.midi_preset_number,
+        )
+        .unwrap();
+
+        for zone in &preset_zones[i] {
+            write!(
+                &mut info_file,
+                "        Instrument={}\r\n",
+                name_to_str(&program_entries[zone.program_index as usize].name),
+            )
+            .unwrap();
+            write!(&mut info_file, "            L_LowKey={}\r\n", zone.note_low).unwrap();
+            write!(
+                &mut info_file,
+                "            L_HighKey={}\r\n",
+                zone.note_high,
+            )
+            .unwrap();
+            if header.version_number > HEADER_VERSION_1_8 {
+                write!(
+                    &mut info_file,
+                    "            L_LowVelocity={}\r\n",
+                    zone.velocity_low,
+                )
+                .unwrap();
+                write!(
+                    &mut info_file,
+                    "            L_HighVelocity={}\r\n",
+                    zone.velocity_high,
+                )
+                .unwrap();
+            }
+            write!(&mut info_file, "\r\n        GlobalLayer\r\n").unwrap();
+        }
+    }
+
+    write!(&mut info_file, "\r\n\r\n[Info]\r\n").unwrap();
+    write!(&mut info_file, "Version=2.1\r\n").unwrap();
+    write!(&mut info_file, "Engine=EMU8000\r\n").unwrap();
+    write!(
+        &mut info_file,
+        "Name={}\r\n",
+        args.mus_path
+            .with_extension("")
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+    )
+    .unwrap();
+    write!(&mut info_file, "Editor=Demus\r\n").unwrap();
+
+    let name = args
+        .mus_path
+        .with_extension("")
+        .file_stem()
+        .unwrap()
+        .to_string_lossy()
+        .into_owned();
+    if args.sf2 {
+        let sf2_path = output_dir.join(format!("{name}.sf2"));
+        let mut bytes = vec![];
+        soundfont_from_mus(&mus, &waves, &sam_file, platform, name.clone()).write(&mut bytes);
+        if let Err(e) = std::fs::write(&sf2_path, bytes) {
+            error!("Unable to write SoundFont {sf2_path:?}: {e}");
+        } else {
+            report::written(&sf2_path);
+        }
+    }
+    if args.dls {
+        let dls_path = output_dir.join(format!("{name}.dls"));
+        let mut bytes = vec![];
+        dls_from_mus(&mus, &waves, &sam_file, platform, name)
+            .write(args.dls_level.unwrap_or_default(), &mut bytes);
+        if let Err(e) = std::fs::write(&dls_path, bytes) {
+            error!("Unable to write DLS {dls_path:?}: {e}");
+        } else {
+            report::written(&dls_path);
+        }
+    }
+
+    info!(
+        "\n{}",
+        header.display(
+            platform,
+            program_zones.iter().fold(0, |acc, xs| acc + xs.len()),
+            preset_zones.iter().fold(0, |acc, xs| acc + xs.len()),
+        )
+    );
+}
+
+#[test]
+fn sf2comp_lines() {
+    assert_eq!(
+        sf2comp_generator(Generator::Pan, -250),
+        "            Z_pan=-250\r\n"
+    );
+    let mut zone = sf2::Zone::new(0);
+    zone.push_range(Generator::KeyRange, 36, 72);
+    let (generator, amount) = zone.generators[0];
+    assert_eq!(
+        sf2comp_generator(generator, amount as i32),
+        "            Z_LowKey=36\r\n            Z_HighKey=72\r\n"
+    );
+}
@@ -0,0 +1,397 @@
+use std::{
+    fs::File,
+    io::Write,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use core::{
+    cds,
+    clap::{self, CommandFactory, FromArgMatches, Parser},
+    log::{error, info},
+    msq,
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    serde_json::json,
+    snd::{SmpFile, SndFile, Version},
+    vab::{self, Program, Tone, VabFile, VabHeader},
+    vag::VagHeader,
+    wav::Wav,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// The `snd` path to load from.
+    snd_path: PathBuf,
+    /// The `smp` path to load from.
+    smp_path: PathBuf,
+    /// What version the `snd` file is.
+    #[clap(long)]
+    snd_version: Option<Version>,
+    /// Whether on the Dreamcast platform or not.
+    #[clap(short, long)]
+    dreamcast: bool,
+    #[clap(flatten)]
+    output: OutputArgs,
+    /// Decode the samples to WAV files, with the loop points and root key in a `smpl` chunk.
+    #[clap(long)]
+    wav: bool,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Parses the arguments of the standalone binary, which also accepts the old
+/// `-f`/`--file-version` spelling of `--snd-version`.
+#[must_use]
+pub fn parse_legacy() -> Args {
+    Args::from_arg_matches(&legacy_command().get_matches()).unwrap_or_else(|e| e.exit())
+}
+
+fn legacy_command() -> clap::Command {
+    Args::command().mut_arg("snd_version", |arg| {
+        arg.short_alias('f').alias("file-version")
+    })
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+
+    let output = Output::new(&args.output, &args.snd_path);
+    let summary = core::batch::run(vec![args.snd_path.clone()], NonZeroUsize::MIN, |_| {
+        extract(&args, &output);
+    });
+    if !summary.failed.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Splits one pair of SND and SMP files of `snd_version`, with the default
+/// options.
+pub fn convert_file(
+    snd_path: &Path,
+    smp_path: &Path,
+    output: &Output,
+    snd_version: Option<Version>,
+) {
+    let args = Args {
+        snd_path: snd_path.to_owned(),
+        smp_path: smp_path.to_owned(),
+        snd_version,
+        dreamcast: false,
+        output: OutputArgs::default(),
+        wav: false,
+        report: ReportArgs::default(),
+    };
+    extract(&args, output);
+}
+
+/// Splits the SND and SMP files into sequences, samples and a VAB bank.
+fn extract(args: &Args, output: &Output) {
+    let snd_bytes = match std::fs::read(&args.snd_path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Unable to load SND file {:?}: {e}", &args.snd_path);
+            return;
+        }
+    };
+    let smp_bytes = match std::fs::read(&args.smp_path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Unable to load SMP file {:?}: {e}", &args.smp_path);
+            return;
+        }
+    };
+
+    let snd_file = match SndFile::parse(&snd_bytes, args.snd_version.unwrap_or_default()) {
+        Ok(snd_file) => snd_file,
+        Err(e) => {
+            error!("Unable to parse SND file {:?}: {e}", &args.snd_path);
+            return;
+        }
+    };
+    let smp_file = match SmpFile::parse(&snd_file, &smp_bytes) {
+        Ok(smp_file) => smp_file,
+        Err(e) => {
+            error!("Unable to parse SMP file {:?}: {e}", &args.smp_path);
+            return;
+        }
+    };
+    report::set("header", &snd_file.header);
+
+    let Some(output_folder) = output.dir(&args.snd_path.with_extension("")) else {
+        return;
+    };
+    let sequences_folder = output_folder.join("sequences");
+    let samples_folder = output_folder.join("samples");
+    for folder in [&sequences_folder, &samples_folder] {
+        if let Err(e) = std::fs::create_dir_all(folder) {
+            error!("Unable to create output folder {folder:?}: {e}");
+            return;
+        }
+    }
+
+    for (i, sequence) in snd_file.sequences.iter().enumerate() {
+        let bytes = &snd_bytes[sequence.clone()];
+        let extension = match bytes.get(0..4) {
+            Some(magic) if magic == msq::MAGIC.to_le_bytes() => "msq",
+            Some(magic) if magic == cds::MAGIC => "cds",
+            magic => {
+                error!(
+                    "Unsupported sequence magic number {magic:x?}, skipping {}_{i:04}",
+                    output_folder.file_name().unwrap().to_string_lossy()
+                );
+                continue;
+            }
+        };
+
+        let output_path = sequences_folder.join(format!(
+            "{}_{i:04}.{extension}",
+            output_folder.file_name().unwrap().to_string_lossy()
+        ));
+        let mut output_file = File::create(&output_path).unwrap();
+        output_file.write_all(bytes).unwrap();
+        report::written(&output_path);
+        report::push(
+            "sequences",
+            json!({ "offset": sequence.start, "size": bytes.len(), "format": extension }),
+        );
+    }
+
+    let wav = args.wav && !args.dreamcast;
+    if args.wav && args.dreamcast {
+        error!("WAV output isn't supported for Dreamcast samples, writing them as-is");
+    }
+    for (i, wave) in smp_file.waves.iter().enumerate() {
+        let output_path = samples_folder.join(format!(
+            "{}_{i:04}.{}",
+            output_folder.file_name().unwrap().to_string_lossy(),
+            if args.dreamcast {
+                "dcs"
+            } else if wav {
+                "wav"
+            } else {
+                "vag"
+            }
+        ));
+
+        let mut output_file = File::create(&output_path).unwrap();
+        report::written(&output_path);
+        report::push(
+            "samples",
+            json!({ "offset": wave.start, "size": wave.len() }),
+        );
+        if wav {
+            let unity_note = snd_file
+                .zones
+                .iter()
+                .find(|zone| zone.wave_index as usize == i)
+                .map_or(60, |zone| zone.root_key);
+            let mut bytes = vec![];
+            Wav::from_adpcm(&smp_bytes[wave.clone()], 44100, unity_note).write(&mut bytes);
+            output_file.write_all(&bytes).unwrap();
+            continue;
+        }
+        if !args.dreamcast {
+            let mut header = vec![];
+            VagHeader::new(wave.len() as u32, 44100).write(&mut header);
+            output_file.write_all(&header).unwrap();
+        }
+        output_file.write_all(&smp_bytes[wave.clone()]).unwrap();
+    }
+
+    let vh_output_path = output_folder.join(
+        PathBuf::from(
+            output_folder
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .as_ref(),
+        )
+        .with_extension("vh"),
+    );
+    let mut vh_output = File::create(&vh_output_path).unwrap();
+    report::written(&vh_output_path);
+    let mut vh = vec![];
+    vab_from_snd(&snd_file, &smp_file).write_header(&mut vh);
+    vh_output.write_all(&vh).unwrap();
+
+    let vb_output_path = output_folder.join(
+        PathBuf::from(
+            output_folder
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .as_ref(),
+        )
+        .with_extension("vb"),
+    );
+    let mut vb_output = File::create(&vb_output_path).unwrap();
+    report::written(&vb_output_path);
+    for wave in &smp_file.waves {
+        vb_output.write_all(&smp_bytes[wave.clone()]).unwrap();
+    }
+
+    if snd_file.header.num_labels != 0 {
+        let label_path = output_folder
+            .join(args.snd_path.with_extension("lbl").file_name().unwrap())
+            .with_extension("lbl");
+        let mut label_file = File::create(&label_path).unwrap();
+        report::written(&label_path);
+        label_file.write_all(&[0x4C, 0x42, 0x4C, 0x61]).unwrap(); // Header, LBLa
+        label_file
+            .write_all(&snd_file.header.num_labels.to_le_bytes())
+            .unwrap();
+        for label in &snd_file.labels {
+            label_file.write_all(&label.to_le_bytes()).unwrap();
+        }
+    }
+
+    if report::enabled() {
+        return;
+    }
+    info!("");
+    println!("SND header");
+    println!("Header bytes: {}", snd_file.header.header_size);
+    if let Some(version) = snd_file.header.bank_version {
+        let minor = version.to_le_bytes()[0];
+        let major = version.to_le_bytes()[1];
+        println!(
+            "SND version: {major}.{minor} ({:?})",
+            args.snd_version.unwrap_or_default()
+        );
+    } else {
+        println!("SND version: Gex");
+    }
+    println!(
+        "System: {}",
+        if args.dreamcast {
+            "Dreamcast"
+        } else {
+            "PlayStation"
+        },
+    );
+    println!("Reverb mode: {}", snd_file.header.reverb_mode);
+    println!("Reverb depth: {}", snd_file.header.reverb_depth);
+    println!("Instruments: {}", snd_file.header.num_programs);
+    println!("Zones: {}", snd_file.header.num_zones);
+    println!("Samples: {}", snd_file.header.num_waves);
+    println!("Sequences: {}", snd_file.header.num_sequences);
+    println!("Labels: {}", snd_file.header.num_labels);
+}
+
+/// Builds the VAB bank that holds the same programs, zones and waves as the SND file.
+fn vab_from_snd(snd_file: &SndFile, smp_file: &SmpFile) -> VabFile {
+    // Because some zones can access more zones than are available, we have to skip them in a lot of things
+    // The way we "skip" it is by setting the number of zones a program requests to zero.
+    // This keeps the program around, but can also be a little confusing in some places (hence annoying iterator code like this)
+    let finalised_number_of_programs = snd_file.programs.iter().filter(|p| p.num_zones > 0).count();
+    let vag_sizes = smp_file
+        .waves
+        .iter()
+        .map(|wave| wave.len())
+        .collect::<Vec<_>>();
+
+    let header = VabHeader {
+        magic_number: u32::from_le_bytes(vab::MAGIC),
+        version: 7,
+        vab_id: 0,
+        total_size: (VabFile::header_size(finalised_number_of_programs)
+            + vag_sizes.iter().sum::<usize>()) as u32,
+        _pad0: 0,
+        programs_number: finalised_number_of_programs as u16,
+        tones_number: snd_file.header.num_zones as u16,
+        vags_number: snd_file.header.num_waves as u16,
+        master_volume: 0x7f,
+        master_pan: 0x40,
+        bank_attributes_1: 0,
+        bank_attributes_2: 0,
+        _pad1: 0,
+    };
+
+    let mut bank_attribute: i16 = -1;
+    let mut zone_index = 0;
+    let mut programs = Vec::with_capacity(snd_file.programs.len());
+    let mut tones = Vec::with_capacity(snd_file.programs.len());
+    for (index, program) in snd_file.programs.iter().enumerate() {
+        if let Some(zone) = snd_file.zones.get(zone_index) {
+            if zone.parent_program == 0 {
+                bank_attribute += 1;
+            }
+        }
+        programs.push(Program {
+            index: index as u8,
+            tones_number: program.num_zones as u8,
+            volume: program.volume,
+            priority: 0,
+            mode: 0,
+            pan: program.pan_pos,
+            _pad0: 0,
+            attribute: bank_attribute as u16,
+            _pad1: 0,
+            _pad2: 0,
+        });
+        tones.push(
+            snd_file.zones[zone_index..zone_index + program.num_zones as usize]
+                .iter()
+                .map(|zone| Tone {
+                    priority: zone.priority,
+                    reverb_mode: zone.mode,
+                    volume: zone.volume,
+                    pan: zone.pan_pos,
+                    unity_key: zone.root_key,
+                    pitch_tune: zone.pitch_fine_tuning,
+                    key_low: zone.note_low,
+                    key_high: zone.note_high,
+                    vibrato_width: 0,
+                    vibrato_time: 0,
+                    port_width: 0,
+                    port_hold: 0,
+                    pitch_bend_minimum: zone.max_pitch_range,
+                    pitch_bend_maximum: zone.max_pitch_range,
+                    _pad0: 0,
+                    _pad1: 0,
+                    adsr1: zone.adsr1,
+                    adsr2: zone.adsr2,
+                    parent_program: zone.parent_program as u16,
+                    sample_number: zone.wave_index.checked_add(1).unwrap_or(1),
+                    _pad2: 0,
+                    _pad3: 0,
+                    _pad4: 0,
+                    _pad5: 0,
+                })
+                .collect(),
+        );
+        zone_index += program.num_zones as usize;
+    }
+
+    let vag_ranges = vag_sizes
+        .iter()
+        .fold(
+            (vec![], VabFile::header_size(finalised_number_of_programs)),
+            |(mut acc, cursor), size| {
+                acc.push(cursor..cursor + size);
+                (acc, cursor + size)
+            },
+        )
+        .0;
+
+    VabFile {
+        header,
+        programs,
+        tones,
+        vag_sizes,
+        vag_ranges,
+    }
+}
+
+#[test]
+fn legacy_spelling() {
+    let matches = legacy_command()
+        .try_get_matches_from(["desnd", "a.snd", "a.smp", "-f", "gex"])
+        .unwrap();
+    let args = Args::from_arg_matches(&matches).unwrap();
+    assert!(args.snd_version == Some(Version::Gex));
+    assert!(Args::try_parse_from(["desnd", "a.snd", "a.smp", "-f", "gex"]).is_err());
+}
@@ -0,0 +1,294 @@
+use std::{
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use core::{
+    ads::{self, AdsHeader},
+    batch::JobArgs,
+    cds,
+    clap::{self, Parser, ValueEnum},
+    format::Format,
+    input::InputArgs,
+    log::{error, info},
+    msq::MsqFile,
+    mus::MusFile,
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    seq,
+    snd::{self, SndFile},
+    vab::VabFile,
+    vag::VagHeader,
+    Reader,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// The file, or folder of files, to identify.
+    input: PathBuf,
+    /// Also convert the files with the program for their format: cds2seq for CDS, msqsplit for
+    /// MSQ, seq2mid for SEQ, vabsmp for VAB, and demus and desnd for MUS and SND files with a
+    /// SAM or SMP file of the same name. ADS and VAG files are left as they are.
+    #[clap(long, conflicts_with = "report")]
+    convert: bool,
+    /// The sample rate of the samples vabsmp exports from VAB files, which don't store it.
+    #[clap(long, default_value = "44100")]
+    vab_sample_rate: NonZeroU32,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+    let output = Output::new(&args.output, &args.input);
+
+    let found = Mutex::new(vec![]);
+    let files = core::get_files(&args.input, &args.files, &[]);
+    core::batch::run(files, args.batch.jobs, |file_path| {
+        let file = match std::fs::read(file_path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Unable to read {file_path:?}: {e}");
+                return;
+            }
+        };
+        let format = Format::detect(&file);
+        report::set("format", format);
+        let Some(format) = format else {
+            if !report::enabled() {
+                println!("{}: unknown format", file_path.display());
+            }
+            return;
+        };
+        match describe(format, &file) {
+            Ok(description) => {
+                if !report::enabled() {
+                    println!("{}: {format}, {description}", file_path.display());
+                }
+                found.lock().unwrap().push((file_path.to_owned(), format));
+            }
+            Err(e) => error!("Unable to parse {format} file {file_path:?}: {e}"),
+        }
+    });
+
+    if args.convert {
+        let mut found = found.into_inner().unwrap();
+        found.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (path, format) in &found {
+            convert(path, *format, &found, &output, args.vab_sample_rate);
+        }
+    }
+}
+
+/// Parses the header of the file, adding it to the report, and describes its
+/// main fields.
+fn describe(format: Format, file: &[u8]) -> core::Result<String> {
+    let bytes = &mut Reader::new(file);
+    Ok(match format {
+        Format::Ads => {
+            let header = AdsHeader::parse(bytes)?;
+            report::set("header", &header);
+            let codec = match header.codec {
+                ads::CODEC_PCM16 => "PCM16".to_owned(),
+                ads::CODEC_VAG => "4-bit ADPCM".to_owned(),
+                codec => format!("codec {codec:#x}"),
+            };
+            let mut description = format!(
+                "{codec}, {} Hz, {} channels, interleave {}, {} bytes",
+                header.sample_rate, header.channels, header.interleave, header.body_size
+            );
+            if let (Some(start), Some(end)) = (header.loop_start, header.loop_end) {
+                description += &format!(", loop {start}-{end}");
+            }
+            description
+        }
+        Format::Cds => {
+            let header = cds::Header::parse(bytes)?;
+            report::set("header", &header);
+            format!(
+                "version {:#06x}, {} PPQN, tempo {} µs per quarter note",
+                header.version, header.ppqn, header.quarter_note_time
+            )
+        }
+        Format::Msq => {
+            let header = MsqFile::parse(file)?.header;
+            report::set("header", &header);
+            format!(
+                "version {:#06x}, {} PPQN, tempo {} µs per quarter note, {} tracks",
+                header.version, header.ppqn, header.quarter_note_time, header.num_tracks
+            )
+        }
+        Format::Mus => {
+            let header = MusFile::parse(file)?.header;
+            report::set("header", &header);
+            format!(
+                "version {}.{}, {} sequences, {} samples, {} programs, {} presets",
+                header.version_number >> 8,
+                header.version_number & 0xff,
+                header.num_sequences,
+                header.num_waves,
+                header.num_programs,
+                header.num_presets
+            )
+        }
+        Format::Sam | Format::Smp => format!("{} bytes", file.len()),
+        Format::Seq => {
+            let header = seq::Header::parse(bytes)?;
+            report::set("header", &header);
+            format!(
+                "version {}, {} PPQN, tempo {} µs per quarter note, {}/{} time",
+                header.version,
+                header.ppqn,
+                header.tempo,
+                header.time_signature.0,
+                1u32 << header.time_signature.1
+            )
+        }
+        Format::Snd => {
+            // When none of the layouts fit, the default one gives the error.
+            let version = snd::Version::detect(file).unwrap_or_default();
+            let header = SndFile::parse(file, version)?.header;
+            report::set("version", version);
+            report::set("header", &header);
+            format!(
+                "{} layout, {} programs, {} zones, {} samples, {} sequences",
+                value_name(version),
+                header.num_programs,
+                header.num_zones,
+                header.num_waves,
+                header.num_sequences
+            )
+        }
+        Format::Vab => {
+            let header = VabFile::parse(file)?.header;
+            report::set("header", &header);
+            format!(
+                "version {}, {} programs, {} tones, {} samples",
+                header.version, header.programs_number, header.tones_number, header.vags_number
+            )
+        }
+        Format::Vag => {
+            let header = VagHeader::parse(bytes)?;
+            report::set("header", &header);
+            let name = header.name.split(|byte| *byte == 0).next().unwrap();
+            let mut description = format!(
+                "version {}, {} Hz, {} bytes",
+                header.version, header.sample_rate, header.data_size
+            );
+            if !name.is_empty() {
+                description += &format!(", named {:?}", String::from_utf8_lossy(name));
+            }
+            description
+        }
+    })
+}
+
+/// Converts a file with the program for its format, using the same output
+/// options.
+fn convert(
+    path: &Path,
+    format: Format,
+    found: &[(PathBuf, Format)],
+    output: &Output,
+    vab_sample_rate: NonZeroU32,
+) {
+    let output = Output::new(&output.args_for(path), path);
+    match format {
+        Format::Cds => cds2seq::convert_file(
+            path,
+            &output,
+            cds::Commands::default(),
+            cds::Loops::default(),
+            false,
+        ),
+        Format::Msq => msqsplit::convert_file(path, &output, false),
+        Format::Seq => seq2mid::convert_file(path, &output),
+        Format::Mus => {
+            let Some(sam) = partner(path, Format::Sam, found) else {
+                error!("No SAM file found for {path:?}, skipping");
+                return;
+            };
+            info!("Handling {path:?}");
+            demus::convert_file(path, &sam, &output);
+        }
+        Format::Snd => {
+            let Some(smp) = partner(path, Format::Smp, found) else {
+                error!("No SMP file found for {path:?}, skipping");
+                return;
+            };
+            let version = std::fs::read(path)
+                .ok()
+                .and_then(|file| snd::Version::detect(&file));
+            info!("Handling {path:?}");
+            desnd::convert_file(path, &smp, &output, version);
+        }
+        Format::Vab => vabsmp::convert_file(path, vab_sample_rate, &output),
+        Format::Sam | Format::Smp => {
+            info!(
+                "Leaving {path:?}, {format} files are converted along with their MUS or SND file"
+            );
+        }
+        Format::Ads | Format::Vag => info!("No converter for {format} files, leaving {path:?}"),
+    }
+}
+
+/// The SAM or SMP file that goes with `path`, which is a file found to be of
+/// `format` with the same name in the same folder, or else a file with the
+/// usual extension.
+fn partner(path: &Path, format: Format, found: &[(PathBuf, Format)]) -> Option<PathBuf> {
+    found
+        .iter()
+        .find(|(other, other_format)| {
+            *other_format == format && other.with_extension("") == path.with_extension("")
+        })
+        .map(|(other, _)| other.clone())
+        .or_else(|| {
+            [
+                format.extension().to_owned(),
+                format.extension().to_ascii_uppercase(),
+            ]
+            .into_iter()
+            .map(|extension| path.with_extension(extension))
+            .find(|path| path.is_file())
+        })
+}
+
+/// The name of a command line value, like `soul-reaver`.
+fn value_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_owned())
+        .unwrap_or_default()
+}
+
+#[test]
+fn descriptions() {
+    let mut vag = vec![];
+    VagHeader::new(0x6270, 22050).write(&mut vag);
+    assert_eq!(
+        describe(Format::Vag, &vag),
+        Ok("version 3, 22050 Hz, 25200 bytes".to_owned())
+    );
+    assert!(describe(Format::Vag, &vag[0..20]).is_err());
+
+    let found = [
+        (PathBuf::from("music/a.bin"), Format::Mus),
+        (PathBuf::from("music/a"), Format::Sam),
+        (PathBuf::from("other/b"), Format::Sam),
+    ];
+    assert_eq!(
+        partner(Path::new("music/a.bin"), Format::Sam, &found),
+        Some(PathBuf::from("music/a"))
+    );
+    assert_eq!(partner(Path::new("music/b.mus"), Format::Sam, &found), None);
+    assert_eq!(value_name(snd::Version::SoulReaver), "soul-reaver");
+}
@@ -0,0 +1,199 @@
+use std::path::{Path, PathBuf};
+
+use core::{
+    batch::JobArgs,
+    cds,
+    clap::{self, Parser},
+    input::InputArgs,
+    log::{error, info},
+    midi::Smf,
+    msq::MsqFile,
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    seq,
+    serde_json::json,
+    Reader,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// msq file to read
+    input: PathBuf,
+    /// Merge the tracks into a single type 1 MIDI file instead of splitting them.
+    #[clap(long)]
+    midi: bool,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+    let output = Output::new(&args.output, &args.input);
+
+    let files = core::get_files(&args.input, &args.files, &["msq"]);
+    core::batch::run(files, args.batch.jobs, |file_path| {
+        convert_file(file_path, &output, args.midi);
+    });
+}
+
+/// Splits one MSQ file into CDS tracks, or merges them into a MIDI file if
+/// `midi` is set.
+pub fn convert_file(file_path: &Path, output: &Output, midi: bool) {
+    info!("Handling {file_path:?}");
+    let bytes = match std::fs::read(file_path) {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Unable to open {file_path:?}: {e}");
+            return;
+        }
+    };
+
+    let msq = match MsqFile::parse(&bytes) {
+        Ok(msq) => msq,
+        Err(e) => {
+            error!("Unable to parse {file_path:?}, skipping: {e}");
+            return;
+        }
+    };
+    report::set("header", &msq.header);
+    for track in &msq.tracks {
+        report::push(
+            "tracks",
+            json!({ "offset": track.start, "size": track.len() }),
+        );
+    }
+    if midi {
+        let Some(midi_path) = output.file(&file_path.with_extension("mid")) else {
+            return;
+        };
+        if let Some(smf) = merge_tracks(file_path, &bytes, &msq) {
+            let mut midi = vec![];
+            smf.write(&mut midi);
+            if let Err(e) = std::fs::write(&midi_path, midi) {
+                error!("Unable to write MIDI file {midi_path:?}: {e}");
+            }
+        }
+    } else {
+        let Some(folder) = output.dir(&file_path.with_extension("")) else {
+            return;
+        };
+        split_tracks(&folder, &bytes, &msq);
+    }
+
+    let header = msq.header;
+    info!("MSQ header for: {file_path:?}");
+    info!("Quarter note time: {}", header.quarter_note_time);
+    info!("PPQN: {}", header.ppqn);
+    info!(
+        "BPM: {}",
+        60_000_000u32
+            .checked_div(header.quarter_note_time)
+            .unwrap_or_default()
+    );
+    info!(
+        "Version: {}.{}",
+        header.version.to_be_bytes()[0],
+        header.version.to_be_bytes()[1]
+    );
+    info!("Tracks/Channels: {}", header.num_tracks);
+}
+
+/// Writes each track as a CDS file in `folder`, named after the folder.
+fn split_tracks(folder: &Path, bytes: &[u8], msq: &MsqFile) {
+    for (index, track) in msq.tracks.iter().enumerate() {
+        let path = folder.join(format!(
+            "{}_{index:04}.cds",
+            folder.file_name().unwrap().to_string_lossy()
+        ));
+        let mut cds = vec![];
+        msq.header.cds_header().write(&mut cds);
+        cds.extend_from_slice(&bytes[track.clone()]);
+        if let Err(e) = std::fs::write(&path, cds) {
+            error!("Unable to write {path:?}: {e}");
+        } else {
+            report::written(&path);
+        }
+    }
+}
+
+/// Converts every track the way `cds2seq` does, then merges them after a
+/// shared tempo track. Commands other than tempo changes are dropped, as
+/// they would otherwise reset the tempo.
+fn merge_tracks(file_path: &Path, bytes: &[u8], msq: &MsqFile) -> Option<Smf> {
+    let header = seq::Header::new(msq.header.ppqn, msq.header.quarter_note_time);
+    let mut tracks = vec![];
+    for (index, track) in msq.tracks.iter().enumerate() {
+        let track = match cds::unroll(&bytes[track.clone()], header.tempo, cds::Commands::Drop) {
+            Ok(track) => track,
+            Err(e) => {
+                error!("Unable to convert track {index} of {file_path:?}, skipping: {e}");
+                return None;
+            }
+        };
+        match seq::parse_events(&mut Reader::new(&track)) {
+            Ok(events) => tracks.push(events),
+            Err(e) => {
+                error!("Unable to read converted track {index} of {file_path:?}, skipping: {e}");
+                return None;
+            }
+        }
+    }
+
+    Some(Smf::with_tempo_track(
+        header.ppqn,
+        header.tempo,
+        header.time_signature,
+        tracks,
+    ))
+}
+
+#[test]
+fn tempo_change_before_loop() {
+    use core::{
+        midi::Message,
+        msq::{self, MsqHeader},
+    };
+
+    let header = MsqHeader {
+        magic: msq::MAGIC,
+        quarter_note_time: 500_000,
+        ppqn: 480,
+        version: 0x0101,
+        num_tracks: 0,
+        _padding: 0,
+    };
+    let track = [
+        0x00, 0xff, 0x05, 0x03, 0x06, 0x1a, 0x80, // Tempo
+        0x00, 0xff, 0x2e, 0x01, 0x02, // Loop start
+        0x00, 0x90, 0x3c, 0x40, // Note on
+        0x60, 0x80, 0x3c, 0x00, // Note off
+        0x00, 0xff, 0x2f, 0x00, // Loop finish
+        0x00, 0xff, 0x44, 0x00, // Global ending
+    ];
+    let mut bytes = vec![];
+    MsqFile::write(&header, &[&track], &mut bytes);
+    let msq = MsqFile::parse(&bytes).unwrap();
+
+    let smf = merge_tracks(Path::new("test.msq"), &bytes, &msq).unwrap();
+    let tempos = smf.tracks[0]
+        .iter()
+        .filter_map(|event| match event.message {
+            Message::Tempo(tempo) => Some(tempo),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(tempos, [500_000, 400_000]);
+    let notes = smf.tracks[1]
+        .iter()
+        .filter(|event| matches!(event.message, Message::Channel { status: 0x90, .. }))
+        .count();
+    assert_eq!(notes, 2);
+}
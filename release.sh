@@ -11,14 +11,20 @@ cp target/release/cds2seq out
 cp target/release/demul out
 cp target/release/demus out
 cp target/release/desnd out
+cp target/release/identify out
 cp target/release/msqsplit out
+cp target/release/seq2mid out
+cp target/release/seqasm out
+cp target/release/seqdump out
 cp target/release/seqrepeat out
 cp target/release/sf2panlaw out
 cp target/release/vabfine out
 cp target/release/vabsmp out
 cp target/release/vagheader out
+cp target/release/vaginfo out
 cp target/release/vagsanitizer out
 cp target/release/vagunloop out
+cp target/release/unlokable out
 
 zip -r linux.zip out
 rm -rf out
@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use core::{
+    batch::JobArgs,
+    clap::{self, Parser},
+    input::InputArgs,
+    log::{error, info},
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    seq::SeqFile,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// `seq` files to convert.
+    input: PathBuf,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+
+    let output = Output::new(&args.output, &args.input);
+    let files = core::get_files(&args.input, &args.files, &["seq"]);
+    core::batch::run(files, args.batch.jobs, |file| {
+        convert_file(file, &output);
+    });
+}
+
+/// Converts one SEQ file to a MIDI file.
+pub fn convert_file(path: &Path, output: &Output) {
+    info!("Converting {path:?}");
+    let bytes = match std::fs::read(path) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("Unable to open file: {e}");
+            return;
+        }
+    };
+
+    let midi = match seq_to_midi(&bytes) {
+        Ok(midi) => midi,
+        Err(e) => {
+            error!("Unable to parse {path:?}, skipping: {e}");
+            return;
+        }
+    };
+
+    let Some(out_path) = output.file(&path.with_extension("mid")) else {
+        return;
+    };
+    if let Err(e) = std::fs::write(&out_path, midi) {
+        error!("Unable to write output file {out_path:?}: {e}");
+    }
+}
+
+fn seq_to_midi(bytes: &[u8]) -> core::Result<Vec<u8>> {
+    let seq = SeqFile::parse(bytes)?;
+    report::set("header", &seq.header);
+    report::set("events", seq.events.len());
+    let mut midi = vec![];
+    seq.to_smf().write(&mut midi);
+    Ok(midi)
+}
+
+#[test]
+fn with_loop() {
+    let midi = seq_to_midi(include_bytes!("../tests/loop.seq")).unwrap();
+    assert_eq!(&midi[0..4], b"MThd");
+    // Type 0 with one track at 480 PPQN
+    assert_eq!(midi[8..14], [0, 0, 0, 1, 0x01, 0xe0]);
+    assert_eq!(&midi[14..18], b"MTrk");
+    assert_eq!(
+        u32::from_be_bytes(midi[18..22].try_into().unwrap()) as usize,
+        midi.len() - 22
+    );
+    // The loop markers are kept as controllers
+    assert!(midi.windows(3).any(|x| x == [0xb0, 0x63, 0x14]));
+    assert!(midi.windows(3).any(|x| x == [0xb0, 0x63, 0x1e]));
+}
+
+#[test]
+fn truncated() {
+    let file = include_bytes!("../tests/loop.seq");
+    assert!(matches!(
+        seq_to_midi(&file[..file.len() - 4]),
+        Err(core::Error::Truncated { .. })
+    ));
+}
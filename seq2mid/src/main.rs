@@ -0,0 +1,6 @@
+use core::clap::Parser;
+
+fn main() {
+    core::init();
+    seq2mid::run(seq2mid::Args::parse());
+}
@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+
+use core::{
+    batch::JobArgs,
+    clap::{self, Parser},
+    input::InputArgs,
+    listing,
+    log::{error, info},
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// Listing made by `seqdump` to assemble
+    input: PathBuf,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+    let output_paths = Output::new(&args.output, &args.input);
+
+    let files = core::get_files(&args.input, &args.files, &["txt"]);
+    core::batch::run(files, args.batch.jobs, |path| {
+        info!("Assembling {path:?}");
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                error!("Unable to read file {path:?}, skipping: {e}");
+                return;
+            }
+        };
+
+        let assembled = match listing::assemble(&text) {
+            Ok(assembled) => assembled,
+            Err(e) => {
+                error!("Unable to assemble {path:?}, skipping: {e}");
+                return;
+            }
+        };
+        report::set("format", assembled.extension);
+        report::set("size", assembled.bytes.len());
+        let Some(output) = output_paths.file(&output_path(path, assembled.extension)) else {
+            return;
+        };
+        info!("Writing {output:?}");
+        if let Err(e) = std::fs::write(&output, assembled.bytes) {
+            error!("Unable to write output file {output:?}: {e}");
+        }
+    });
+}
+
+/// Where the output goes by default, next to the listing.
+fn output_path(path: &Path, extension: &str) -> PathBuf {
+    path.with_extension(extension)
+}
+
+#[test]
+fn assemble() {
+    let text = include_str!("../tests/loop.txt");
+    let assembled = listing::assemble(text).unwrap();
+    assert_eq!(
+        output_path(Path::new("songs/loop.txt"), assembled.extension),
+        Path::new("songs/loop.cds")
+    );
+    assert_eq!(
+        listing::assemble(&listing::cds(&assembled.bytes).unwrap())
+            .unwrap()
+            .bytes,
+        assembled.bytes
+    );
+}
@@ -0,0 +1,133 @@
+use std::{
+    fs::File,
+    io::Write,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::exit,
+    sync::Mutex,
+};
+
+use core::{
+    clap::{self, CommandFactory, FromArgMatches, Parser},
+    format::Format,
+    input::InputArgs,
+    listing,
+    log::{error, info},
+    report::{self, ReportArgs},
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// `cds`, `msq` or `seq` file to list the events of
+    input: PathBuf,
+    /// The file to write the listing to, writes to STDOUT otherwise.
+    #[clap(long)]
+    list_file: Option<PathBuf>,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Parses the arguments of the standalone binary, which also accepts the old
+/// `-o`/`--output` spelling of `--list-file`.
+#[must_use]
+pub fn parse_legacy() -> Args {
+    Args::from_arg_matches(&legacy_command().get_matches()).unwrap_or_else(|e| e.exit())
+}
+
+fn legacy_command() -> clap::Command {
+    Args::command().mut_arg("list_file", |arg| arg.short_alias('o').alias("output"))
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+
+    let file = Mutex::new(args.list_file.map(|path| {
+        File::create(&path).unwrap_or_else(|e| {
+            error!("Unable to create file {path:?}: {e}");
+            exit(1)
+        })
+    }));
+
+    // One at a time, to keep the listings in order
+    let files = core::get_files(&args.input, &args.files, &["cds", "msq", "seq"]);
+    core::batch::run(files, NonZeroUsize::MIN, |path| {
+        info!("Listing {path:?}");
+        let contents = match std::fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                error!("Unable to read file {path:?}, skipping: {e}");
+                return;
+            }
+        };
+
+        let listing = match dump(path, &contents) {
+            Some(Ok(listing)) => listing,
+            Some(Err(e)) => {
+                error!("Unable to parse {path:?}, skipping: {e}");
+                return;
+            }
+            None => {
+                error!("Unknown format for {path:?}, skipping");
+                return;
+            }
+        };
+        let format = listing.split_whitespace().next().unwrap_or_default();
+        report::set("format", format.trim_start_matches('.'));
+        report::set("lines", listing.lines().count());
+        let text = format!("; {}\n{listing}", path.display());
+        if let Some(file) = file.lock().unwrap().as_mut() {
+            writeln!(file, "{text}").unwrap();
+        } else if !report::enabled() {
+            println!("{text}");
+        }
+    });
+}
+
+/// Lists the file by its extension, or by its magic number for other
+/// extensions. MSQ files can also start with the CDS magic number.
+fn dump(path: &Path, contents: &[u8]) -> Option<core::Result<String>> {
+    match path
+        .extension()
+        .map(|extension| extension.to_ascii_lowercase())
+        .as_ref()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("cds") => Some(listing::cds(contents)),
+        Some("msq") => Some(listing::msq(contents)),
+        Some("seq") => Some(listing::seq(contents)),
+        _ => match Format::detect(contents)? {
+            Format::Cds => Some(listing::cds(contents)),
+            Format::Msq => Some(listing::msq(contents)),
+            Format::Seq => Some(listing::seq(contents)),
+            _ => None,
+        },
+    }
+}
+
+#[test]
+fn detection() {
+    let seq = include_bytes!("../tests/loop.seq");
+    let listing = dump(Path::new("loop.SEQ"), seq).unwrap().unwrap();
+    assert!(listing.starts_with(".seq "));
+    let listing = dump(Path::new("loop.bin"), seq).unwrap().unwrap();
+    assert!(listing.starts_with(".seq "));
+    assert!(listing.contains(", loop start\n"));
+    assert!(!listing.contains("; error"));
+
+    assert!(dump(Path::new("loop.msq"), seq).unwrap().is_err());
+    assert!(dump(Path::new("loop.bin"), &[0; 16]).is_none());
+}
+
+#[test]
+fn legacy_spelling() {
+    let matches = legacy_command()
+        .try_get_matches_from(["seqdump", "a.seq", "--output", "a.txt"])
+        .unwrap();
+    let args = Args::from_arg_matches(&matches).unwrap();
+    assert!(args.list_file.is_some());
+    assert!(Args::try_parse_from(["seqdump", "a.seq", "--output", "a.txt"]).is_err());
+}
@@ -0,0 +1,4 @@
+fn main() {
+    core::init();
+    seqdump::run(seqdump::parse_legacy());
+}
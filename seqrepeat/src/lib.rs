@@ -0,0 +1,219 @@
+use std::{io::Write, num::NonZeroUsize, path::PathBuf};
+
+use core::{
+    batch::JobArgs,
+    clap::{self, Parser},
+    input::InputArgs,
+    log::{error, info, warn},
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    seq, Reader,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// `seq` files to read from.
+    input: PathBuf,
+    /// The number of the passes in the final file.
+    count: NonZeroUsize,
+    /// Whether to read from the tempo marker rather than the entire file.
+    #[clap(short, long)]
+    tempo_marker: bool,
+    /// Whether to read from the loop markers. This is the default.
+    #[clap(short, long)]
+    loop_marker: bool,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+    let output_paths = Output::new(&args.output, &args.input);
+
+    let files = core::get_files(&args.input, &args.files, &["seq"]);
+    core::batch::run(files, args.batch.jobs, |file| {
+        info!("Repeating {file:?}");
+        let bytes = match std::fs::read(file) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("Unable to open file: {e}");
+                return;
+            }
+        };
+
+        let Some(output) = repeat_file(&bytes, &args) else {
+            return;
+        };
+
+        let Some(out_path) = output_paths.file(&file.with_file_name(format!(
+            "{}_x{:02}.seq",
+            file.file_stem().unwrap().to_string_lossy(),
+            args.count
+        ))) else {
+            return;
+        };
+        if let Err(e) = std::fs::write(&out_path, output) {
+            error!("Unable to write output file {out_path:?}: {e}");
+        }
+    });
+}
+
+fn repeat_file(file: &[u8], args: &Args) -> Option<Vec<u8>> {
+    let (loop_start, loop_end) = if args.loop_marker {
+        find_loops(file)
+    } else {
+        (None, None)
+    };
+
+    let header = match seq::Header::parse(&mut Reader::new(file)) {
+        Ok(header) => header,
+        Err(e) => {
+            error!("Unable to load header: {e}");
+            return None;
+        }
+    };
+    report::set("header", &header);
+    report::set("loop_start", loop_start);
+    report::set("loop_end", loop_end);
+
+    let beginning_index = match args.tempo_marker {
+        // 0xff51XXXXXX
+        true => {
+            file.windows(2)
+                .enumerate()
+                .find(|(_, w)| *w == [0xff, 0x51])
+                .unwrap_or_else(|| {
+                    warn!("No marker found, defaulting to full file");
+                    (10, &[])
+                })
+                .0
+                + 5
+        }
+        false => seq::HEADER_SIZE,
+    };
+    let beginning = &file[0..beginning_index];
+    let to_copy = match (loop_start, loop_end) {
+        (Some(start), Some(end)) => &file[start + 6..end + 3],
+        (Some(start), None) => &file[start + 6..],
+        _ => {
+            &file[beginning.len()
+                ..file
+                    .windows(3)
+                    .enumerate()
+                    .find(|(_, w)| w.len() == 3 && w == &[0xff, 0x2f, 0x00])
+                    .unwrap()
+                    .0
+                    + 3]
+        }
+    };
+
+    let mut output = Vec::with_capacity(file.len());
+    output.write_all(beginning).unwrap();
+    if let Some(start) = loop_start {
+        output.write_all(&file[beginning_index..start + 6]).unwrap();
+    }
+    for i in 0..args.count.get() {
+        output.write_all(to_copy).unwrap();
+        if i < args.count.get() - 1 {
+            output.splice(
+                output.len() - 3..,
+                dummy_string(&header, file, args.loop_marker, loop_start),
+            );
+        }
+    }
+    if let Some(end) = loop_end {
+        output.write_all(&file[end + 3..]).unwrap();
+    }
+
+    Some(output)
+}
+
+fn find_loops(file: &[u8]) -> (Option<usize>, Option<usize>) {
+    let mut start = None;
+    let mut end = None;
+    for (index, bytes) in file.windows(3).enumerate() {
+        if bytes[0] & 0xf0 == 0xb0 && bytes[1] == 0x63 {
+            if bytes[2] == 0x14 {
+                start = Some(index);
+            } else if bytes[2] == 0x1e {
+                end = Some(index);
+            }
+        }
+    }
+
+    (start, end)
+}
+
+fn dummy_string(
+    header: &seq::Header,
+    file: &[u8],
+    loop_marker: bool,
+    loop_start: Option<usize>,
+) -> Vec<u8> {
+    if let Some(start) = loop_start {
+        vec![file[start], 0x63, 0x1e]
+    } else if loop_marker {
+        vec![0xb0, 0x63, 0x1e]
+    } else {
+        [0xff, 0x51]
+            .into_iter()
+            .chain(header.tempo_bytes())
+            .collect()
+    }
+}
+
+#[test]
+fn with_loop_normal() {
+    let args = Args {
+        input: PathBuf::default(),
+        count: NonZeroUsize::new(3).unwrap(),
+        tempo_marker: false,
+        loop_marker: true,
+        output: OutputArgs::default(),
+        files: InputArgs::default(),
+        batch: JobArgs::default(),
+        report: ReportArgs::default(),
+    };
+    let input = include_bytes!("../tests/with_loop_normal.seq");
+    assert_eq!(repeat_file(input, &args).unwrap().len(), 715);
+}
+
+#[test]
+fn with_loop_tempo() {
+    let args = Args {
+        input: PathBuf::default(),
+        count: NonZeroUsize::new(3).unwrap(),
+        tempo_marker: true,
+        loop_marker: false,
+        output: OutputArgs::default(),
+        files: InputArgs::default(),
+        batch: JobArgs::default(),
+        report: ReportArgs::default(),
+    };
+    let input = include_bytes!("../tests/with_loop_tempo.seq");
+    assert_eq!(repeat_file(input, &args).unwrap().len(), 699);
+}
+
+#[test]
+fn no_loops() {
+    let args = Args {
+        input: PathBuf::default(),
+        count: NonZeroUsize::new(3).unwrap(),
+        tempo_marker: true,
+        loop_marker: false,
+        output: OutputArgs::default(),
+        files: InputArgs::default(),
+        batch: JobArgs::default(),
+        report: ReportArgs::default(),
+    };
+    let input = include_bytes!("../tests/noloops.seq");
+    assert_eq!(repeat_file(input, &args).unwrap().len(), 979);
+}
@@ -0,0 +1,187 @@
+use core::clap::{self, Parser, Subcommand};
+
+/// Every unLoKable tool in one program, grouped by the format they read.
+#[derive(Parser)]
+#[clap(version)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Finds the format of files from their contents, and converts them, like identify.
+    Identify(identify::Args),
+    /// Sony ADS streams.
+    #[clap(subcommand)]
+    Ads(Ads),
+    /// Crystal Dynamics CDS sequences.
+    #[clap(subcommand)]
+    Cds(Cds),
+    /// Crystal Dynamics MSQ sequences.
+    #[clap(subcommand)]
+    Msq(Msq),
+    /// Crystal Dynamics MUL multiplexed streams.
+    #[clap(subcommand)]
+    Mul(Mul),
+    /// Crystal Dynamics MUS and SAM banks.
+    #[clap(subcommand)]
+    Mus(Mus),
+    /// Crystal Dynamics SND and SMP banks.
+    #[clap(subcommand)]
+    Snd(Snd),
+    /// Sony SEQ sequences, and listings of any sequence.
+    #[clap(subcommand)]
+    Seq(Seq),
+    /// SF2Comp text files.
+    #[clap(subcommand)]
+    Sf2(Sf2),
+    /// Sony VAB banks.
+    #[clap(subcommand)]
+    Vab(Vab),
+    /// Sony VAG samples.
+    #[clap(subcommand)]
+    Vag(Vag),
+}
+
+#[derive(Subcommand)]
+enum Ads {
+    /// Adds an ADS header to a headerless audio stream, like adsheader.
+    Header(adsheader::Args),
+    /// Lists the loop points of ADS files, like adsloopfind.
+    FindLoops(adsloopfind::Args),
+    /// Removes or sets the loop markers of ADS files, like adsunloop.
+    Unloop(adsunloop::Args),
+}
+
+#[derive(Subcommand)]
+enum Cds {
+    /// Converts CDS files to SEQ, like cds2seq.
+    ToSeq(cds2seq::Args),
+}
+
+#[derive(Subcommand)]
+enum Msq {
+    /// Splits MSQ files into CDS files, or converts them to MIDI, like msqsplit.
+    Split(msqsplit::Args),
+}
+
+#[derive(Subcommand)]
+enum Mul {
+    /// Extracts the audio channels and data of MUL files, like demul.
+    Extract(demul::Args),
+}
+
+#[derive(Subcommand)]
+enum Mus {
+    /// Extracts the sequences, samples and instruments of a MUS and SAM file, like demus.
+    Extract(demus::Args),
+}
+
+#[derive(Subcommand)]
+enum Snd {
+    /// Extracts the sequences, samples and instruments of an SND and SMP file, like desnd.
+    Extract(desnd::Args),
+}
+
+#[derive(Subcommand)]
+enum Seq {
+    /// Converts SEQ files to MIDI, like seq2mid.
+    ToMid(seq2mid::Args),
+    /// Repeats the looped section of SEQ files, like seqrepeat.
+    Repeat(seqrepeat::Args),
+    /// Lists the events of CDS, MSQ or SEQ files, like seqdump.
+    Dump(seqdump::Args),
+    /// Assembles listings back into CDS, MSQ or SEQ files, like seqasm.
+    Asm(seqasm::Args),
+}
+
+#[derive(Subcommand)]
+enum Sf2 {
+    /// Changes the pan law of SF2Comp text files, like sf2panlaw.
+    PanLaw(sf2panlaw::Args),
+}
+
+#[derive(Subcommand)]
+enum Vab {
+    /// Extracts the samples of VAB files, or converts them to SF2 or DLS, like vabsmp.
+    Extract(vabsmp::Args),
+    /// Changes the finetuning scale of VAB files, like vabfine.
+    FineTune(vabfine::Args),
+}
+
+#[derive(Subcommand)]
+enum Vag {
+    /// Adds a VAG header to a headerless stream or WAV file, like vagheader.
+    Header(vagheader::Args),
+    /// Prints the header, length and loop points of VAG files, like vaginfo.
+    Info(vaginfo::Args),
+    /// Fixes the bad shift and filter bytes of VAG files, like vagsanitizer.
+    Sanitize(vagsanitizer::Args),
+    /// Removes or sets the loop markers of VAG files, like vagunloop.
+    Unloop(vagunloop::Args),
+}
+
+fn main() {
+    core::init();
+
+    match Cli::parse().command {
+        Command::Identify(args) => identify::run(args),
+        Command::Ads(Ads::Header(args)) => adsheader::run(args),
+        Command::Ads(Ads::FindLoops(args)) => adsloopfind::run(args),
+        Command::Ads(Ads::Unloop(args)) => adsunloop::run(args),
+        Command::Cds(Cds::ToSeq(args)) => cds2seq::run(args),
+        Command::Msq(Msq::Split(args)) => msqsplit::run(args),
+        Command::Mul(Mul::Extract(args)) => demul::run(args),
+        Command::Mus(Mus::Extract(args)) => demus::run(args),
+        Command::Snd(Snd::Extract(args)) => desnd::run(args),
+        Command::Seq(Seq::ToMid(args)) => seq2mid::run(args),
+        Command::Seq(Seq::Repeat(args)) => seqrepeat::run(args),
+        Command::Seq(Seq::Dump(args)) => seqdump::run(args),
+        Command::Seq(Seq::Asm(args)) => seqasm::run(args),
+        Command::Sf2(Sf2::PanLaw(args)) => sf2panlaw::run(args),
+        Command::Vab(Vab::Extract(args)) => vabsmp::run(args),
+        Command::Vab(Vab::FineTune(args)) => vabfine::run(args),
+        Command::Vag(Vag::Header(args)) => vagheader::run(args),
+        Command::Vag(Vag::Info(args)) => vaginfo::run(args),
+        Command::Vag(Vag::Sanitize(args)) => vagsanitizer::run(args),
+        Command::Vag(Vag::Unloop(args)) => vagunloop::run(args),
+    }
+}
+
+#[test]
+fn commands() {
+    use clap::CommandFactory;
+
+    Cli::command().debug_assert();
+    let cli = Cli::try_parse_from(["unlokable", "vag", "unloop", "music", "-r", "-j", "2"]);
+    assert!(matches!(
+        cli,
+        Ok(Cli {
+            command: Command::Vag(Vag::Unloop(_))
+        })
+    ));
+    assert!(Cli::try_parse_from(["unlokable", "vag", "split", "music"]).is_err());
+
+    // `-o` is only ever the output path.
+    let parses = |args: &[&str]| Cli::try_parse_from(["unlokable"].iter().chain(args)).is_ok();
+    assert!(!parses(&["ads", "find-loops", "a.ads", "-o", "loops.txt"]));
+    assert!(parses(&[
+        "ads",
+        "find-loops",
+        "a.ads",
+        "--list-file",
+        "loops.txt"
+    ]));
+    assert!(!parses(&["seq", "dump", "a.seq", "-o", "a.txt"]));
+    assert!(parses(&["seq", "dump", "a.seq", "--list-file", "a.txt"]));
+    assert!(!parses(&["snd", "extract", "a.snd", "a.smp", "-f", "gex"]));
+    assert!(parses(&[
+        "snd",
+        "extract",
+        "a.snd",
+        "a.smp",
+        "--snd-version",
+        "gex"
+    ]));
+}
@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+
+use core::{
+    batch::JobArgs,
+    clap::{self, Parser},
+    input::InputArgs,
+    log::{error, info},
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    vab::VabFile,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    vab_path: PathBuf,
+    /// DEFAULT
+    #[clap(long)]
+    cents: bool,
+    #[clap(long)]
+    psx: bool,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+
+    let output = Output::new(&args.output, &args.vab_path);
+    let files = core::get_files(&args.vab_path, &args.files, &["vab"]);
+    core::batch::run(files, args.batch.jobs, |file| {
+        fine_tune(file, args.psx, &output);
+    });
+}
+
+fn fine_tune(path: &Path, psx: bool, output: &Output) {
+    info!("Handling {path:?}");
+    let mut file = match std::fs::read(path) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("Unable to open file {path:?}: {e}");
+            return;
+        }
+    };
+
+    if let Err(e) = convert_file(&mut file, psx) {
+        error!("Unable to parse VAB file {path:?}: {e}");
+        return;
+    };
+
+    let path_stem = path.with_extension("");
+    let out_path = if psx {
+        format!("{}_psx.vab", path_stem.to_string_lossy())
+    } else {
+        format!("{}_cents.vab", path_stem.to_string_lossy())
+    };
+    let Some(out_path) = output.file(Path::new(&out_path)) else {
+        return;
+    };
+    if let Err(e) = std::fs::write(&out_path, file) {
+        error!("Unable to write {out_path:?}: {e}");
+    }
+}
+
+/// Converts the pitch tune of every tone in the file, returning the converted bank.
+fn convert_file(file: &mut Vec<u8>, psx: bool) -> core::Result<VabFile> {
+    let mut vab = VabFile::parse(file)?;
+    for tone in vab.tones.iter_mut().flatten() {
+        tone.pitch_tune = convert(tone.pitch_tune, psx);
+    }
+
+    let pitch_finetunings = vab.tones.iter().map(Vec::len).sum::<usize>();
+    let nonzero_finetunings = vab
+        .tones
+        .iter()
+        .map(|tones| tones.iter().filter(|t| t.pitch_tune != 0).count())
+        .sum::<usize>();
+
+    info!("Tones found: {pitch_finetunings}");
+    info!("Changed Non-zero Pitch Finetunings: {nonzero_finetunings}");
+    report::set("header", &vab.header);
+    report::set("tones", pitch_finetunings);
+    report::set("changed_tones", nonzero_finetunings);
+
+    vab.write_header(file);
+    Ok(vab)
+}
+
+fn convert(byte: u8, psx: bool) -> u8 {
+    if psx {
+        ((byte as f32) * 128.0 / 100.0).round() as u8
+    } else {
+        ((byte as f32) * 100.0 / 128.0).round() as u8
+    }
+}
+
+#[test]
+fn test_cents() {
+    assert_eq!(convert(0, false), 0);
+    assert_eq!(convert(127, false), 99);
+}
+
+#[test]
+fn test_psx() {
+    assert_eq!(convert(0, true), 0);
+    assert_eq!(convert(99, true), 127);
+}
+
+#[test]
+fn cents_parsing() {
+    let mut file = include_bytes!("../tests/test.vab").to_vec();
+    let vab = convert_file(&mut file, false).unwrap();
+
+    assert_eq!(vab.tones[1][1].pitch_tune, 56);
+    assert_eq!(vab.tones[1][2].pitch_tune, 19);
+    assert_eq!(vab.tones[1][15].pitch_tune, 27);
+    assert_eq!(vab.tones.iter().map(std::vec::Vec::len).sum::<usize>(), 17);
+    assert_eq!(VabFile::parse(&file).unwrap().tones[1][1].pitch_tune, 56);
+}
+
+#[test]
+fn psx_parsing() {
+    let mut file = include_bytes!("../tests/test.vab").to_vec();
+    let vab = convert_file(&mut file, true).unwrap();
+
+    assert_eq!(vab.tones[1][1].pitch_tune, 92);
+    assert_eq!(vab.tones[1][2].pitch_tune, 31);
+    assert_eq!(vab.tones[1][15].pitch_tune, 45);
+    assert_eq!(vab.tones.iter().map(std::vec::Vec::len).sum::<usize>(), 17);
+}
@@ -0,0 +1,541 @@
+use std::{
+    num::NonZeroU32,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use core::{
+    adpcm::{self, Decoded},
+    ads::{self, AdsHeader},
+    adsr::Envelope,
+    batch::JobArgs,
+    clap::{self, Parser},
+    dls::{self, Articulation, Dls},
+    input::InputArgs,
+    log::{error, info},
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    serde_json::json,
+    sf2::{self, Generator, SoundFont},
+    vab::{Program, Tone, VabFile},
+    vag::VagHeader,
+    wav::Wav,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    vab_path: PathBuf,
+    sample_rate: NonZeroU32,
+    /// DEFAULT
+    #[clap(long)]
+    vag: bool,
+    #[clap(long)]
+    ads: bool,
+    /// Decode the samples to WAV files, with the loop points and root key in a `smpl` chunk.
+    #[clap(long)]
+    wav: bool,
+    /// Convert the whole bank to a SoundFont next to the VAB instead of exporting the samples.
+    #[clap(long)]
+    sf2: bool,
+    /// Convert the whole bank to a DLS next to the VAB instead of exporting the samples.
+    #[clap(long)]
+    dls: bool,
+    /// Which DLS level to write, defaults to level 2.
+    #[clap(long, value_enum)]
+    dls_level: Option<dls::Level>,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Vag,
+    Ads,
+    Wav,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Vag => "vag",
+            Format::Ads => "ads",
+            Format::Wav => "wav",
+        }
+    }
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+
+    let format = if args.wav {
+        Format::Wav
+    } else if args.ads {
+        Format::Ads
+    } else {
+        Format::Vag
+    };
+    let output = Output::new(&args.output, &args.vab_path);
+    let files = core::get_files(&args.vab_path, &args.files, &["vab"]);
+    core::batch::run(files, args.batch.jobs, |file| {
+        if args.sf2 || args.dls {
+            convert_bank(
+                file,
+                args.sample_rate,
+                args.sf2,
+                args.dls.then(|| args.dls_level.unwrap_or_default()),
+                &output,
+            );
+        } else {
+            convert(file, args.sample_rate, format, &output);
+        }
+    });
+}
+
+fn convert_bank(
+    path: &Path,
+    sample_rate: NonZeroU32,
+    sf2: bool,
+    dls: Option<dls::Level>,
+    output: &Output,
+) {
+    info!("Reading {path:?}");
+    let file = match std::fs::read(path) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("Unable to open file: {e}");
+            return;
+        }
+    };
+    let vab_file = match VabFile::parse(&file) {
+        Ok(vab_file) => vab_file,
+        Err(e) => {
+            error!("Unable to parse VAB file {path:?}: {e}");
+            return;
+        }
+    };
+    info!("Samples found: {}", vab_file.header.vags_number);
+    report_bank(&vab_file, &file);
+
+    let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+    let extension = if sf2 { "sf2" } else { "dls" };
+    let Some(main_path) = output.file(&path.with_extension(extension)) else {
+        return;
+    };
+    if sf2 {
+        let mut bytes = vec![];
+        soundfont_from_vab(&vab_file, &file, sample_rate, name.clone()).write(&mut bytes);
+        if let Err(e) = std::fs::write(&main_path, bytes) {
+            error!("Unable to write SoundFont {main_path:?}: {e}");
+        }
+    }
+    if let Some(level) = dls {
+        let output_path = if sf2 {
+            output.extra_file(&main_path, "dls")
+        } else {
+            Some(main_path)
+        };
+        let Some(output_path) = output_path else {
+            return;
+        };
+        let mut bytes = vec![];
+        dls_from_vab(&vab_file, &file, sample_rate, name).write(level, &mut bytes);
+        if let Err(e) = std::fs::write(&output_path, bytes) {
+            error!("Unable to write DLS {output_path:?}: {e}");
+        }
+    }
+}
+
+/// Converts a pan from the 0-127 range of VAB files to a fraction from -1 to 1.
+fn pan_fraction(pan: u8) -> f32 {
+    ((pan as f32 - 64.0) / 64.0).clamp(-1.0, 1.0)
+}
+
+/// A tone converted to the units shared by SoundFonts and DLS.
+struct Zone {
+    sample_index: usize,
+    key_range: (u8, u8),
+    unity_key: u8,
+    /// Tuning in cents.
+    fine_tune: i32,
+    pan: f32,
+    /// Attenuation in centibels.
+    attenuation: i32,
+    envelope: Envelope,
+}
+
+/// Converts the tones of a program, skipping the ones without a sample.
+///
+/// Both formats pan with a -3.01 dB law while the SPU pans linearly, so
+/// panned zones are attenuated to match.
+fn program_zones(program: &Program, tones: &[Tone], num_samples: usize) -> Vec<Zone> {
+    tones
+        .iter()
+        .filter_map(|tone| {
+            let sample_index = (tone.sample_number as usize)
+                .checked_sub(1)
+                .filter(|index| *index < num_samples)?;
+            let pan = (pan_fraction(tone.pan) + pan_fraction(program.pan)).clamp(-1.0, 1.0);
+            let volume = (tone.volume as f32 / 127.0) * (program.volume as f32 / 127.0);
+
+            Some(Zone {
+                sample_index,
+                key_range: (tone.key_low, tone.key_high),
+                unity_key: tone.unity_key,
+                fine_tune: (tone.pitch_tune as f32 * 100.0 / 128.0).round() as i32,
+                pan,
+                attenuation: sf2::centibels(volume) + (pan.abs() * 30.1) as i32,
+                envelope: Envelope::from_registers(tone.adsr1, tone.adsr2),
+            })
+        })
+        .collect()
+}
+
+fn decode_samples(vab_file: &VabFile, file: &[u8]) -> Vec<Decoded> {
+    vab_file
+        .vag_ranges
+        .iter()
+        .map(|range| adpcm::decode(&file[range.clone()]))
+        .collect()
+}
+
+fn loop_range(decoded: &Decoded) -> Option<Range<u32>> {
+    decoded
+        .loop_start
+        .zip(decoded.loop_end)
+        .map(|(start, end)| start as u32..end as u32)
+}
+
+/// Builds a SoundFont with a preset and instrument for each program.
+fn soundfont_from_vab(
+    vab_file: &VabFile,
+    file: &[u8],
+    sample_rate: NonZeroU32,
+    name: String,
+) -> SoundFont {
+    let samples = decode_samples(vab_file, file)
+        .into_iter()
+        .enumerate()
+        .map(|(index, decoded)| sf2::Sample {
+            name: format!("{name}_{index:04}"),
+            loop_range: loop_range(&decoded),
+            samples: decoded.samples,
+            sample_rate: sample_rate.get(),
+            original_key: 60,
+            correction: 0,
+        })
+        .collect::<Vec<_>>();
+
+    let mut instruments = vec![];
+    let mut presets = vec![];
+    for (program, tones) in vab_file.programs.iter().zip(&vab_file.tones) {
+        let zones = program_zones(program, tones, samples.len())
+            .into_iter()
+            .map(|zone| {
+                let mut sf2_zone = sf2::Zone::new(zone.sample_index as u16);
+                sf2_zone.push_range(Generator::KeyRange, zone.key_range.0, zone.key_range.1);
+                sf2_zone.push(Generator::OverridingRootKey, zone.unity_key as i32);
+                sf2_zone.push(Generator::FineTune, zone.fine_tune);
+                sf2_zone.push(Generator::Pan, (zone.pan * 500.0) as i32);
+                sf2_zone.push(Generator::InitialAttenuation, zone.attenuation);
+                sf2_zone.push(
+                    Generator::AttackVolEnv,
+                    sf2::timecents(zone.envelope.attack),
+                );
+                sf2_zone.push(Generator::DecayVolEnv, sf2::timecents(zone.envelope.decay));
+                sf2_zone.push(
+                    Generator::SustainVolEnv,
+                    sf2::centibels(zone.envelope.sustain_level),
+                );
+                sf2_zone.push(
+                    Generator::ReleaseVolEnv,
+                    sf2::timecents(zone.envelope.release),
+                );
+                sf2_zone.push(
+                    Generator::SampleModes,
+                    samples[zone.sample_index].loop_range.is_some() as i32,
+                );
+                sf2_zone
+            })
+            .collect();
+
+        let mut preset_zone = sf2::Zone::new(instruments.len() as u16);
+        preset_zone.push_range(Generator::KeyRange, 0, 127);
+        presets.push(sf2::Preset {
+            name: format!("Program {}", program.index),
+            bank: 0,
+            program: program.index as u16,
+            zones: vec![preset_zone],
+        });
+        instruments.push(sf2::Instrument {
+            name: format!("Program {}", program.index),
+            zones,
+        });
+    }
+
+    SoundFont {
+        name,
+        engine: "EMU8000".to_owned(),
+        software: "vabsmp".to_owned(),
+        samples,
+        instruments,
+        presets,
+    }
+}
+
+/// Builds a DLS bank with an instrument for each program, all in bank 0.
+fn dls_from_vab(vab_file: &VabFile, file: &[u8], sample_rate: NonZeroU32, name: String) -> Dls {
+    let samples = decode_samples(vab_file, file)
+        .into_iter()
+        .enumerate()
+        .map(|(index, decoded)| dls::Sample {
+            name: format!("{name}_{index:04}"),
+            loop_range: loop_range(&decoded),
+            samples: decoded.samples,
+            sample_rate: sample_rate.get(),
+        })
+        .collect::<Vec<_>>();
+
+    let instruments = vab_file
+        .programs
+        .iter()
+        .zip(&vab_file.tones)
+        .map(|(program, tones)| dls::Instrument {
+            name: format!("Program {}", program.index),
+            bank: 0,
+            program: program.index,
+            regions: program_zones(program, tones, samples.len())
+                .into_iter()
+                .map(|zone| dls::Region {
+                    key_range: zone.key_range,
+                    velocity_range: (0, 127),
+                    sample: zone.sample_index as u32,
+                    unity_note: zone.unity_key,
+                    fine_tune: zone.fine_tune as i16,
+                    attenuation: zone.attenuation,
+                    looped: samples[zone.sample_index].loop_range.is_some(),
+                    articulation: Articulation {
+                        attack: zone.envelope.attack,
+                        decay: zone.envelope.decay,
+                        sustain_level: zone.envelope.sustain_level,
+                        release: zone.envelope.release,
+                        pan: zone.pan,
+                    },
+                })
+                .collect(),
+        })
+        .collect();
+
+    Dls {
+        name,
+        samples,
+        instruments,
+    }
+}
+
+/// Exports the samples of one VAB file as VAG files, as the tool does by
+/// default.
+pub fn convert_file(path: &Path, sample_rate: NonZeroU32, output: &Output) {
+    convert(path, sample_rate, Format::Vag, output);
+}
+
+fn convert(path: &Path, sample_rate: NonZeroU32, format: Format, output: &Output) {
+    info!("Reading {path:?}");
+    let file = match std::fs::read(path) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("Unable to open file: {e}");
+            return;
+        }
+    };
+    let vab_file = match VabFile::parse(&file) {
+        Ok(vab_file) => vab_file,
+        Err(e) => {
+            error!("Unable to parse VAB file {path:?}: {e}");
+            return;
+        }
+    };
+    info!("Samples found: {}", vab_file.header.vags_number);
+    report_bank(&vab_file, &file);
+
+    let Some(output_path) = output.dir(&path.with_extension("")) else {
+        return;
+    };
+
+    let samples = create_samples(&vab_file, &file, sample_rate, format);
+    for (index, sample) in samples.iter().enumerate() {
+        let path = output_path.join(format!(
+            "{}_{index:04}.{}",
+            output_path.file_name().unwrap().to_string_lossy(),
+            format.extension()
+        ));
+        if let Err(e) = std::fs::write(&path, sample) {
+            error!("Unable to write output file {path:?}: {e}");
+        }
+    }
+}
+
+fn create_samples(
+    vab_file: &VabFile,
+    file: &[u8],
+    sample_rate: NonZeroU32,
+    format: Format,
+) -> Vec<Vec<u8>> {
+    vab_file
+        .vag_ranges
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(index, range)| {
+            let mut sample = vec![];
+            match format {
+                Format::Ads => AdsHeader {
+                    codec: ads::CODEC_VAG,
+                    sample_rate: sample_rate.get(),
+                    channels: 1,
+                    interleave: 0,
+                    loop_start: None,
+                    loop_end: None,
+                    body_size: range.len() as u32,
+                }
+                .write(&mut sample),
+                Format::Vag => {
+                    VagHeader::new(range.len() as u32, sample_rate.get()).write(&mut sample);
+                }
+                Format::Wav => {
+                    let unity_note = unity_note(vab_file, index).unwrap_or(60);
+                    Wav::from_adpcm(&file[range], sample_rate.get(), unity_note).write(&mut sample);
+                    return sample;
+                }
+            }
+            sample.extend_from_slice(&file[range]);
+            sample
+        })
+        .collect()
+}
+
+/// Adds the header and the samples of the bank to the report.
+fn report_bank(vab_file: &VabFile, file: &[u8]) {
+    if !report::enabled() {
+        return;
+    }
+    report::set("header", &vab_file.header);
+    let samples = vab_file
+        .vag_ranges
+        .iter()
+        .zip(decode_samples(vab_file, file));
+    for (range, decoded) in samples {
+        report::push(
+            "samples",
+            json!({
+                "offset": range.start,
+                "size": range.len(),
+                "length": decoded.samples.len(),
+                "loop_start": decoded.loop_start,
+                "loop_end": decoded.loop_end,
+            }),
+        );
+    }
+}
+
+/// The root key of the first tone that plays the sample at `index`.
+fn unity_note(vab_file: &VabFile, index: usize) -> Option<u8> {
+    vab_file
+        .tones
+        .iter()
+        .flatten()
+        .find(|tone| tone.sample_number as usize == index + 1)
+        .map(|tone| tone.unity_key)
+}
+
+#[test]
+fn test_file() {
+    let vab = include_bytes!("../tests/test.vab");
+    let vab_file = VabFile::parse(vab).unwrap();
+    assert_eq!(vab_file.header.total_size, vab.len() as u32);
+    assert_eq!(vab_file.tones.len(), 2);
+}
+
+#[test]
+fn test_conversion() {
+    let vab = include_bytes!("../tests/test.vab");
+    let vab_file = VabFile::parse(vab).unwrap();
+    let samples = create_samples(&vab_file, vab, NonZeroU32::new(22050).unwrap(), Format::Vag);
+    assert_eq!(samples[0].len(), 25264);
+    assert_eq!(samples[1].len(), 15968);
+}
+
+#[test]
+fn test_wav() {
+    let vab = include_bytes!("../tests/test.vab");
+    let vab_file = VabFile::parse(vab).unwrap();
+    let samples = create_samples(&vab_file, vab, NonZeroU32::new(22050).unwrap(), Format::Wav);
+    assert_eq!(&samples[0][0..4], b"RIFF");
+    assert_eq!(&samples[0][8..12], b"WAVE");
+    assert_eq!(
+        unity_note(&vab_file, 0),
+        Some(vab_file.tones[0][0].unity_key)
+    );
+}
+
+#[test]
+fn test_sf2() {
+    let vab = include_bytes!("../tests/test.vab");
+    let vab_file = VabFile::parse(vab).unwrap();
+    let soundfont = soundfont_from_vab(
+        &vab_file,
+        vab,
+        NonZeroU32::new(22050).unwrap(),
+        "test".to_owned(),
+    );
+    assert_eq!(soundfont.samples.len(), vab_file.vag_ranges.len());
+    assert_eq!(soundfont.presets.len(), vab_file.programs.len());
+    assert_eq!(
+        soundfont
+            .instruments
+            .iter()
+            .map(|instrument| instrument.zones.len())
+            .sum::<usize>(),
+        17
+    );
+
+    let tone = &vab_file.tones[1][1];
+    let zone = &soundfont.instruments[1].zones[1];
+    assert_eq!(tone.pitch_tune, 72);
+    assert!(zone.generators.contains(&(Generator::FineTune, 56)));
+    assert!(zone
+        .generators
+        .contains(&(Generator::OverridingRootKey, tone.unity_key as i16)));
+}
+
+#[test]
+fn test_dls() {
+    let vab = include_bytes!("../tests/test.vab");
+    let vab_file = VabFile::parse(vab).unwrap();
+    let dls = dls_from_vab(
+        &vab_file,
+        vab,
+        NonZeroU32::new(22050).unwrap(),
+        "test".to_owned(),
+    );
+    assert_eq!(dls.samples.len(), vab_file.vag_ranges.len());
+    assert_eq!(
+        dls.instruments
+            .iter()
+            .map(|instrument| instrument.program)
+            .collect::<Vec<_>>(),
+        vab_file
+            .programs
+            .iter()
+            .map(|program| program.index)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(dls.instruments[1].regions[1].fine_tune, 56);
+}
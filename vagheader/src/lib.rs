@@ -0,0 +1,268 @@
+use std::{
+    num::{NonZeroU32, NonZeroU8},
+    path::PathBuf,
+};
+
+use core::{
+    adpcm,
+    batch::JobArgs,
+    clap::{self, Parser},
+    input::InputArgs,
+    log::{error, info},
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    vag::{self, VagHeader},
+    wav::Wav,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    input: PathBuf,
+    /// Required for raw input, overrides the rate of WAV input.
+    #[clap(required_unless_present = "wav")]
+    sample_rate: Option<NonZeroU32>,
+    #[clap(long)]
+    long: bool,
+    #[clap(long)]
+    short: bool,
+    /// Encode 16-bit WAV files, looping them if they have a `smpl` chunk.
+    #[clap(long)]
+    wav: bool,
+    /// Number of interleaved channels in raw input, WAV input uses its own.
+    #[clap(long, default_value = "1")]
+    channels: NonZeroU8,
+    /// Bytes of each channel before the next one's, rounded up to whole blocks.
+    #[clap(long, default_value_t = vag::DEFAULT_INTERLEAVE as u32)]
+    interleave: u32,
+    /// Write a `VAGi` header, which is padded to 0x800 bytes and needs at least two channels.
+    #[clap(long, conflicts_with = "short")]
+    interleaved: bool,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+    let output = Output::new(&args.output, &args.input);
+
+    let extensions: &[&str] = if args.wav { &["wav"] } else { &[] };
+    let files = core::get_files(&args.input, &args.files, extensions);
+    core::batch::run(files, args.batch.jobs, |file_path| {
+        info!("Handling {file_path:?}");
+        let file = match std::fs::read(file_path) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("Unable to open file: {e}");
+                return;
+            }
+        };
+
+        let new_file = if args.wav {
+            match encode_wav(&file, &args) {
+                Ok(new_file) => new_file,
+                Err(e) => {
+                    error!("Unable to encode {file_path:?}, skipping: {e}");
+                    return;
+                }
+            }
+        } else {
+            match add_header(
+                &file,
+                args.sample_rate.unwrap().get(),
+                args.channels.get(),
+                &args,
+            ) {
+                Ok(new_file) => new_file,
+                Err(e) => {
+                    error!("Unable to add a header to {file_path:?}, skipping: {e}");
+                    return;
+                }
+            }
+        };
+
+        let Some(output_path) = output.file(&file_path.with_extension("vag")) else {
+            return;
+        };
+        if let Err(e) = std::fs::write(&output_path, new_file) {
+            error!("Unable to write output {output_path:?}: {e}");
+        }
+    });
+}
+
+fn encode_wav(file: &[u8], args: &Args) -> core::Result<Vec<u8>> {
+    let wav = Wav::parse(file)?;
+    if wav.channels == 0 || wav.channels > u8::MAX as u16 {
+        return Err(core::Error::Unsupported {
+            format: "WAV",
+            feature: "only up to 255 channels can be encoded",
+        });
+    }
+    let loop_range = wav
+        .sampler
+        .as_ref()
+        .and_then(|sampler| sampler.loops.first())
+        .map(|sample_loop| sample_loop.start as usize..sample_loop.end as usize + 1);
+    let sample_rate = args
+        .sample_rate
+        .map_or(wav.sample_rate, |sample_rate| sample_rate.get());
+
+    let channels = (0..wav.channels as usize)
+        .map(|channel| {
+            let samples = wav
+                .samples
+                .iter()
+                .skip(channel)
+                .step_by(wav.channels as usize)
+                .copied()
+                .collect::<Vec<_>>();
+            adpcm::encode(&samples, loop_range.clone())
+        })
+        .collect::<Vec<_>>();
+    let body = match channels.as_slice() {
+        [body] => body.clone(),
+        _ => adpcm::interleave(&channels, interleave(args) as usize),
+    };
+
+    add_header(&body, sample_rate, wav.channels as u8, args)
+}
+
+/// The interleave of the header, in whole blocks.
+fn interleave(args: &Args) -> u32 {
+    args.interleave
+        .max(1)
+        .next_multiple_of(vag::BLOCK_SIZE as u32)
+}
+
+fn add_header(file: &[u8], sample_rate: u32, channels: u8, args: &Args) -> core::Result<Vec<u8>> {
+    if args.interleaved && channels < 2 {
+        return Err(core::Error::Unsupported {
+            format: "VAGi",
+            feature: "interleaved files need at least two channels",
+        });
+    }
+    let mut new_file = vec![];
+    let header = VagHeader {
+        magic: if args.interleaved {
+            vag::MAGIC_INTERLEAVED
+        } else {
+            vag::MAGIC
+        },
+        // Mono files keep these fields zeroed like the usual header.
+        interleave: if channels > 1 { interleave(args) } else { 0 },
+        channels: if channels > 1 { channels } else { 0 },
+        ..VagHeader::new(file.len() as u32, sample_rate)
+    };
+    report::set("header", &header);
+    header.write(&mut new_file);
+    if args.interleaved {
+        new_file.resize(vag::INTERLEAVED_START, 0);
+    } else if !args.short {
+        new_file.extend_from_slice(&[0; 16]);
+    }
+    new_file.extend_from_slice(file);
+    Ok(new_file)
+}
+
+/// The arguments of a test, with only the options it needs.
+#[cfg(test)]
+fn args(options: &[&str]) -> Args {
+    Args::parse_from(["vagheader", "input"].iter().chain(options))
+}
+
+#[test]
+fn short() {
+    let file = include_bytes!("../tests/silence.bin");
+    let vag = add_header(file, 22100, 1, &args(&["22100", "--short"])).unwrap();
+    assert_eq!(vag.len(), file.len() + 48);
+}
+
+#[test]
+fn long() {
+    let file = include_bytes!("../tests/silence.bin");
+    let vag = add_header(file, 22100, 1, &args(&["22100", "--long"])).unwrap();
+    assert_eq!(vag.len(), file.len() + 64);
+}
+
+#[test]
+fn wav() {
+    let mut file = vec![];
+    Wav {
+        sample_rate: 32000,
+        channels: 1,
+        samples: vec![0x100; 100],
+        sampler: Some(core::wav::Sampler {
+            unity_note: 60,
+            loops: vec![core::wav::Loop { start: 28, end: 83 }],
+        }),
+    }
+    .write(&mut file);
+    let vag = encode_wav(&file, &args(&["--wav", "--short"])).unwrap();
+    let header = VagHeader::parse(&mut core::Reader::new(&vag)).unwrap();
+    assert_eq!(header.sample_rate, 32000);
+    assert_eq!(header.data_size, 3 * 16);
+
+    let decoded = adpcm::decode(&vag[48..]);
+    assert_eq!(decoded.loop_start, Some(28));
+    assert_eq!(decoded.loop_end, Some(84));
+}
+
+#[test]
+fn stereo() {
+    let mut file = vec![];
+    Wav {
+        sample_rate: 44100,
+        channels: 2,
+        samples: [0x100, -0x100].repeat(100),
+        sampler: Some(core::wav::Sampler {
+            unity_note: 60,
+            loops: vec![core::wav::Loop { start: 28, end: 83 }],
+        }),
+    }
+    .write(&mut file);
+    let vag = encode_wav(
+        &file,
+        &args(&["--wav", "--interleave", "20", "--interleaved"]),
+    )
+    .unwrap();
+    let header = VagHeader::parse(&mut core::Reader::new(&vag)).unwrap();
+    assert_eq!(header.magic, vag::MAGIC_INTERLEAVED);
+    assert_eq!((header.channels, header.interleave), (2, 32));
+    assert_eq!(header.data_size, 4 * 32);
+    assert_eq!(vag.len(), vag::INTERLEAVED_START + 4 * 32);
+
+    let layout = vag::Layout::detect(&header, &vag).unwrap();
+    let decoded = adpcm::decode_interleaved(&vag[layout.start..], 2, layout.interleave);
+    for (channel, sample) in decoded.iter().zip([0x100, -0x100]) {
+        assert_eq!(channel.loop_start, Some(28));
+        assert_eq!(channel.loop_end, Some(84));
+        assert!(channel.samples[50].abs_diff(sample) < 0x10);
+    }
+}
+
+#[test]
+fn interleaved_mono() {
+    let file = include_bytes!("../tests/silence.bin");
+    assert!(add_header(file, 22050, 1, &args(&["22050", "--interleaved"])).is_err());
+
+    let mut wav = vec![];
+    Wav {
+        sample_rate: 22050,
+        channels: 1,
+        samples: vec![0; 100],
+        sampler: None,
+    }
+    .write(&mut wav);
+    assert!(encode_wav(&wav, &args(&["--wav", "--interleaved"])).is_err());
+
+    let args = args(&["22050", "--interleaved", "--channels", "2"]);
+    let vag = add_header(file, 22050, 2, &args).unwrap();
+    assert_eq!(vag.len(), vag::INTERLEAVED_START + file.len());
+}
@@ -0,0 +1,231 @@
+use std::{fmt::Write, path::PathBuf};
+
+use core::{
+    adpcm,
+    batch::JobArgs,
+    clap::{self, Parser},
+    input::InputArgs,
+    log::{error, info, warn},
+    report::{self, ReportArgs},
+    vag::{self, Layout, VagHeader},
+    Reader,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// The `vag` file, or folder of them, to inspect.
+    input: PathBuf,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// What was found in a VAG file.
+#[derive(Debug, PartialEq, Eq)]
+struct Info {
+    header: VagHeader,
+    layout: Layout,
+    /// Size of the data after the header, which the header should agree with.
+    body_size: usize,
+    blocks: usize,
+    /// Samples of the first channel played before the end flag, or until the
+    /// end of the data.
+    samples: usize,
+    loop_start: Option<usize>,
+    loop_end: Option<usize>,
+    /// Block of the first channel with the end flag.
+    end_block: Option<usize>,
+    /// Whether the other channels play as long and loop the same way.
+    channels_match: bool,
+}
+
+impl Info {
+    /// The header fields and what the blocks hold, one per line.
+    fn describe(&self) -> String {
+        let name = self.header.name.split(|byte| *byte == 0).next().unwrap();
+        let mut text = String::new();
+        writeln!(text, "Version: {}", self.header.version).unwrap();
+        writeln!(text, "Data size: {} bytes", self.header.data_size).unwrap();
+        writeln!(text, "Sample rate: {} Hz", self.header.sample_rate).unwrap();
+        writeln!(text, "Name: {}", String::from_utf8_lossy(name)).unwrap();
+        writeln!(text, "Channels: {}", self.layout.channels).unwrap();
+        writeln!(text, "Data start: {:#x}", self.layout.start).unwrap();
+        if self.layout.channels > 1 {
+            writeln!(text, "Interleave: {:#x} bytes", self.layout.interleave).unwrap();
+        }
+        writeln!(text, "Blocks: {}", self.blocks).unwrap();
+        writeln!(text, "Samples: {}", self.samples).unwrap();
+        writeln!(text, "Duration: {:.3} s", self.duration()).unwrap();
+        match (self.loop_start, self.loop_end) {
+            (Some(start), Some(end)) => writeln!(text, "Loop: samples {start} to {end}").unwrap(),
+            _ => writeln!(text, "Loop: none").unwrap(),
+        }
+        match self.end_block {
+            Some(block) => write!(
+                text,
+                "End flag: block {block}, offset {:#x}",
+                self.layout.offset(0, block)
+            )
+            .unwrap(),
+            None => write!(text, "End flag: none").unwrap(),
+        }
+        text
+    }
+
+    fn parse(file: &[u8]) -> core::Result<Self> {
+        let header = VagHeader::parse(&mut Reader::new(file))?;
+        let layout = Layout::detect(&header, file)?;
+        let body = &file[layout.start..];
+        let channels = layout.deinterleave(file);
+        let decoded = channels
+            .iter()
+            .map(|body| adpcm::decode(body))
+            .collect::<Vec<_>>();
+        let first = &decoded[0];
+        Ok(Self {
+            header,
+            layout,
+            body_size: body.len(),
+            blocks: body.len() / vag::BLOCK_SIZE,
+            samples: first.samples.len(),
+            loop_start: first.loop_start,
+            loop_end: first.loop_end,
+            end_block: adpcm::end_block(&channels[0]),
+            channels_match: decoded.iter().all(|channel| {
+                (channel.samples.len(), channel.loop_start, channel.loop_end)
+                    == (first.samples.len(), first.loop_start, first.loop_end)
+            }),
+        })
+    }
+
+    /// Length in seconds when played at the sample rate of the header.
+    fn duration(&self) -> f64 {
+        self.samples as f64 / self.header.sample_rate.max(1) as f64
+    }
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+
+    let files = core::get_files(&args.input, &args.files, &["vag"]);
+    core::batch::run(files, args.batch.jobs, |file_path| {
+        let file = match std::fs::read(file_path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Unable to read {file_path:?}: {e}");
+                return;
+            }
+        };
+        let info = match Info::parse(&file) {
+            Ok(info) => info,
+            Err(e) => {
+                error!("Unable to parse VAG file {file_path:?}: {e}");
+                return;
+            }
+        };
+
+        if info.header.data_size as usize != info.body_size {
+            warn!(
+                "{file_path:?} has {} bytes of data, but its header says {}",
+                info.body_size, info.header.data_size
+            );
+        }
+        if !info.channels_match {
+            warn!("The channels of {file_path:?} don't all play and loop like the first one");
+        }
+
+        report::set("header", &info.header);
+        report::set("layout", info.layout);
+        report::set("body_size", info.body_size);
+        report::set("blocks", info.blocks);
+        report::set("samples", info.samples);
+        report::set("duration", info.duration());
+        report::set("loop_start", info.loop_start);
+        report::set("loop_end", info.loop_end);
+        report::set("end_block", info.end_block);
+        report::set("channels_match", info.channels_match);
+        if report::enabled() {
+            return;
+        }
+
+        info!("{}\n{}", file_path.display(), info.describe());
+    });
+}
+
+#[test]
+fn inspection() {
+    let mut body = vec![0; 5 * vag::BLOCK_SIZE];
+    body[vag::BLOCK_SIZE + 1] = vag::FLAG_LOOP_START;
+    body[3 * vag::BLOCK_SIZE + 1] = vag::FLAG_LOOP_END;
+    body[4 * vag::BLOCK_SIZE + 1] = vag::FLAG_END;
+    let mut file = vec![];
+    VagHeader::new(body.len() as u32, 14).write(&mut file);
+    file.extend(&body);
+
+    let info = Info::parse(&file).unwrap();
+    assert_eq!(info.body_size, 80);
+    assert_eq!(info.blocks, 5);
+    assert_eq!(info.samples, 4 * vag::SAMPLES_PER_BLOCK);
+    assert_eq!(info.duration(), 8.0);
+    assert_eq!(info.loop_start, Some(vag::SAMPLES_PER_BLOCK));
+    assert_eq!(info.loop_end, Some(4 * vag::SAMPLES_PER_BLOCK));
+    assert_eq!(info.end_block, Some(3));
+    let description = info.describe();
+    assert!(description.starts_with("Version: 3\n"));
+    assert!(description.contains("\nLoop: samples 28 to 112\n"));
+    assert!(description.ends_with("\nEnd flag: block 3, offset 0x60"));
+
+    assert_eq!(info.layout.start, vag::HEADER_SIZE);
+    assert!(Info::parse(&file[0..40]).is_err());
+
+    // The padding of a long header isn't part of the data.
+    file.splice(vag::HEADER_SIZE..vag::HEADER_SIZE, [0xff; vag::BLOCK_SIZE]);
+    assert_eq!(Info::parse(&file).unwrap().blocks, 6);
+    file[vag::HEADER_SIZE..vag::LONG_HEADER_SIZE].fill(0);
+    assert_eq!(
+        Info::parse(&file).unwrap(),
+        Info {
+            layout: Layout {
+                start: vag::LONG_HEADER_SIZE,
+                ..info.layout
+            },
+            ..info
+        }
+    );
+}
+
+#[test]
+fn stereo() {
+    let mut left = vec![0; 5 * vag::BLOCK_SIZE];
+    left[4 * vag::BLOCK_SIZE + 1] = vag::FLAG_END;
+    let mut right = left.clone();
+    let body = adpcm::interleave(&[left.clone(), right.clone()], 2 * vag::BLOCK_SIZE);
+    let header = VagHeader {
+        channels: 2,
+        interleave: 2 * vag::BLOCK_SIZE as u32,
+        ..VagHeader::new(body.len() as u32, 28)
+    };
+    let mut file = vec![];
+    header.write(&mut file);
+    file.extend(&body);
+
+    let info = Info::parse(&file).unwrap();
+    assert_eq!(info.blocks, 12);
+    assert_eq!(info.samples, 4 * vag::SAMPLES_PER_BLOCK);
+    assert_eq!(info.end_block, Some(4));
+    assert_eq!(
+        info.layout.offset(0, 4),
+        vag::HEADER_SIZE + 8 * vag::BLOCK_SIZE
+    );
+    assert!(info.channels_match);
+
+    right[vag::BLOCK_SIZE + 1] = vag::FLAG_LOOP_START;
+    file.truncate(vag::HEADER_SIZE);
+    file.extend(adpcm::interleave(&[left, right], 2 * vag::BLOCK_SIZE));
+    assert!(!Info::parse(&file).unwrap().channels_match);
+}
@@ -0,0 +1,6 @@
+use core::clap::Parser;
+
+fn main() {
+    core::init();
+    vaginfo::run(vaginfo::Args::parse());
+}
@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+
+use core::{
+    batch::JobArgs,
+    clap::{self, Parser},
+    input::InputArgs,
+    log::{error, info},
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    vag::{self, Layout, VagHeader},
+    Reader,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// The `vag` file to read from.
+    input: PathBuf,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+    let output = Output::new(&args.output, &args.input);
+
+    let files = core::get_files(&args.input, &args.files, &["vag"]);
+    core::batch::run(files, args.batch.jobs, |file_path| {
+        info!("Sanitizing {file_path:?}");
+        let mut vag_bytes = match std::fs::read(file_path) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Unable to read file: {e}");
+                return;
+            }
+        };
+        let layout = match VagHeader::parse(&mut Reader::new(&vag_bytes))
+            .and_then(|header| Layout::detect(&header, &vag_bytes))
+        {
+            Ok(layout) => layout,
+            Err(e) => {
+                error!("Invalid header, skipping: {e}");
+                return;
+            }
+        };
+        report::set("layout", layout);
+        let changed = sanitized(&mut vag_bytes, &layout);
+        report::set("fixed_chunks", changed);
+
+        if changed != 0 {
+            info!("Fixed {changed} bad chunks");
+            let Some(out_path) = output.file(&clean_path(file_path)) else {
+                return;
+            };
+            if let Err(e) = std::fs::write(&out_path, &vag_bytes) {
+                error!("Unable to write {out_path:?}: {e}");
+            }
+        } else {
+            info!("No bad chunks were found!");
+        }
+    });
+}
+
+/// The path of the sanitized file, next to the original with `_clean` added
+/// to its name. Files without an extension get `vag`.
+fn clean_path(file_path: &Path) -> PathBuf {
+    PathBuf::from(format!(
+        "{}_clean.{}",
+        file_path.with_extension("").to_string_lossy(),
+        file_path
+            .extension()
+            .map_or("vag".into(), |extension| extension.to_string_lossy()),
+    ))
+}
+
+/// Replaces the invalid shift/filter bytes of the blocks with the last valid
+/// one of the same channel.
+fn sanitized(bytes: &mut [u8], layout: &Layout) -> usize {
+    let mut last_valid = vec![0; layout.channels];
+    let mut changed = 0;
+    for (index, line) in bytes[layout.start..]
+        .chunks_mut(vag::BLOCK_SIZE)
+        .enumerate()
+    {
+        let last_valid = &mut last_valid[layout.channel(index * vag::BLOCK_SIZE)];
+        if line[0] == 0xff {
+            line[0] = *last_valid;
+            changed += 1;
+        } else {
+            *last_valid = line[0];
+        }
+    }
+    changed
+}
+
+#[test]
+fn sanitization() {
+    let mut file = include_bytes!("../tests/silence.vag").to_vec();
+    let header = VagHeader::parse(&mut Reader::new(&file)).unwrap();
+    let layout = Layout::detect(&header, &file).unwrap();
+    let changed = sanitized(&mut file, &layout);
+    assert_eq!(changed, 6);
+    assert_eq!(
+        format!("{:X}", md5::compute(file)),
+        "6B55C00C906E3D11165F0988981ECA1B"
+    );
+}
+
+#[test]
+fn interleaved() {
+    let layout = Layout {
+        start: vag::HEADER_SIZE,
+        channels: 2,
+        interleave: vag::BLOCK_SIZE,
+    };
+    let mut file = vec![0; vag::HEADER_SIZE];
+    for shift in [0x01, 0x02, 0xff, 0xff] {
+        file.extend([shift; vag::BLOCK_SIZE]);
+    }
+    assert_eq!(sanitized(&mut file, &layout), 2);
+    let shifts = file[vag::HEADER_SIZE..]
+        .chunks(vag::BLOCK_SIZE)
+        .map(|block| block[0])
+        .collect::<Vec<_>>();
+    assert_eq!(shifts, [0x01, 0x02, 0x01, 0x02]);
+}
+
+#[test]
+fn clean_paths() {
+    assert_eq!(
+        clean_path(Path::new("music/a.vag")),
+        Path::new("music/a_clean.vag")
+    );
+    assert_eq!(
+        clean_path(Path::new("music/a.bin")),
+        Path::new("music/a_clean.bin")
+    );
+    assert_eq!(
+        clean_path(Path::new("music/a")),
+        Path::new("music/a_clean.vag")
+    );
+}
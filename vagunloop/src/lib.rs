@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+
+use core::{
+    adpcm,
+    batch::JobArgs,
+    clap::{self, Parser},
+    input::InputArgs,
+    log::{error, info},
+    output::{Output, OutputArgs},
+    report::{self, ReportArgs},
+    vag::{self, Layout, VagHeader},
+    Reader,
+};
+
+#[derive(Parser)]
+#[clap(version)]
+pub struct Args {
+    /// The `vag` file to read from.
+    input: PathBuf,
+    /// Sets a loop starting at this sample instead of removing the loops,
+    /// rounded down to a whole block.
+    #[clap(long, requires = "loop_end")]
+    loop_start: Option<usize>,
+    /// The sample just after the end of the loop to set, rounded up to a whole block.
+    #[clap(long, requires = "loop_start")]
+    loop_end: Option<usize>,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    files: InputArgs,
+    #[clap(flatten)]
+    batch: JobArgs,
+    #[clap(flatten)]
+    report: ReportArgs,
+}
+
+/// Runs the tool with the arguments it was given.
+pub fn run(args: Args) {
+    args.report.start(env!("CARGO_PKG_NAME"));
+    let output = Output::new(&args.output, &args.input);
+
+    let files = core::get_files(&args.input, &args.files, &["vag"]);
+    core::batch::run(files, args.batch.jobs, |file_path| {
+        info!("Handling {file_path:?}");
+        let mut vag_bytes = match std::fs::read(file_path) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Unable to read file: {e}");
+                return;
+            }
+        };
+
+        let layout = match VagHeader::parse(&mut Reader::new(&vag_bytes))
+            .and_then(|header| Ok((Layout::detect(&header, &vag_bytes)?, header)))
+        {
+            Ok((layout, header)) => {
+                report::set("header", header);
+                report::set("layout", layout);
+                layout
+            }
+            Err(e) => {
+                error!("Invalid header, skipping: {e}");
+                return;
+            }
+        };
+
+        if let (Some(start), Some(end)) = (args.loop_start, args.loop_end) {
+            let looped = match adpcm::set_loop(
+                &mut vag_bytes[layout.start..],
+                layout.channels,
+                layout.interleave,
+                start..end,
+            ) {
+                Ok(looped) => looped,
+                Err(e) => {
+                    error!("Unable to set the loop, skipping: {e}");
+                    return;
+                }
+            };
+            report::set("loop_start", looped.start);
+            report::set("loop_end", looped.end);
+            if write(&output, file_path, "looped", &vag_bytes) {
+                info!("Looped samples {} to {}", looped.start, looped.end);
+            }
+            return;
+        }
+
+        let changed_chunks = unloop(&mut vag_bytes, &layout);
+        report::set("markers_removed", changed_chunks);
+
+        if changed_chunks == 0 {
+            info!("No markers found");
+        } else if write(&output, file_path, "unlooped", &vag_bytes) {
+            info!("Removed {changed_chunks} markers");
+        }
+    });
+}
+
+/// Writes the edited file next to the input with a suffix, returning whether it was written.
+fn write(output: &Output, file_path: &Path, suffix: &str, vag_bytes: &[u8]) -> bool {
+    let out_path = format!(
+        "{}_{suffix}.vag",
+        file_path.with_extension("").to_string_lossy()
+    );
+    let Some(out_path) = output.file(Path::new(&out_path)) else {
+        return false;
+    };
+    if let Err(e) = std::fs::write(&out_path, vag_bytes) {
+        error!("Unable to write output file: {e}");
+        return false;
+    }
+    true
+}
+
+fn unloop(vag_bytes: &mut [u8], layout: &Layout) -> usize {
+    let mut changed_chunks = 0;
+    for chunk in vag_bytes[layout.start..].chunks_mut(vag::BLOCK_SIZE) {
+        if vag::remove_loop(chunk) {
+            changed_chunks += 1;
+        }
+    }
+    changed_chunks
+}
+
+#[cfg(test)]
+fn layout(file: &[u8]) -> Layout {
+    let header = VagHeader::parse(&mut Reader::new(file)).unwrap();
+    Layout::detect(&header, file).unwrap()
+}
+
+#[test]
+fn long_header() {
+    let file = include_bytes!("../tests/silence.vag");
+    assert_eq!(layout(file).start, vag::LONG_HEADER_SIZE);
+
+    // The same data after a standard header.
+    let mut file = file.to_vec();
+    file.drain(vag::HEADER_SIZE..vag::LONG_HEADER_SIZE);
+    let layout = layout(&file);
+    assert_eq!(layout.start, vag::HEADER_SIZE);
+    assert_eq!(unloop(&mut file, &layout), 1575);
+}
+
+#[test]
+fn count_changes() {
+    let mut file = include_bytes!("../tests/silence.vag").to_vec();
+    let layout = layout(&file);
+    let changes = unloop(&mut file, &layout);
+    assert_eq!(changes, 1575);
+}
+
+#[test]
+fn checksum() {
+    let mut file = include_bytes!("../tests/silence.vag").to_vec();
+    let layout = layout(&file);
+    unloop(&mut file, &layout);
+    assert_eq!(
+        format!("{:X}", md5::compute(file)),
+        "0722A447B5600CB563166ED2ECB582AD"
+    );
+}
+
+#[test]
+fn interleaved() {
+    let mut body = vec![0; 3 * vag::BLOCK_SIZE];
+    body[1] = vag::FLAG_LOOP_START;
+    body[2 * vag::BLOCK_SIZE + 1] = vag::FLAG_LOOP_END;
+    let header = VagHeader {
+        magic: vag::MAGIC_INTERLEAVED,
+        interleave: vag::BLOCK_SIZE as u32,
+        ..VagHeader::new(6 * vag::BLOCK_SIZE as u32, 44100)
+    };
+    let mut file = vec![];
+    header.write(&mut file);
+    // Flags in the padding before the data aren't markers.
+    file.resize(vag::INTERLEAVED_START, vag::FLAG_LOOP_START);
+    file.extend(core::adpcm::interleave(
+        &[body.clone(), body],
+        vag::BLOCK_SIZE,
+    ));
+
+    let layout = layout(&file);
+    assert_eq!(unloop(&mut file, &layout), 4);
+    for channel in layout.deinterleave(&file) {
+        assert_eq!(core::adpcm::decode(&channel).loop_start, None);
+    }
+}